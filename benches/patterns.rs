@@ -0,0 +1,124 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::thread;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use phonenumber::{self, Type, PhoneNumber};
+use phonenumber::metadata::{DATABASE, Descriptor};
+
+const TYPES: &[Type] = &[
+	Type::FixedLine, Type::Mobile, Type::TollFree, Type::PremiumRate,
+	Type::SharedCost, Type::PersonalNumber, Type::Voip, Type::Pager, Type::Uan,
+	Type::Voicemail,
+];
+
+/// The descriptors of the number types of a region, and its example numbers
+/// in E.164.
+struct Region {
+	descriptors: Vec<&'static Descriptor>,
+	examples:    Vec<(String, String)>,
+}
+
+/// Collect the descriptors and examples of every region of the database.
+fn regions() -> Vec<Region> {
+	let mut result = Vec::new();
+
+	for code in 1 ..= 999 {
+		for meta in DATABASE.by_code(&code).into_iter().flatten() {
+			let descriptors = TYPES.iter()
+				.filter_map(|&kind| meta.descriptors().get(kind))
+				.collect::<Vec<_>>();
+
+			let examples = descriptors.iter()
+				.filter_map(|d| d.example())
+				.map(|e| (e.to_owned(), format!("+{}{}", code, e)))
+				.collect();
+
+			result.push(Region { descriptors, examples });
+		}
+	}
+
+	result
+}
+
+/// Classify every example against every descriptor of its region.
+fn classify(regions: &[Region]) -> usize {
+	let mut matches = 0;
+
+	for region in regions {
+		for (example, _) in &region.examples {
+			matches += region.descriptors.iter().filter(|d| d.is_match(example)).count();
+		}
+	}
+
+	matches
+}
+
+/// Parse every example.
+fn parse(regions: &[Region]) -> Vec<PhoneNumber> {
+	regions.iter()
+		.flat_map(|r| &r.examples)
+		.filter_map(|(_, e164)| phonenumber::parse(None, e164).ok())
+		.collect()
+}
+
+/// Run the given function on several threads at once.
+fn spread<F: Fn() + Sync>(threads: usize, f: F) {
+	thread::scope(|scope| {
+		for _ in 0 .. threads {
+			scope.spawn(&f);
+		}
+	});
+}
+
+/// Match, parse and validate the example numbers of every region through the
+/// public API, on one thread and on several at once, which share the compiled
+/// patterns of the database.
+fn patterns(c: &mut Criterion) {
+	let regions = regions();
+	let numbers = parse(&regions);
+
+	assert!(classify(&regions) > 0);
+	assert!(numbers.iter().any(phonenumber::is_valid));
+
+	let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).clamp(2, 4);
+
+	let mut group = c.benchmark_group("patterns");
+	group.sample_size(10);
+
+	group.bench_function("is_match", |b| b.iter(||
+		classify(&regions)));
+
+	group.bench_function("parse", |b| b.iter(||
+		parse(&regions)));
+
+	group.bench_function("is_valid", |b| b.iter(||
+		numbers.iter().filter(|n| phonenumber::is_valid(n)).count()));
+
+	group.bench_function(format!("is_match/{} threads", threads), |b| b.iter(||
+		spread(threads, || { classify(&regions); })));
+
+	group.bench_function(format!("parse/{} threads", threads), |b| b.iter(||
+		spread(threads, || { parse(&regions); })));
+
+	group.bench_function(format!("is_valid/{} threads", threads), |b| b.iter(||
+		spread(threads, || { numbers.iter().filter(|n| phonenumber::is_valid(n)).count(); })));
+
+	group.finish();
+}
+
+criterion_group!(benches, patterns);
+criterion_main!(benches);
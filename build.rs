@@ -1,6 +1,6 @@
 use std::path::Path;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{self, BufReader, BufWriter, Write};
 use std::env;
 
 extern crate thiserror;
@@ -10,26 +10,168 @@ extern crate quick_xml as xml;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
-extern crate bincode;
-
-use bincode::Options;
+extern crate serde_json;
 
 #[path = "src/metadata/loader.rs"]
+#[allow(dead_code)]
 mod loader;
 
 #[path = "src/error.rs"]
+#[allow(dead_code)]
 mod error;
 
 fn main() {
-	let metadata = loader::load(BufReader::new(
+	let loader::Loaded { territories, version } = loader::load_versioned(BufReader::new(
 		File::open("assets/PhoneNumberMetadata.xml")
 			.expect("could not open metadata file")))
 				.expect("failed to load metadata");
 
 	let mut out = BufWriter::new(File::create(
-		&Path::new(&env::var("OUT_DIR").unwrap()).join("database.bin"))
-			.expect("could not create database file"));
+		Path::new(&env::var("OUT_DIR").unwrap()).join("metadata.rs"))
+			.expect("could not create metadata file"));
+
+	// The bundled metadata is the libphonenumber release in the build metadata
+	// of the crate version.
+	let version = version.or_else(|| env::var("CARGO_PKG_VERSION").ok()
+		.and_then(|v| v.split('+').nth(1).map(String::from)));
+
+	writeln!(out, "pub(crate) const VERSION: Option<&str> = {:?};", version)
+		.and_then(|_| generate(&mut out, territories))
+		.expect("failed to generate metadata");
+}
+
+/// Generate the static tables for the given metadata, see
+/// `src/metadata/generated.rs` for the types.
+///
+/// Patterns are written as strings, they're only compiled into regexes at
+/// runtime, the first time their region is accessed.
+fn generate<W: Write>(out: &mut W, metadata: Vec<loader::Metadata>) -> io::Result<()> {
+	// Regions are sorted by ID to be binary searched, the non-geographical
+	// entity "001" appears once per country code.
+	let mut order = (0 .. metadata.len()).collect::<Vec<_>>();
+	order.sort_by(|&a, &b| metadata[a].id.cmp(&metadata[b].id));
+
+	let mut position = vec![0; metadata.len()];
+	for (index, &original) in order.iter().enumerate() {
+		position[original] = index;
+	}
+
+	// Regions for a code keep the order of the XML, with the main country first.
+	let mut by_code = Vec::<(u16, Vec<usize>)>::new();
+	for (original, meta) in metadata.iter().enumerate() {
+		let code = meta.country_code.expect("missing country code");
+
+		let index = if let Some(index) = by_code.iter().position(|&(c, _)| c == code) { index } else {
+			by_code.push((code, Vec::new()));
+			by_code.len() - 1
+		};
+
+		if meta.main_country_for_code {
+			by_code[index].1.insert(0, position[original]);
+		}
+		else {
+			by_code[index].1.push(position[original]);
+		}
+	}
+
+	by_code.sort_by_key(|&(code, _)| code);
+
+	writeln!(out, "pub(crate) const COUNT: usize = {};", metadata.len())?;
+	writeln!(out)?;
+
+	writeln!(out, "pub(crate) static REGIONS: [Region; COUNT] = [")?;
+	for &original in &order {
+		region(out, &metadata[original])?;
+	}
+	writeln!(out, "];")?;
+	writeln!(out)?;
+
+	writeln!(out, "pub(crate) static BY_CODE: [(u16, &[u16]); {}] = [", by_code.len())?;
+	for (code, indices) in &by_code {
+		writeln!(out, "\t({}, &{:?}),", code, indices)?;
+	}
+	writeln!(out, "];")
+}
+
+fn region<W: Write>(out: &mut W, meta: &loader::Metadata) -> io::Result<()> {
+	writeln!(out, "\tRegion {{")?;
+
+	write!(out, "\t\tgeneral: ")?;
+	descriptor(out, meta.general.as_ref().expect("missing general descriptor"))?;
+
+	let descriptors = [
+		("fixed_line", &meta.fixed_line),
+		("mobile", &meta.mobile),
+		("toll_free", &meta.toll_free),
+		("premium_rate", &meta.premium_rate),
+		("shared_cost", &meta.shared_cost),
+		("personal_number", &meta.personal_number),
+		("voip", &meta.voip),
+		("pager", &meta.pager),
+		("uan", &meta.uan),
+		("emergency", &meta.emergency),
+		("voicemail", &meta.voicemail),
+		("short_code", &meta.short_code),
+		("standard_rate", &meta.standard_rate),
+		("carrier", &meta.carrier),
+		("no_international", &meta.no_international),
+		("sms_services", &meta.sms_services),
+		("expanded_emergency", &meta.expanded_emergency),
+		("area_code_optional", &meta.area_code_optional),
+	];
+
+	for (name, desc) in &descriptors {
+		if let Some(desc) = desc {
+			write!(out, "\t\t{}: Some(", name)?;
+			descriptor(out, desc)?;
+			writeln!(out, "\t\t),")?;
+		}
+		else {
+			writeln!(out, "\t\t{}: None,", name)?;
+		}
+	}
+
+	writeln!(out, "\t\tid: {:?},", meta.id.as_ref().unwrap())?;
+	writeln!(out, "\t\tcountry_code: {},", meta.country_code.unwrap())?;
+	writeln!(out, "\t\tinternational_prefix: {:?},", meta.international_prefix)?;
+	writeln!(out, "\t\tpreferred_international_prefix: {:?},", meta.preferred_international_prefix)?;
+	writeln!(out, "\t\tnational_prefix: {:?},", meta.national_prefix)?;
+	writeln!(out, "\t\tpreferred_extension_prefix: {:?},", meta.preferred_extension_prefix)?;
+	writeln!(out, "\t\tnational_prefix_for_parsing: {:?},", meta.national_prefix_for_parsing)?;
+	writeln!(out, "\t\tnational_prefix_transform_rule: {:?},", meta.national_prefix_transform_rule)?;
+
+	for (name, formats) in &[("formats", &meta.formats), ("international_formats", &meta.international_formats)] {
+		writeln!(out, "\t\t{}: &[", name)?;
+		for format in formats.iter() {
+			writeln!(out, "\t\t\tFormat {{")?;
+			writeln!(out, "\t\t\t\tpattern: {:?},", format.pattern.as_ref().expect("missing format pattern"))?;
+			writeln!(out, "\t\t\t\tformat: {:?},", format.format.as_ref().expect("missing format"))?;
+			writeln!(out, "\t\t\t\tleading_digits: &{:?},", format.leading_digits)?;
+			writeln!(out, "\t\t\t\tnational_prefix: {:?},", format.national_prefix_formatting_rule)?;
+			writeln!(out, "\t\t\t\tnational_prefix_optional: {:?},", format.national_prefix_optional_when_formatting)?;
+			writeln!(out, "\t\t\t\tdomestic_carrier: {:?},", format.domestic_carrier)?;
+			writeln!(out, "\t\t\t}},")?;
+		}
+		writeln!(out, "\t\t],")?;
+	}
+
+	writeln!(out, "\t\tmain_country_for_code: {:?},", meta.main_country_for_code)?;
+	writeln!(out, "\t\tleading_digits: {:?},", meta.leading_digits)?;
+	writeln!(out, "\t\tmobile_number_portable: {:?},", meta.mobile_number_portable)?;
+	writeln!(out, "\t\tsame_mobile_and_fixed_line_pattern: {:?},", meta.same_mobile_and_fixed_line_pattern)?;
+	writeln!(out, "\t\tnational_prefix_formatting_rule: {:?},", meta.national_prefix_formatting_rule)?;
+	writeln!(out, "\t\tnational_prefix_optional_when_formatting: {:?},", meta.national_prefix_optional_when_formatting)?;
+	writeln!(out, "\t\tcarrier_code_formatting_rule: {:?},", meta.carrier_code_formatting_rule)?;
+	writeln!(out, "\t\treferences: &{:?},", meta.references)?;
+
+	writeln!(out, "\t}},")
+}
 
-	bincode::options().with_varint_encoding().serialize_into(&mut out, &metadata)
-		.expect("failed to serialize database");
+fn descriptor<W: Write>(out: &mut W, desc: &loader::Descriptor) -> io::Result<()> {
+	writeln!(out, "Descriptor {{")?;
+	writeln!(out, "\t\t\tnational_number: {:?},", desc.national_number.as_ref().expect("missing national number"))?;
+	writeln!(out, "\t\t\tpossible_length: &{:?},", desc.possible_length)?;
+	writeln!(out, "\t\t\tpossible_local_length: &{:?},", desc.possible_local_length)?;
+	writeln!(out, "\t\t\texample: {:?},", desc.example)?;
+	writeln!(out, "\t\t}},")
 }
@@ -0,0 +1,56 @@
+use std::env;
+use std::thread;
+use std::time::Instant;
+
+extern crate phonenumber;
+use phonenumber::{country, Mode};
+
+const NUMBERS: &[(Option<country::Id>, &str)] = &[
+	(Some(country::US), "(650) 253-0000"),
+	(Some(country::GB), "020 7031 3000"),
+	(Some(country::DE), "030 1234567"),
+	(Some(country::NZ), "03-331 6005"),
+	(Some(country::BR), "012 3121286979"),
+	(Some(country::IT), "02 1234 5678"),
+	(Some(country::JP), "03-1234-5678"),
+	(None, "+61 2 9876 5432"),
+	(None, "+33 1 42 68 53 00"),
+	(None, "+7 495 123-45-67"),
+	(None, "+91 98765 43210"),
+	(None, "+55 11 98765 4321"),
+];
+
+/// Parse, validate and format the sample numbers over and over on the given
+/// number of threads, printing the throughput.
+fn main() {
+	let iterations = env::args().nth(1).and_then(|v| v.parse().ok()).unwrap_or(2_000usize);
+	let max        = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).max(4);
+
+	// Load the database before timing anything.
+	phonenumber::parse(None, "+1 650 253 0000").unwrap();
+
+	let mut threads = 1;
+	while threads <= max {
+		let start = Instant::now();
+
+		thread::scope(|scope| {
+			for _ in 0 .. threads {
+				scope.spawn(|| {
+					for _ in 0 .. iterations {
+						for &(country, string) in NUMBERS {
+							let number = phonenumber::parse(country, string).unwrap();
+							assert!(phonenumber::is_valid(&number));
+							number.format().mode(Mode::International).to_string();
+						}
+					}
+				});
+			}
+		});
+
+		let elapsed = start.elapsed();
+		let total   = threads * iterations * NUMBERS.len();
+
+		println!("{:>3} threads: {:>10.0} numbers/s", threads, total as f64 / elapsed.as_secs_f64());
+		threads *= 2;
+	}
+}
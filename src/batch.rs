@@ -0,0 +1,271 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Deref;
+use std::vec;
+use std::thread;
+
+use fnv::FnvHashMap;
+
+use crate::metadata::{DATABASE, Database};
+use crate::phone_number::PhoneNumber;
+use crate::country;
+use crate::parser;
+use crate::error;
+
+/// The number of rows parsed by each thread at a time when streaming.
+const CHUNK: usize = 1024;
+
+/// Parser for many phone numbers at once, optionally spread over several
+/// threads.
+///
+/// Every row is parsed with `parse_with`, so results are the same as parsing
+/// the rows one by one, and always in the same order as the rows whatever the
+/// number of threads. The rows share the `Database`, whose regions are
+/// compiled once on first use, but each row still allocates its own
+/// `PhoneNumber`, there are no scratch buffers kept between rows.
+#[derive(Copy, Clone, Debug)]
+pub struct BatchParser<'d> {
+	database: &'d Database,
+	threads:  usize,
+}
+
+/// The results of a batch, in the same order as the rows.
+#[derive(Clone, Debug)]
+pub struct Batch(Vec<Result<PhoneNumber, error::Parse>>);
+
+/// Iterator over the results of rows parsed a chunk at a time, see
+/// `BatchParser::stream`.
+#[derive(Debug)]
+pub struct Stream<'d, I: Iterator> {
+	parser:  BatchParser<'d>,
+	rows:    I,
+	chunk:   Vec<I::Item>,
+	results: vec::IntoIter<Result<PhoneNumber, error::Parse>>,
+}
+
+impl BatchParser<'static> {
+	/// Create a batch parser using the default `Database`, on the current
+	/// thread.
+	pub fn new() -> Self {
+		BatchParser::with_database(&DATABASE)
+	}
+}
+
+impl Default for BatchParser<'static> {
+	fn default() -> Self {
+		BatchParser::new()
+	}
+}
+
+impl<'d> BatchParser<'d> {
+	/// Create a batch parser using a specific `Database`, on the current thread.
+	pub fn with_database(database: &'d Database) -> Self {
+		BatchParser {
+			database,
+			threads: 1,
+		}
+	}
+
+	/// Define the number of threads to spread the rows over, zero and one both
+	/// mean the current thread.
+	pub fn threads(mut self, threads: usize) -> Self {
+		self.threads = threads.max(1);
+		self
+	}
+
+	/// Parse the given rows, keeping every result.
+	///
+	/// The rows are collected first and split in one contiguous part per
+	/// thread, so the threads are only spawned once.
+	pub fn parse<I, S>(&self, rows: I) -> Batch
+		where I: IntoIterator<Item = (Option<country::Id>, S)>,
+		      S: AsRef<str> + Sync,
+	{
+		Batch(self.chunk(&rows.into_iter().collect::<Vec<_>>()))
+	}
+
+	/// Parse the given rows lazily, only a chunk of rows and their results are
+	/// held at any time, so inputs of any size can be streamed through.
+	///
+	/// Each chunk holds `1024` rows per thread, and the threads are spawned
+	/// anew for every chunk, so prefer `parse` when the rows fit in memory.
+	pub fn stream<I, S>(&self, rows: I) -> Stream<'d, I::IntoIter>
+		where I: IntoIterator<Item = (Option<country::Id>, S)>,
+		      S: AsRef<str> + Sync,
+	{
+		Stream {
+			parser:  *self,
+			rows:    rows.into_iter(),
+			chunk:   Vec::with_capacity(CHUNK * self.threads),
+			results: Vec::new().into_iter(),
+		}
+	}
+
+	/// Parse a chunk of rows, split in contiguous parts, one per thread, which
+	/// are joined back in order.
+	fn chunk<S: AsRef<str> + Sync>(&self, rows: &[(Option<country::Id>, S)]) -> Vec<Result<PhoneNumber, error::Parse>> {
+		let parse = |rows: &[(Option<country::Id>, S)]| -> Vec<Result<PhoneNumber, error::Parse>> {
+			rows.iter()
+				.map(|(country, string)| parser::parse_with(self.database, *country, string))
+				.collect()
+		};
+
+		if self.threads == 1 || rows.len() < 2 {
+			return parse(rows);
+		}
+
+		let size = rows.len().div_ceil(self.threads);
+		let mut results = Vec::with_capacity(rows.len());
+
+		thread::scope(|scope| {
+			let parse   = &parse;
+			let workers = rows.chunks(size)
+				.map(|chunk| scope.spawn(move || parse(chunk)))
+				.collect::<Vec<_>>();
+
+			for worker in workers {
+				results.extend(worker.join().unwrap());
+			}
+		});
+
+		results
+	}
+}
+
+impl<'d, I, S> Iterator for Stream<'d, I>
+	where I: Iterator<Item = (Option<country::Id>, S)>,
+	      S: AsRef<str> + Sync,
+{
+	type Item = Result<PhoneNumber, error::Parse>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if let Some(result) = self.results.next() {
+			return Some(result);
+		}
+
+		let size = self.chunk.capacity();
+		self.chunk.clear();
+		self.chunk.extend(self.rows.by_ref().take(size));
+
+		if self.chunk.is_empty() {
+			return None;
+		}
+
+		self.results = self.parser.chunk(&self.chunk).into_iter();
+		self.results.next()
+	}
+}
+
+impl Batch {
+	/// Get the results.
+	pub fn into_inner(self) -> Vec<Result<PhoneNumber, error::Parse>> {
+		self.0
+	}
+
+	/// Get the number of rows that parsed.
+	pub fn parsed(&self) -> usize {
+		self.0.iter().filter(|r| r.is_ok()).count()
+	}
+
+	/// Get the rows that failed, along with their index.
+	pub fn errors(&self) -> impl Iterator<Item = (usize, &error::Parse)> {
+		self.0.iter().enumerate()
+			.filter_map(|(i, r)| r.as_ref().err().map(|e| (i, e)))
+	}
+
+	/// Get the number of rows that failed, by the stage they failed in.
+	pub fn failures(&self) -> FnvHashMap<error::Stage, usize> {
+		let mut failures = FnvHashMap::default();
+
+		for (_, error) in self.errors() {
+			*failures.entry(error.stage()).or_insert(0) += 1;
+		}
+
+		failures
+	}
+}
+
+impl Deref for Batch {
+	type Target = [Result<PhoneNumber, error::Parse>];
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::batch::{BatchParser, CHUNK};
+	use crate::metadata::Database;
+	use crate::parser;
+	use crate::country;
+	use crate::error;
+
+	#[test]
+	fn parse() {
+		let rows = [
+			(Some(country::US), "(650) 253-0000"),
+			(None, "+44 20 7031 3000"),
+			(Some(country::DE), "call me"),
+			(Some(country::NZ), "03-331 6005"),
+			(Some(country::US), "011 9991 123456"),
+		];
+
+		let single = BatchParser::new().parse(rows.iter().cloned());
+		let multi  = BatchParser::new().threads(4).parse(rows.iter().cloned());
+
+		for (index, &(country, string)) in rows.iter().enumerate() {
+			assert_eq!(parser::parse(country, string).ok().as_ref(), single[index].as_ref().ok());
+			assert_eq!(single[index].as_ref().ok(), multi[index].as_ref().ok());
+		}
+
+		assert_eq!(3, multi.parsed());
+		assert_eq!(vec![2, 4], multi.errors().map(|(i, _)| i).collect::<Vec<_>>());
+
+		let failures = multi.failures();
+		assert_eq!(Some(&1), failures.get(&error::Stage::Extraction));
+		assert_eq!(Some(&1), failures.get(&error::Stage::CountryCode));
+	}
+
+	#[test]
+	fn missing() {
+		let database = Database::from_json(r#"{"territories":[{"id":"US","country_code":1,"general":{"national_number":"[2-9]\\d{9}"}}]}"#).unwrap();
+
+		// A default region missing from the database doesn't matter when the
+		// number has its own country code.
+		let rows  = [(Some(country::GB), "+1 650 253 0000"), (Some(country::GB), "020 7031 3000")];
+		let batch = BatchParser::with_database(&database).parse(rows.iter().cloned());
+
+		assert_eq!(parser::parse_with(&database, None, "+1 650 253 0000").ok().as_ref(), batch[0].as_ref().ok());
+		assert_eq!(error::Stage::CountryCode, batch[1].as_ref().unwrap_err().stage());
+	}
+
+	#[test]
+	fn stream() {
+		// Owned rows spanning several chunks come out in order.
+		let rows = (0 .. CHUNK * 2 + 10)
+			.map(|i| (Some(country::US), format!("650 253 {:04}", i)))
+			.collect::<Vec<_>>();
+
+		let results = BatchParser::new().threads(2).stream(rows.iter().cloned())
+			.collect::<Vec<_>>();
+
+		assert_eq!(rows.len(), results.len());
+
+		for ((country, string), result) in rows.iter().zip(&results) {
+			assert_eq!(parser::parse(*country, string).ok().as_ref(), result.as_ref().ok());
+		}
+	}
+}
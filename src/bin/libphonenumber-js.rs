@@ -0,0 +1,44 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::env;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+extern crate phonenumber;
+use phonenumber::metadata::{DATABASE, Database, export};
+
+/// Write the metadata in the `metadata.json` layout of libphonenumber-js to
+/// the standard output, from the given `PhoneNumberMetadata.xml` or the
+/// bundled one.
+fn main() {
+	let database = match env::args().nth(1) {
+		Some(path) =>
+			Database::load(&path).unwrap_or_else(|e| {
+				eprintln!("{}: {}", path, e);
+				process::exit(1);
+			}),
+
+		None =>
+			DATABASE.clone(),
+	};
+
+	let stdout  = io::stdout();
+	let mut out = BufWriter::new(stdout.lock());
+
+	if let Err(e) = export::libphonenumber_js(&database, &mut out).and_then(|_| Ok(out.flush()?)) {
+		eprintln!("{}", e);
+		process::exit(1);
+	}
+}
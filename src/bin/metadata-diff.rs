@@ -0,0 +1,137 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+extern crate phonenumber;
+use phonenumber::country;
+use phonenumber::metadata::{DATABASE, Database, diff};
+
+/// Report the differences between two databases, each either "bundled", a
+/// JSON file written by `Database::to_json`, or a `PhoneNumberMetadata.xml`.
+///
+/// The corpus has a number per line, optionally preceded by the default
+/// country to parse it with and a tab; without a corpus the example numbers of
+/// both databases are used.
+fn main() {
+	let args = env::args().skip(1).collect::<Vec<_>>();
+
+	if args.len() < 2 || args.len() > 3 {
+		eprintln!("usage: metadata-diff OLD NEW [CORPUS]");
+		process::exit(2);
+	}
+
+	let old = database(&args[0]);
+	let new = database(&args[1]);
+
+	let corpus = match args.get(2) {
+		Some(path) =>
+			corpus(path),
+
+		None => {
+			let mut corpus = diff::examples(&old);
+
+			for example in diff::examples(&new) {
+				if !corpus.contains(&example) {
+					corpus.push(example);
+				}
+			}
+
+			corpus
+		}
+	};
+
+	let report = diff::diff(&old, &new, corpus);
+
+	let stdout  = io::stdout();
+	let mut out = BufWriter::new(stdout.lock());
+
+	if let Err(e) = write(&mut out, &old, &new, &report).and_then(|_| out.flush()) {
+		eprintln!("{}", e);
+		process::exit(1);
+	}
+}
+
+fn database(name: &str) -> Database {
+	let database = if name == "bundled" {
+		Ok(DATABASE.clone())
+	}
+	else if name.ends_with(".json") {
+		fs::read_to_string(name).map_err(Into::into).and_then(Database::from_json)
+	}
+	else {
+		Database::load(name)
+	};
+
+	database.unwrap_or_else(|e| {
+		eprintln!("{}: {}", name, e);
+		process::exit(1);
+	})
+}
+
+fn corpus(path: &str) -> Vec<(Option<country::Id>, String)> {
+	let content = fs::read_to_string(path).unwrap_or_else(|e| {
+		eprintln!("{}: {}", path, e);
+		process::exit(1);
+	});
+
+	content.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(|line| match line.find('\t') {
+			Some(index) =>
+				(line[.. index].trim().parse().ok(), line[index + 1 ..].trim().to_owned()),
+
+			None =>
+				(None, line.to_owned()),
+		})
+		.collect()
+}
+
+fn write<W: Write>(out: &mut W, old: &Database, new: &Database, report: &diff::Report) -> io::Result<()> {
+	writeln!(out, "{} -> {}", old.version(), new.version())?;
+
+	if report.is_empty() {
+		return writeln!(out, "no differences");
+	}
+
+	if !report.regions().is_empty() {
+		writeln!(out)?;
+		writeln!(out, "Regions:")?;
+
+		for change in report.regions() {
+			writeln!(out, "  {}", change)?;
+		}
+	}
+
+	if !report.numbers().is_empty() {
+		writeln!(out)?;
+		writeln!(out, "Numbers:")?;
+
+		for (region, summary) in report.summaries().iter().filter(|(_, s)| !s.is_empty()) {
+			writeln!(out, "  {}: {}", region, summary)?;
+		}
+
+		writeln!(out)?;
+
+		for difference in report.numbers() {
+			writeln!(out, "  {}", difference)?;
+		}
+	}
+
+	Ok(())
+}
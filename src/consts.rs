@@ -190,8 +190,7 @@ lazy_static! {
 		let mut map = FnvHashMap::default();
 
 		for &c in ALPHA_MAPPINGS.keys() {
-			map.insert(c, c);
-			map.insert(c.to_lowercase().next().unwrap(), c);
+			map.insert(c, c.to_ascii_uppercase());
 		}
 
 		map.extend(ASCII_MAPPINGS.iter());
@@ -307,6 +306,38 @@ lazy_static! {
 	/// preferences.
   pub static ref DEFAULT_EXTN_PREFIX: String = String::from(" ext. ");
 
+	/// Words used to introduce an extension, by language. The first word of
+	/// each language is the one used when formatting, any trailing full stop is
+	/// optional when parsing.
+	pub static ref EXTN_KEYWORDS: FnvHashMap<&'static str, &'static [&'static str]> = {
+		let mut map = FnvHashMap::default();
+		map.insert("en", &["ext.", "extension", "extn"][..]);
+		map.insert("de", &["Durchwahl", "Dw.", "App."][..]);
+		map.insert("fr", &["poste"][..]);
+		map.insert("it", &["int.", "interno"][..]);
+		map.insert("es", &["ext.", "anexo", "extensión"][..]);
+		map.insert("pt", &["ramal"][..]);
+		map.insert("nl", &["toestel", "tst."][..]);
+		map.insert("pl", &["wew."][..]);
+		map.insert("ru", &["доб.", "добавочный"][..]);
+		map.insert("uk", &["дод.", "додатковий"][..]);
+		map.insert("ja", &["内線"][..]);
+		map.insert("zh", &["分机", "分機", "转", "轉"][..]);
+		map.insert("ko", &["내선"][..]);
+		map
+	};
+
+	/// Alternation of the extension keywords written in a cased script, which
+	/// have to be separated from the number so they aren't matched inside
+	/// words, for use in extension parsing patterns.
+	pub static ref EXTN_KEYWORDS_PATTERN: String =
+		keywords(|word| word.chars().any(|c| c.is_lowercase() || c.is_uppercase()));
+
+	/// Alternation of the other extension keywords, which can follow the number
+	/// directly.
+	pub static ref EXTN_UNSPACED_KEYWORDS_PATTERN: String =
+		keywords(|word| !word.chars().any(|c| c.is_lowercase() || c.is_uppercase()));
+
 	/// Pattern to capture digits used in an extension. Places a maximum length
 	/// of "7" for an extension.
   pub static ref CAPTURING_EXTN_DIGITS: String = format!("({}{{0,7}})", *DIGITS);
@@ -319,9 +350,11 @@ lazy_static! {
 	/// matching. Here we allow "comma" and "semicolon" as possible extension
 	/// indicators. When matching, these are hardly ever used to indicate this.
 	pub static ref EXTN_PATTERNS_FOR_PARSING: String =
-		format!(r"{rfc3966_extn_prefix}{capturing_extn_digits}|[ \x{{00A0}}\t,]*(?:e?xt(?:ensi(?:o\x{{0301}}?|\x{{00F3}}))?n?|\x{{FF45}}?\x{{FF58}}\x{{FF54}}\x{{FF4E}}?|[{symbols}]|int|anexo|\x{{FF49}}\x{{FF4E}}\x{{FF54}})[:\.\x{{FF0E}}]?[ \x{{00A0}}\t,-]*{capturing_extn_digits}#?|[- ]+({digits}{{1,5}})#",
+		format!(r"{rfc3966_extn_prefix}{capturing_extn_digits}|[ \x{{00A0}}\t,]*(?:e?xt(?:ensi(?:o\x{{0301}}?|\x{{00F3}}))?n?|\x{{FF45}}?\x{{FF58}}\x{{FF54}}\x{{FF4E}}?|[{symbols}]|int|anexo|\x{{FF49}}\x{{FF4E}}\x{{FF54}})[:\.\x{{FF0E}}]?[ \x{{00A0}}\t,-]*{capturing_extn_digits}#?|[ \x{{00A0}}\t,]+(?:{keywords})[:\.\x{{FF0E}}]?[ \x{{00A0}}\t,-]*({digits}{{1,7}})#?|[ \x{{00A0}}\t,]*(?:{unspaced})[:\x{{FF1A}}]?[ \x{{00A0}}\t,-]*({digits}{{1,7}})#?|[- ]+({digits}{{1,5}})#",
 			rfc3966_extn_prefix = RFC3966_EXTN_PREFIX,
 			capturing_extn_digits = *CAPTURING_EXTN_DIGITS,
+			keywords = *EXTN_KEYWORDS_PATTERN,
+			unspaced = *EXTN_UNSPACED_KEYWORDS_PATTERN,
 			symbols = r",;x\x{FF58}#\x{FF03}~\x{FF5E}",
 			digits = *DIGITS);
 
@@ -331,9 +364,11 @@ lazy_static! {
 	///
 	/// One-character symbols that can be used to indicate an extension.
 	pub static ref EXTN_PATTERNS_FOR_MATCHING: String =
-		format!(r"{rfc3966_extn_prefix}{capturing_extn_digits}|[ \x{{00A0}}\t,]*(?:e?xt(?:ensi(?:o\x{{0301}}?|\x{{00F3}}))?n?|\x{{FF45}}?\x{{FF58}}\x{{FF54}}\x{{FF4E}}?|[{symbols}]|int|anexo|\x{{FF49}}\x{{FF4E}}\x{{FF54}})[:\.\x{{FF0E}}]?[ \x{{00A0}}\t,-]*{capturing_extn_digits}#?|[- ]+({digits}{{1,5}})#",
+		format!(r"{rfc3966_extn_prefix}{capturing_extn_digits}|[ \x{{00A0}}\t,]*(?:e?xt(?:ensi(?:o\x{{0301}}?|\x{{00F3}}))?n?|\x{{FF45}}?\x{{FF58}}\x{{FF54}}\x{{FF4E}}?|[{symbols}]|int|anexo|\x{{FF49}}\x{{FF4E}}\x{{FF54}})[:\.\x{{FF0E}}]?[ \x{{00A0}}\t,-]*{capturing_extn_digits}#?|[ \x{{00A0}}\t,]+(?:{keywords})[:\.\x{{FF0E}}]?[ \x{{00A0}}\t,-]*({digits}{{1,7}})#?|[ \x{{00A0}}\t,]*(?:{unspaced})[:\x{{FF1A}}]?[ \x{{00A0}}\t,-]*({digits}{{1,7}})#?|[- ]+({digits}{{1,5}})#",
 			rfc3966_extn_prefix = RFC3966_EXTN_PREFIX,
 			capturing_extn_digits = *CAPTURING_EXTN_DIGITS,
+			keywords = *EXTN_KEYWORDS_PATTERN,
+			unspaced = *EXTN_UNSPACED_KEYWORDS_PATTERN,
 			symbols = r"x\x{FF58}#\x{FF03}~\x{FF5E}",
 			digits = *DIGITS);
 
@@ -374,3 +409,16 @@ lazy_static! {
   pub static ref FIRST_GROUP_ONLY_PREFIX: Regex =
 		Regex::new(r"\(?\$1\)?").unwrap();
 }
+
+/// Alternation of the extension keywords matching the filter, longest first,
+/// with any trailing full stop left to the patterns using it.
+fn keywords<F: Fn(&str) -> bool>(filter: F) -> String {
+	EXTN_KEYWORDS.values()
+		.flat_map(|words| words.iter())
+		.map(|word| word.trim_end_matches('.'))
+		.filter(|word| filter(word))
+		.map(regex::escape)
+		.unique()
+		.sorted_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)))
+		.join("|")
+}
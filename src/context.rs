@@ -0,0 +1,98 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::country;
+use crate::formatter::Mode;
+use crate::phone_number::PhoneNumber;
+
+/// Where a phone number is parsed or formatted from, used to complete local
+/// numbers with the area code of the caller and to format numbers in the same
+/// area in their short local form.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Context {
+	country:   country::Id,
+	area_code: Option<String>,
+}
+
+impl Context {
+	/// Create a context for the given country, without any area code.
+	pub fn new(country: country::Id) -> Self {
+		Context {
+			country,
+			area_code: None,
+		}
+	}
+
+	/// Create a context from the caller's own phone number, taking its country
+	/// and area code.
+	///
+	/// Returns `None` if the country of the number cannot be found.
+	pub fn from_number(number: &PhoneNumber) -> Option<Self> {
+		let country = number.country().id()?;
+
+		// The area code is the first group of the international format after the
+		// country code, if the number is split in more groups.
+		let formatted = number.format().mode(Mode::International).to_string();
+		let groups    = formatted.split(|c: char| !c.is_ascii_digit())
+			.filter(|g| !g.is_empty())
+			.skip(1)
+			.collect::<Vec<_>>();
+
+		let area_code = if groups.len() > 2 {
+			Some(groups[0].to_owned())
+		}
+		else {
+			None
+		};
+
+		Some(Context {
+			country,
+			area_code,
+		})
+	}
+
+	/// Define the area code of the caller.
+	pub fn with_area_code<S: Into<String>>(mut self, area_code: S) -> Self {
+		self.area_code = Some(area_code.into());
+		self
+	}
+
+	/// Get the country.
+	pub fn country(&self) -> country::Id {
+		self.country
+	}
+
+	/// Get the area code.
+	pub fn area_code(&self) -> Option<&str> {
+		self.area_code.as_deref()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::context::Context;
+	use crate::parser;
+	use crate::country;
+
+	#[test]
+	fn from_number() {
+		let context = Context::from_number(&parser::parse(None, "+1 650 253 0000").unwrap()).unwrap();
+		assert_eq!(country::US, context.country());
+		assert_eq!(Some("650"), context.area_code());
+
+		let context = Context::from_number(&parser::parse(None, "+44 20 7031 3000").unwrap()).unwrap();
+		assert_eq!(country::GB, context.country());
+		assert_eq!(Some("20"), context.area_code());
+	}
+}
@@ -27,7 +27,7 @@ pub struct Code {
 
 /// The source from which the country code is derived. This is not set in the
 /// general parsing method, but in the method that parses and keeps raw_input.
-#[derive(Eq, PartialEq, Copy, Clone, Serialize, Deserialize, Hash, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Default, Serialize, Deserialize, Hash, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum Source {
 	/// The country code is derived based on a phone number with a leading "+",
@@ -49,15 +49,10 @@ pub enum Source {
 	/// format (without country code). For example, this would be set when
 	/// parsing the French number "01 42 68 53 00", when the default country is
 	/// supplied as France.
+	#[default]
 	Default,
 }
 
-impl Default for Source {
-	fn default() -> Self {
-		Source::Default
-	}
-}
-
 impl Code {
 	/// The country code number.
 	pub fn value(&self) -> u16 {
@@ -70,9 +65,9 @@ impl Code {
 	}
 }
 
-impl Into<u16> for Code {
-	fn into(self) -> u16 {
-		self.value
+impl From<Code> for u16 {
+	fn from(code: Code) -> u16 {
+		code.value
 	}
 }
 
@@ -0,0 +1,363 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::metadata::{DATABASE, Database};
+use crate::phone_number::{PhoneNumber, Type};
+use crate::context::Context;
+use crate::formatter::{self, Mode};
+use crate::country;
+use crate::consts;
+use crate::validator;
+use crate::error;
+
+/// Where a number is dialled from.
+#[derive(Copy, Clone, Debug)]
+pub enum Origin<'a> {
+	/// Anywhere in the given country.
+	Region(country::Id),
+
+	/// From the given phone number, in the same area.
+	Number(&'a PhoneNumber),
+
+	/// From the given context.
+	Context(&'a Context),
+}
+
+impl<'a> From<country::Id> for Origin<'a> {
+	fn from(value: country::Id) -> Self {
+		Origin::Region(value)
+	}
+}
+
+impl<'a> From<&'a PhoneNumber> for Origin<'a> {
+	fn from(value: &'a PhoneNumber) -> Self {
+		Origin::Number(value)
+	}
+}
+
+impl<'a> From<&'a Context> for Origin<'a> {
+	fn from(value: &'a Context) -> Self {
+		Origin::Context(value)
+	}
+}
+
+/// What to dial to reach a destination number from an origin.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DialPlan {
+	digits:          String,
+	idd:             Option<String>,
+	country_code:    Option<u16>,
+	national_prefix: Option<String>,
+	carrier:         Option<String>,
+	mobile_token:    Option<String>,
+	area_code:       bool,
+}
+
+impl DialPlan {
+	/// The full string to dial.
+	pub fn digits(&self) -> &str {
+		&self.digits
+	}
+
+	/// The international dialing prefix, if the call is international.
+	///
+	/// When the origin has several prefixes and no preferred one, this is "+",
+	/// which mobile phones replace with the right prefix.
+	pub fn idd(&self) -> Option<&str> {
+		self.idd.as_deref()
+	}
+
+	/// The country code to dial, if the call is international or between
+	/// regions of the North American Numbering Plan.
+	pub fn country_code(&self) -> Option<u16> {
+		self.country_code
+	}
+
+	/// The national prefix to dial, if any.
+	pub fn national_prefix(&self) -> Option<&str> {
+		self.national_prefix.as_deref()
+	}
+
+	/// The carrier selection code to dial, if any.
+	pub fn carrier(&self) -> Option<&str> {
+		self.carrier.as_deref()
+	}
+
+	/// The mobile token that is part of the dialled number, if any.
+	pub fn mobile_token(&self) -> Option<&str> {
+		self.mobile_token.as_deref()
+	}
+
+	/// Whether the area code can be, and has been, left out.
+	pub fn drops_area_code(&self) -> bool {
+		!self.area_code
+	}
+}
+
+/// Find what to dial to reach the destination from the origin.
+pub fn dial_plan<'a, O: Into<Origin<'a>>>(origin: O, destination: &PhoneNumber) -> Result<DialPlan, error::DialPlan> {
+	dial_plan_with(&DATABASE, origin, destination)
+}
+
+/// Find what to dial to reach the destination from the origin using a specific
+/// `Database`.
+pub fn dial_plan_with<'a, O: Into<Origin<'a>>>(database: &Database, origin: O, destination: &PhoneNumber) -> Result<DialPlan, error::DialPlan> {
+	let context = match origin.into() {
+		Origin::Region(id) =>
+			Context::new(id),
+
+		Origin::Number(number) =>
+			Context::from_number(number).ok_or(error::DialPlan::InvalidOrigin)?,
+
+		Origin::Context(context) =>
+			context.clone(),
+	};
+
+	let from = database.by_id(context.country().as_ref())
+		.ok_or(error::DialPlan::InvalidOrigin)?;
+	let to = destination.metadata(database)
+		.ok_or(error::DialPlan::InvalidDestination)?;
+
+	// Extensions are never part of what is dialled.
+	let mut number = destination.clone();
+	number.extension = None;
+
+	let national = number.national().digits();
+	let national = &*national;
+
+	// Numbers of private numbering plans are dialled as their digits alone.
+	if number.is_private() {
+		return Ok(DialPlan {
+			digits:          national.into(),
+			idd:             None,
+			country_code:    None,
+			national_prefix: None,
+			carrier:         None,
+			mobile_token:    None,
+			area_code:       true,
+		});
+	}
+
+	if from.country_code() != to.country_code() {
+		if to.descriptors().no_international().map(|d| d.is_match(national)).unwrap_or(false) {
+			return Err(error::DialPlan::NoInternational {
+				region: to.id().into(),
+			});
+		}
+
+		// Regions with several prefixes have a pattern instead of digits.
+		let idd = from.international_prefix()
+			.map(|p| p.as_str().trim())
+			.filter(|p| consts::UNIQUE_INTERNATIONAL_PREFIX.find(p)
+				.map(|m| m.start() == 0 && m.end() == p.len())
+				.unwrap_or(false))
+			.or_else(|| from.preferred_international_prefix())
+			.unwrap_or("+");
+
+		let mobile_token = consts::MOBILE_TOKEN_MAPPINGS.get(&to.country_code())
+			.filter(|&token| national.starts_with(token))
+			.filter(|_| validator::number_type(to, national) == Type::Mobile)
+			.map(|&token| token.to_owned());
+
+		return Ok(DialPlan {
+			digits:          format!("{}{}{}", idd, to.country_code(), national),
+			idd:             Some(idd.into()),
+			country_code:    Some(to.country_code()),
+			national_prefix: None,
+			carrier:         None,
+			mobile_token,
+			area_code:       true,
+		});
+	}
+
+	// Regions of the North American Numbering Plan share the country code, and
+	// calls between them are dialled with it, as long distance calls.
+	if from.id() != to.id() && to.country_code() == 1 {
+		return Ok(DialPlan {
+			digits:          format!("1{}", national),
+			idd:             None,
+			country_code:    Some(1),
+			national_prefix: None,
+			carrier:         None,
+			mobile_token:    None,
+			area_code:       true,
+		});
+	}
+
+	let digits = |mode| formatter::format_with(database, &number).mode(mode).to_string()
+		.chars().filter(char::is_ascii_digit).collect::<String>();
+
+	// Other regions sharing the country code are dialled nationally, but the
+	// area code of the origin says nothing about the destination.
+	let full  = digits(Mode::National);
+	let local = if from.id() == to.id() {
+		digits(Mode::Local { context: &context })
+	}
+	else {
+		full.clone()
+	};
+
+	let format = formatter::formatter(national, to.formats());
+	let rule   = format.and_then(|f| number.carrier().and(f.domestic_carrier()))
+		.or_else(|| format.and_then(|f| f.national_prefix()));
+
+	let carrier = number.carrier()
+		.filter(|_| format.and_then(|f| f.domestic_carrier()).is_some())
+		.map(|c| c.to_string());
+
+	let national_prefix = to.national_prefix()
+		.filter(|_| rule.map(|r| r.contains(*consts::NP)).unwrap_or(false))
+		.filter(|p| full.starts_with(p))
+		.map(|p| p.to_owned());
+
+	if local.len() < full.len() {
+		return Ok(DialPlan {
+			digits:          local,
+			idd:             None,
+			country_code:    None,
+			national_prefix: None,
+			carrier:         None,
+			mobile_token:    None,
+			area_code:       false,
+		});
+	}
+
+	Ok(DialPlan {
+		digits:          full,
+		idd:             None,
+		country_code:    None,
+		national_prefix,
+		carrier,
+		mobile_token:    None,
+		area_code:       true,
+	})
+}
+
+#[cfg(test)]
+mod test {
+	use crate::dial_plan;
+	use crate::context::Context;
+	use crate::metadata::{DatabaseBuilder, loader};
+	use crate::parser;
+	use crate::country;
+	use crate::error;
+
+	#[test]
+	fn international() {
+		let number = parser::parse(None, "+44 20 7031 3000").unwrap();
+
+		let plan = dial_plan::dial_plan(country::US, &number).unwrap();
+		assert_eq!("011442070313000", plan.digits());
+		assert_eq!(Some("011"), plan.idd());
+		assert_eq!(Some(44), plan.country_code());
+		assert_eq!(None, plan.national_prefix());
+
+		let plan = dial_plan::dial_plan(country::DE, &number).unwrap();
+		assert_eq!("00442070313000", plan.digits());
+
+		let number = parser::parse(None, "+54 9 11 2345 6789").unwrap();
+		let plan   = dial_plan::dial_plan(country::US, &number).unwrap();
+		assert_eq!(Some("9"), plan.mobile_token());
+	}
+
+	#[test]
+	fn national() {
+		let number = parser::parse(None, "+44 20 7031 3000").unwrap();
+
+		let plan = dial_plan::dial_plan(country::GB, &number).unwrap();
+		assert_eq!("02070313000", plan.digits());
+		assert_eq!(None, plan.idd());
+		assert_eq!(Some("0"), plan.national_prefix());
+		assert!(!plan.drops_area_code());
+
+		let number  = parser::parse(None, "+1 650 253 0000").unwrap();
+		let context = Context::new(country::US).with_area_code("650");
+		let plan    = dial_plan::dial_plan(&context, &number).unwrap();
+		assert_eq!("2530000", plan.digits());
+		assert!(plan.drops_area_code());
+
+		let origin = parser::parse(None, "+1 415 555 0100").unwrap();
+		let plan   = dial_plan::dial_plan(&origin, &number).unwrap();
+		assert_eq!("6502530000", plan.digits());
+		assert!(!plan.drops_area_code());
+
+		let number = parser::parse(Some(country::BR), "012 3121286979").unwrap();
+		let plan   = dial_plan::dial_plan(country::BR, &number).unwrap();
+		assert_eq!(Some("12"), plan.carrier());
+	}
+
+	#[test]
+	fn no_international() {
+		let number = parser::parse(None, "+61 1800 123 456").unwrap();
+
+		let error = dial_plan::dial_plan(country::GB, &number).unwrap_err();
+		assert!(matches!(error, error::DialPlan::NoInternational { .. }));
+		assert_eq!("AU number cannot be dialled internationally", error.to_string());
+
+		assert_eq!("1800123456", dial_plan::dial_plan(country::AU, &number).unwrap().digits());
+	}
+
+	#[test]
+	fn shared() {
+		// Regions of the North American Numbering Plan dial each other with the
+		// country code.
+		let number = parser::parse(None, "+1 876 927 1234").unwrap();
+		let plan   = dial_plan::dial_plan(country::US, &number).unwrap();
+		assert_eq!("18769271234", plan.digits());
+		assert_eq!(None, plan.idd());
+		assert_eq!(Some(1), plan.country_code());
+
+		let number = parser::parse(None, "+1 613 555 0123").unwrap();
+		let plan   = dial_plan::dial_plan(country::US, &number).unwrap();
+		assert_eq!("16135550123", plan.digits());
+
+		// Within the same region they're dialled as usual.
+		let origin = parser::parse(None, "+1 613 555 0100").unwrap();
+		let plan   = dial_plan::dial_plan(&origin, &number).unwrap();
+		assert_eq!("5550123", plan.digits());
+		assert_eq!(None, plan.country_code());
+
+		// Other regions sharing the country code dial each other nationally,
+		// keeping the area code.
+		let number  = parser::parse(None, "+44 1481 256 789").unwrap();
+		let context = Context::new(country::GB).with_area_code("1481");
+		let plan    = dial_plan::dial_plan(&context, &number).unwrap();
+		assert_eq!("01481256789", plan.digits());
+		assert_eq!(None, plan.idd());
+		assert!(!plan.drops_area_code());
+	}
+
+	#[test]
+	fn private() {
+		let database = DatabaseBuilder::new()
+			.private_plan(loader::Metadata {
+				id: Some("ACME".into()),
+
+				general: Some(loader::Descriptor {
+					national_number: Some("[1-7]\\d{3}".into()),
+					possible_length: vec![4],
+					.. Default::default()
+				}),
+
+				.. Default::default()
+			}, None::<String>)
+			.build().unwrap();
+
+		let number = parser::parse_with(&database, None, "4567").unwrap();
+		let plan   = dial_plan::dial_plan_with(&database, country::US, &number).unwrap();
+		assert_eq!("4567", plan.digits());
+		assert_eq!(None, plan.idd());
+		assert_eq!(None, plan.country_code());
+	}
+}
@@ -0,0 +1,328 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::str::FromStr;
+use std::ops::Deref;
+
+use crate::metadata::{DATABASE, Database};
+use crate::phone_number::PhoneNumber;
+use crate::formatter::Mode;
+use crate::country;
+use crate::parser;
+use crate::error;
+
+/// A phone number followed by a sequence of post-dial steps, like the ones
+/// used by PBXs and mobile phones, e.g. "+1 650 253 0000,,1234#".
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Debug)]
+pub struct DialString {
+	number: PhoneNumber,
+	steps:  Vec<Step>,
+}
+
+/// A single post-dial step.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+	/// Pause for a short, device dependent, amount of time, usually written as
+	/// "," or "p".
+	Pause,
+
+	/// Wait for confirmation before going on, usually written as ";" or "w".
+	Wait,
+
+	/// DTMF tones to send, made of digits, "*", "#" and "A" to "D".
+	Dtmf(String),
+}
+
+/// Devices a `DialString` can be formatted for.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Device {
+	/// Android dialer, using "," for pauses and ";" for waits.
+	Android,
+
+	/// iOS dialer, using "," for pauses and ";" for waits.
+	Ios,
+
+	/// Asterisk `Dial()` arguments, sending the post-dial sequence with the
+	/// `D` option. Asterisk cannot wait for confirmation, so waits are
+	/// rendered as a pause.
+	Asterisk,
+}
+
+/// Formatter for a `DialString` on a given `Device`.
+///
+/// The extension of the number, if any, is dialled after a pause ahead of the
+/// other post-dial steps, since dialers drop it from the number.
+#[derive(Copy, Clone, Debug)]
+pub struct Dial<'a> {
+	string:   &'a DialString,
+	database: &'a Database,
+	device:   Device,
+}
+
+impl DialString {
+	/// Parse a dial string.
+	pub fn parse<S: AsRef<str>>(country: Option<country::Id>, string: S) -> Result<Self, error::Parse> {
+		DialString::parse_with(&DATABASE, country, string)
+	}
+
+	/// Parse a dial string using a specific `Database`.
+	pub fn parse_with<S: AsRef<str>>(database: &Database, country: Option<country::Id>, string: S) -> Result<Self, error::Parse> {
+		let string = string.as_ref();
+		let index  = post_dial(string).unwrap_or(string.len());
+
+		Ok(DialString {
+			number: parser::parse_with(database, country, &string[.. index])?,
+			steps:  steps(&string[index ..])
+				.map_err(|e| { let span = e.span(); e.with_span(index + span.start .. index + span.end) })?,
+		})
+	}
+
+	/// Get the phone number.
+	pub fn number(&self) -> &PhoneNumber {
+		&self.number
+	}
+
+	/// Get the post-dial steps.
+	pub fn steps(&self) -> &[Step] {
+		&self.steps
+	}
+
+	/// Prepare a formatter for the given device.
+	pub fn format(&self, device: Device) -> Dial<'_> {
+		self.format_with(&DATABASE, device)
+	}
+
+	/// Prepare a formatter for the given device using a specific `Database`,
+	/// the one the string was parsed with.
+	pub fn format_with<'a>(&'a self, database: &'a Database, device: Device) -> Dial<'a> {
+		Dial {
+			string: self,
+			database,
+			device,
+		}
+	}
+
+	/// Get the post-dial steps, preceded by the pause and tones of the
+	/// extension if any.
+	fn dialled(&self) -> Vec<Step> {
+		let mut steps = Vec::with_capacity(self.steps.len() + 2);
+
+		if let Some(extension) = self.number.extension() {
+			steps.push(Step::Pause);
+			steps.push(Step::Dtmf(extension.chars().filter(|&c| is_tone(c)).collect()));
+		}
+
+		steps.extend(self.steps.iter().cloned());
+		steps
+	}
+}
+
+impl FromStr for DialString {
+	type Err = error::Parse;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		DialString::parse(None, s)
+	}
+}
+
+impl Deref for DialString {
+	type Target = PhoneNumber;
+
+	fn deref(&self) -> &PhoneNumber {
+		&self.number
+	}
+}
+
+impl<'a> fmt::Display for Dial<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.string.number.format_with(self.database).mode(Mode::E164).write_to(f)?;
+
+		let steps = self.string.dialled();
+
+		if steps.is_empty() {
+			return Ok(());
+		}
+
+		if self.device == Device::Asterisk {
+			write!(f, ",,D(")?;
+		}
+
+		for step in &steps {
+			match (self.device, step) {
+				(_, Step::Dtmf(tones)) =>
+					write!(f, "{}", tones)?,
+
+				(Device::Android, Step::Pause) |
+				(Device::Ios, Step::Pause) =>
+					write!(f, ",")?,
+
+				(Device::Android, Step::Wait) |
+				(Device::Ios, Step::Wait) =>
+					write!(f, ";")?,
+
+				// Each `w` waits half a second, so this matches the two seconds
+				// of a handset pause.
+				(Device::Asterisk, Step::Pause) |
+				(Device::Asterisk, Step::Wait) =>
+					write!(f, "wwww")?,
+			}
+		}
+
+		if self.device == Device::Asterisk {
+			write!(f, ")")?;
+		}
+
+		Ok(())
+	}
+}
+
+fn is_separator(c: char) -> bool {
+	matches!(c, ',' | ';' | 'p' | 'P' | 'w' | 'W')
+}
+
+fn is_tone(c: char) -> bool {
+	matches!(c, '0' ..= '9' | '*' | '#' | 'A' ..= 'D')
+}
+
+/// Find where the post-dial sequence starts, a separator only starts it when
+/// it follows the number and is followed by something diallable, so that words
+/// like "Durchwahl" or "poste" are left to the extension parser.
+fn post_dial(string: &str) -> Option<usize> {
+	let mut previous = None;
+
+	for (index, c) in string.char_indices() {
+		if is_separator(c) {
+			let follows = previous.map(|p: char| p.is_ascii_digit() || p == '#' || p == '*')
+				.unwrap_or(false);
+
+			let precedes = string[index + c.len_utf8() ..].chars().find(|n| !n.is_whitespace())
+				.map(|n| n.is_ascii_digit() || n == '*' || n == '#' || is_separator(n))
+				.unwrap_or(true);
+
+			if follows && precedes {
+				return Some(index);
+			}
+		}
+
+		if !c.is_whitespace() {
+			previous = Some(c);
+		}
+	}
+
+	None
+}
+
+fn steps(string: &str) -> Result<Vec<Step>, error::Parse> {
+	let mut steps = Vec::new();
+
+	for (index, c) in string.char_indices() {
+		match c {
+			',' | 'p' | 'P' =>
+				steps.push(Step::Pause),
+
+			';' | 'w' | 'W' =>
+				steps.push(Step::Wait),
+
+			c if is_tone(c) => {
+				if let Some(Step::Dtmf(tones)) = steps.last_mut() {
+					tones.push(c);
+				}
+				else {
+					steps.push(Step::Dtmf(c.to_string()));
+				}
+			}
+
+			c if c.is_whitespace() || c == '-' =>
+				(),
+
+			_ =>
+				return Err(error::Parse::new(error::ParseKind::InvalidPostDial,
+					error::Stage::PostDial, index .. index + c.len_utf8())),
+		}
+	}
+
+	Ok(steps)
+}
+
+#[cfg(test)]
+mod test {
+	use crate::dial_string::{DialString, Step, Device};
+	use crate::metadata::{DatabaseBuilder, loader};
+	use crate::country;
+	use crate::error;
+
+	#[test]
+	fn parse() {
+		let string = DialString::parse(None, "+1 650 253 0000,,1234#").unwrap();
+
+		assert_eq!(1, string.number().code().value());
+		assert_eq!(6502530000, string.number().national().value());
+		assert_eq!(None, string.number().extension());
+		assert_eq!(&[Step::Pause, Step::Pause, Step::Dtmf("1234#".into())][..], string.steps());
+
+		let string = DialString::parse(Some(country::US), "(650) 253-0000 w 1 p 2 ; *9").unwrap();
+		assert_eq!(&[
+			Step::Wait, Step::Dtmf("1".into()), Step::Pause, Step::Dtmf("2".into()),
+			Step::Wait, Step::Dtmf("*9".into()),
+		][..], string.steps());
+
+		let string = DialString::parse(Some(country::DE), "030 1234567 Durchwahl 89").unwrap();
+		assert!(string.steps().is_empty());
+		assert_eq!("89", &**string.number().extension().unwrap());
+
+		let error = DialString::parse(None, "+1 650 253 0000,,12x4").unwrap_err();
+		assert_eq!(error::Stage::PostDial, error.stage());
+		assert_eq!(19 .. 20, error.span());
+	}
+
+	#[test]
+	fn format() {
+		let string = DialString::parse(None, "+1 650 253 0000,,1234#;5").unwrap();
+
+		assert_eq!("+16502530000,,1234#;5", string.format(Device::Android).to_string());
+		assert_eq!("+16502530000,,1234#;5", string.format(Device::Ios).to_string());
+		assert_eq!("+16502530000,,D(wwwwwwww1234#wwww5)", string.format(Device::Asterisk).to_string());
+
+		let string = DialString::parse(None, "+1 650 253 0000").unwrap();
+		assert_eq!("+16502530000", string.format(Device::Asterisk).to_string());
+
+		// The extension is dialled after a pause.
+		let string = DialString::parse(Some(country::DE), "030 1234567 Durchwahl 89").unwrap();
+		assert_eq!("+49301234567,89", string.format(Device::Android).to_string());
+		assert_eq!("+49301234567,,D(wwww89)", string.format(Device::Asterisk).to_string());
+
+		let string = DialString::parse(None, "+1 650 253 0000 ext. 12;3").unwrap();
+		assert_eq!("+16502530000,12;3", string.format(Device::Ios).to_string());
+
+		// Numbers are formatted with the database they were parsed with.
+		let database = DatabaseBuilder::new()
+			.private_plan(loader::Metadata {
+				id: Some("ACME".into()),
+
+				general: Some(loader::Descriptor {
+					national_number: Some("[1-7]\\d{3}".into()),
+					possible_length: vec![4],
+					.. Default::default()
+				}),
+
+				.. Default::default()
+			}, None::<String>)
+			.build().unwrap();
+
+		let string = DialString::parse_with(&database, None, "4567,,89").unwrap();
+		assert_eq!("4567,,89", string.format_with(&database, Device::Android).to_string());
+	}
+}
@@ -0,0 +1,47 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Deref;
+use std::fmt;
+
+/// The domain name a local phone number is valid within, as given by an
+/// RFC3966 `phone-context`.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Debug)]
+pub struct Domain(pub(crate) String);
+
+impl<T: Into<String>> From<T> for Domain {
+	fn from(value: T) -> Domain {
+		Domain(value.into())
+	}
+}
+
+impl Deref for Domain {
+	type Target = str;
+
+	fn deref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl AsRef<str> for Domain {
+	fn as_ref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl fmt::Display for Domain {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
@@ -12,6 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fmt;
+use std::ops::Range;
+
 use thiserror::Error;
 
 /// Metadata loading errors.
@@ -48,54 +51,169 @@ pub enum Metadata {
 		value: String,
 	},
 
+	/// A value was malformed.
+	#[error("{phase}: malformed value: {name:?}={value:?}")]
+	MalformedValue {
+		phase: String,
+		name:  String,
+		value: String,
+	},
+
 	/// An event was not handled.
 	#[error("{phase}: unhandled event: {event:?}")]
 	UnhandledEvent {
 		phase: String,
 		event: String,
-	}
+	},
+
+	/// An overlay referred to a region missing from the database.
+	#[error("unknown region: {id:?}")]
+	UnknownRegion {
+		id: String,
+	},
+
+	/// An overlay added a region already in the database.
+	#[error("duplicate region: {id:?}")]
+	DuplicateRegion {
+		id: String,
+	},
 }
 
 /// Parsing errors.
+///
+/// Besides what went wrong, they carry which stage of parsing failed, the byte
+/// span of the offending part of the input, and the country code found before
+/// failing, if any.
+#[derive(Error, Clone, Debug)]
+#[error("{kind}")]
+pub struct Parse {
+	kind:  ParseKind,
+	stage: Stage,
+	span:  Range<usize>,
+	code:  Option<u16>,
+}
+
+impl Parse {
+	pub(crate) fn new(kind: ParseKind, stage: Stage, span: Range<usize>) -> Self {
+		Parse {
+			kind,
+			stage,
+			span,
+			code: None,
+		}
+	}
+
+	pub(crate) fn with_code(mut self, code: Option<u16>) -> Self {
+		self.code = code;
+		self
+	}
+
+	pub(crate) fn with_span(mut self, span: Range<usize>) -> Self {
+		self.span = span;
+		self
+	}
+
+	/// What went wrong.
+	pub fn kind(&self) -> &ParseKind {
+		&self.kind
+	}
+
+	/// The stage of parsing that failed.
+	pub fn stage(&self) -> Stage {
+		self.stage
+	}
+
+	/// The byte span of the offending part of the input.
+	pub fn span(&self) -> Range<usize> {
+		self.span.clone()
+	}
+
+	/// The candidate country code, if one was found before failing.
+	pub fn code(&self) -> Option<u16> {
+		self.code
+	}
+}
+
+/// The stage of parsing an error happened in.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Stage {
+	/// Extracting a possible phone number from the input.
+	Extraction,
+
+	/// Stripping the international dialing prefix.
+	Idd,
+
+	/// Extracting the country code.
+	CountryCode,
+
+	/// Stripping the national prefix.
+	NationalPrefix,
+
+	/// Checking the length of the national number.
+	Length,
+
+	/// Parsing the post-dial sequence of a dial string.
+	PostDial,
+}
+
+/// Kinds of parsing errors.
 #[derive(Error, Clone, Debug)]
-pub enum Parse {
+pub enum ParseKind {
 	/// This generally indicates the string passed in had less than 3 digits in
 	/// it.
     #[error("not a number")]
-    #[allow(unused)] // This is unused in the build script
 	NoNumber,
 
 	/// The country code supplied did not belong to a supported country or
 	/// non-geographical entity.
     #[error("invalid country code")]
-    #[allow(unused)] // This is unused in the build script
 	InvalidCountryCode,
 
 	/// This indicates the string started with an international dialing prefix,
 	/// but after this was stripped from the number, had less digits than any
 	/// valid phone number (including country code) could have.
     #[error("the number is too short after IDD")]
-    #[allow(unused)] // This is unused in the build script
 	TooShortAfterIdd,
 
 	/// This indicates the string, after any country code has been stripped, had
 	/// less digits than any valid phone number could have.
     #[error("the number is too short after the country code")]
-    #[allow(unused)] // This is unused in the build script
 	TooShortNsn,
 
 	/// This indicates the string had more digits than any valid phone number
 	/// could have.
     #[error("the number is too long")]
-    #[allow(unused)] // This is unused in the build script
     TooLong,
 
+    /// The post-dial sequence of a dial string contained characters that
+    /// cannot be dialled.
+    #[error("invalid post-dial sequence")]
+    InvalidPostDial,
+
     /// A integer parts of a number is malformed, normally this should be caught by the parsing regexes.
     #[error("malformed integer part in phone number: {0}")]
     MalformedInteger(#[from] std::num::ParseIntError),
 }
 
 
+/// Dial plan errors.
+#[derive(Error, Clone, Debug)]
+pub enum DialPlan {
+	/// The country of the origin is not in the database.
+	#[error("invalid origin")]
+	InvalidOrigin,
+
+	/// The country of the destination is not in the database.
+	#[error("invalid destination")]
+	InvalidDestination,
+
+	/// The destination cannot be dialled from outside its country.
+	#[error("{region} number cannot be dialled internationally")]
+	NoInternational {
+		region: String,
+	},
+}
+
 /// Loading of Database) Error
 #[derive(Error, Debug)]
 pub enum LoadMetadata {
@@ -128,4 +246,85 @@ pub enum LoadMetadata {
     #[error("Malformed Regex: {0}")]
     Regex(#[from] regex::Error),
 
-}
\ No newline at end of file
+    /// Malformed or unserializable Metadata JSON
+    #[error("Malformed Metadata JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The metadata is for another version.
+    #[error("Metadata version mismatch: expected {expected}, found {found}")]
+    VersionMismatch {
+        expected: String,
+        found:    String,
+    },
+
+    /// Error at a location of the Metadata XML
+    #[error("{location}: {error}")]
+    Located {
+        location: Location,
+        error:    Box<LoadMetadata>,
+    },
+
+}
+
+impl LoadMetadata {
+	/// Where in the Metadata XML the error happened, if known.
+	pub fn location(&self) -> Option<&Location> {
+		if let LoadMetadata::Located { location, .. } = self {
+			Some(location)
+		}
+		else {
+			None
+		}
+	}
+
+	/// Attach a location to the error.
+	pub(crate) fn at(self, location: Location) -> Self {
+		LoadMetadata::Located {
+			location,
+			error: Box::new(self),
+		}
+	}
+}
+
+/// A location in the Metadata XML.
+#[derive(Clone, Eq, PartialEq, Default, Debug)]
+pub struct Location {
+	pub(crate) offset:    usize,
+	pub(crate) line:      usize,
+	pub(crate) column:    usize,
+	pub(crate) territory: Option<String>,
+}
+
+impl Location {
+	/// The byte offset.
+	pub fn offset(&self) -> usize {
+		self.offset
+	}
+
+	/// The line, starting from 1.
+	pub fn line(&self) -> usize {
+		self.line
+	}
+
+	/// The column in bytes, starting from 1.
+	pub fn column(&self) -> usize {
+		self.column
+	}
+
+	/// The ID of the territory being loaded, if any.
+	pub fn territory(&self) -> Option<&str> {
+		self.territory.as_ref().map(AsRef::as_ref)
+	}
+}
+
+impl fmt::Display for Location {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "line {}, column {}", self.line, self.column)?;
+
+		if let Some(territory) = self.territory.as_ref() {
+			write!(f, " in territory {:?}", territory)?;
+		}
+
+		Ok(())
+	}
+}
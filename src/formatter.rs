@@ -12,17 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{borrow::Cow, fmt};
+use std::fmt;
 use crate::{
   metadata::{DATABASE, Database, Metadata, Format},
   phone_number::PhoneNumber,
+  context::Context,
+  parser::{rfc3966, helper::Number as ParseNumber},
+  validator::{self, Validation},
   consts
 };
 
 /// Formatting modes for phone number.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub enum Mode {
+pub enum Mode<'a> {
 	/// E.164 formatting, no spaces, no decorations.
+	///
+	/// Numbers of private numbering plans have no country code, they're
+	/// written as their digits alone.
 	E164,
 
 	/// International formatting, contains country code and country dependent
@@ -34,15 +40,35 @@ pub enum Mode {
 
 	/// RFC3966 formatting, see the RFC.
 	Rfc3966,
+
+	/// SIP URI formatting, the RFC3966 representation of the number is used as
+	/// the user part as described in RFC3261 section 19.1.6.
+	SipUri {
+		/// The host part of the URI.
+		host: &'a str,
+	},
+
+	/// Local formatting, the national format without the area code when the
+	/// number is in the same area as the `Context`, falling back to national
+	/// formatting in the same country and international formatting otherwise.
+	Local {
+		/// Where the number is dialled from.
+		context: &'a Context,
+	},
 }
 
 /// A formatter for a `PhoneNumber`.
+///
+/// The `'f` lifetime covers both the custom `Format` and any data borrowed by
+/// the formatting `Mode`.
 #[derive(Copy, Clone, Debug)]
 pub struct Formatter<'n, 'd, 'f> {
 	number:   &'n PhoneNumber,
 	database: Option<&'d Database>,
-	mode:     Mode,
+	mode:     Mode<'f>,
 	format:   Option<&'f Format>,
+	language: Option<&'f str>,
+	vanity:   bool,
 }
 
 impl<'n, 'd, 'f> Formatter<'n, 'd, 'f> {
@@ -53,22 +79,58 @@ impl<'n, 'd, 'f> Formatter<'n, 'd, 'f> {
 			database: Some(database),
 			mode:     self.mode,
 			format:   self.format,
+			language: self.language,
+			vanity:   self.vanity,
 		}
 	}
 
 	/// Define the formatting mode.
-	pub fn mode(mut self, mode: Mode) -> Formatter<'n, 'd, 'f> {
-		self.mode = mode;
+	pub fn mode<'a>(self, mode: Mode<'a>) -> Formatter<'n, 'd, 'a>
+		where 'f: 'a
+	{
+		Formatter {
+			number:   self.number,
+			database: self.database,
+			mode,
+			format:   self.format,
+			language: self.language,
+			vanity:   self.vanity,
+		}
+	}
+
+	/// Define the language used for the extension prefix, by default the
+	/// region's preferred prefix or " ext. " is used.
+	pub fn language<'a>(self, language: &'a str) -> Formatter<'n, 'd, 'a>
+		where 'f: 'a
+	{
+		Formatter {
+			number:   self.number,
+			database: self.database,
+			mode:     self.mode,
+			format:   self.format,
+			language: Some(language),
+			vanity:   self.vanity,
+		}
+	}
+
+	/// Use the vanity spelling of the number, if any, in the international and
+	/// national formats.
+	pub fn vanity(mut self, vanity: bool) -> Formatter<'n, 'd, 'f> {
+		self.vanity = vanity;
 		self
 	}
 
 	/// Define a custom `Format` to use for formatting.
-	pub fn with<'a>(self, format: &'a Format) -> Formatter<'n, 'd, 'a> {
+	pub fn with<'a>(self, format: &'a Format) -> Formatter<'n, 'd, 'a>
+		where 'f: 'a
+	{
 		Formatter {
 			number:   self.number,
 			database: self.database,
 			mode:     self.mode,
 			format:   Some(format),
+			language: self.language,
+			vanity:   self.vanity,
 		}
 	}
 }
@@ -76,10 +138,12 @@ impl<'n, 'd, 'f> Formatter<'n, 'd, 'f> {
 /// Create a new `Formatter` for the given phone number.
 pub fn format<'n>(number: &'n PhoneNumber) -> Formatter<'n, 'static, 'static> {
 	Formatter {
-		number:   number,
+		number,
 		database: None,
 		mode:     Mode::E164,
 		format:   None,
+		language: None,
+		vanity:   false,
 	}
 }
 
@@ -87,132 +151,382 @@ pub fn format<'n>(number: &'n PhoneNumber) -> Formatter<'n, 'static, 'static> {
 /// metadata database.
 pub fn format_with<'d, 'n>(database: &'d Database, number: &'n PhoneNumber) -> Formatter<'n, 'd, 'static> {
 	Formatter {
-		number:   number,
+		number,
 		database: Some(database),
 		mode:     Mode::E164,
 		format:   None,
+		language: None,
+		vanity:   false,
 	}
 }
 
-impl<'n, 'd, 'f> fmt::Display for Formatter<'n, 'd, 'f> {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl<'n, 'd, 'f> Formatter<'n, 'd, 'f> {
+	/// Write the formatted number to the given writer.
+	///
+	/// Nothing is allocated on the way, except for the local form of a number
+	/// which is cut out of its national form.
+	pub fn write_to<W: fmt::Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
 		let db = self.database.unwrap_or(&*DATABASE);
 
-		// If the country code is invalid, return an error.
-		let meta = try_opt!(Err(fmt::Error);
-			db.by_code(&self.number.country().code()).map(|m|
-				m.into_iter().next().unwrap()));
+		// Private numbers have no country code, their E.164 form is the digits
+		// of the plan, which need no metadata.
+		if self.mode == Mode::E164 && self.number.is_private() {
+			return out.write_str(&self.number.national().digits());
+		}
 
-		let national  = self.number.national().to_string();
-		let formatter = self.format.or_else(|| formatter(&national,
-			if meta.international_formats().is_empty() || self.mode == Mode::National {
+		// Private numbers use the metadata of their plan, otherwise if the
+		// country code is invalid, return an error.
+		let meta = try_opt!(Err(fmt::Error); match self.number.private_plan() {
+			Some(plan) =>
+				db.private_plan(plan).map(|p| p.metadata()),
+
+			None =>
+				db.by_code(&self.number.country().code()).and_then(|mut m| m.next()),
+		});
+
+		let national = self.number.national().digits();
+		let national = &*national;
+
+		// Resolve the local mode to the mode it falls back to, keeping the length
+		// of the local number if the area code can be left out.
+		let (mode, local) = match self.mode {
+			// Private numbers are written in their national form otherwise.
+			Mode::International | Mode::Local { .. } if self.number.is_private() =>
+				(Mode::National, None),
+
+			Mode::Local { context } if db.by_id(context.country().as_ref())
+				.map(|m| m.country_code() == meta.country_code()).unwrap_or(false) =>
+			{
+				let local = context.area_code()
+					.filter(|area| national.starts_with(area) && national.len() > area.len())
+					.map(|area| national.len() - area.len())
+					.filter(|&length| validator::any_length(meta, &ParseNumber {
+						national: national[national.len() - length ..].into(),
+						.. Default::default()
+					}) == Validation::IsPossibleLocalOnly);
+
+				(Mode::National, local)
+			}
+
+			Mode::Local { .. } =>
+				(Mode::International, None),
+
+			mode =>
+				(mode, None),
+		};
+
+		let formatter = self.format.or_else(|| formatter(national,
+			if meta.international_formats().is_empty() || mode == Mode::National {
 				meta.formats()
 			}
 			else {
 				meta.international_formats()
 			}));
 
-		match self.mode {
+		match mode {
 			// Requires no formatting at all, easy life.
 			Mode::E164 => {
-				write!(f, "+{}{}", self.number.country().code(), national)?;
+				write!(out, "+{}{}", self.number.country().code(), national)?;
 			}
 
 			// Space separated formatting with national specific rules.
 			Mode::International => {
-				write!(f, "+{} ", self.number.country().code())?;
+				write!(out, "+{} ", self.number.country().code())?;
 
-				if let Some(formatter) = formatter {
-					write!(f, "{}", replace(&national, meta, formatter, None, None))?;
+				if let Some(vanity) = self.number.vanity().filter(|_| self.vanity) {
+					out.write_str(vanity)?;
+				}
+				else if let Some(formatter) = formatter {
+					replace(out, national, meta, formatter, None, None)?;
 				}
 				else {
-					write!(f, "{}", national)?;
+					out.write_str(national)?;
 				}
 
 				if let Some(ext) = self.number.extension() {
-					write!(f, "{}{}", meta.preferred_extension_prefix()
-						.unwrap_or(" ext. "), ext)?;
+					extension_prefix(out, meta, self.language)?;
+					out.write_str(ext)?;
 				}
 			}
 
 			Mode::National => {
-				if let Some(formatter) = formatter {
-					let carrier = self.number.carrier().and_then(|c|
-						formatter.domestic_carrier().map(|f| (c, f)));
-
-					if let Some((carrier, format)) = carrier {
-						write!(f, "{}", replace(&national, meta, formatter, Some(format), Some(carrier)))?;
-					}
-					else if let Some(prefix) = formatter.national_prefix() {
-						write!(f, "{}", replace(&national, meta, formatter, Some(prefix), None))?;
-					}
-					else {
-						write!(f, "{}", replace(&national, meta, formatter, None, None))?;
-					}
+				if let Some(length) = local {
+					let mut written = String::new();
+					self.national(&mut written, national, meta, formatter)?;
+					out.write_str(tail(&written, length))?;
 				}
 				else {
-					write!(f, "{}", national)?;
+					self.national(out, national, meta, formatter)?;
 				}
 
 				if let Some(ext) = self.number.extension() {
-					write!(f, "{}{}", meta.preferred_extension_prefix().unwrap_or(" ext. "), ext)?;
+					extension_prefix(out, meta, self.language)?;
+					out.write_str(ext)?;
 				}
 			}
 
 			Mode::Rfc3966 => {
-				write!(f, "tel:+{}-", self.number.country().code())?;
+				out.write_str("tel:")?;
+				rfc3966(out, self.number, national, meta, formatter)?;
+			}
 
-				if let Some(formatter) = formatter {
-					write!(f, "{}", consts::SEPARATOR_PATTERN.replace_all(
-						&replace(&national, meta, formatter, None, None), "-"))?;
-				}
-				else {
-					write!(f, "{}", national)?;
-				}
+			Mode::SipUri { host } => {
+				out.write_str("sip:")?;
+				rfc3966(out, self.number, national, meta, formatter)?;
+				write!(out, "@{};user=phone", host)?;
+			}
 
-				if let Some(ext) = self.number.extension() {
-					write!(f, ";ext={}", ext)?;
-				}
+			// Local formatting was resolved to another mode above.
+			Mode::Local { .. } =>
+				return Err(fmt::Error),
+		}
+
+		Ok(())
+	}
+
+	/// Append the formatted number to the given string.
+	pub fn format_into(&self, out: &mut String) -> fmt::Result {
+		self.write_to(out)
+	}
+
+	/// Write the national part of the national format, with the national prefix
+	/// or carrier code when the format asks for them.
+	fn national<W: fmt::Write + ?Sized>(&self, out: &mut W, national: &str, meta: &Metadata, formatter: Option<&Format>) -> fmt::Result {
+		if let Some(vanity) = self.number.vanity().filter(|_| self.vanity) {
+			out.write_str(vanity)
+		}
+		else if let Some(formatter) = formatter {
+			let carrier = self.number.carrier().and_then(|c|
+				formatter.domestic_carrier().map(|f| (c, f)));
+
+			if let Some((carrier, format)) = carrier {
+				replace(out, national, meta, formatter, Some(format), Some(carrier))
+			}
+			else if let Some(prefix) = formatter.national_prefix() {
+				replace(out, national, meta, formatter, Some(prefix), None)
+			}
+			else {
+				replace(out, national, meta, formatter, None, None)
 			}
 		}
+		else {
+			out.write_str(national)
+		}
+	}
+}
+
+impl<'n, 'd, 'f> fmt::Display for Formatter<'n, 'd, 'f> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.write_to(f)
+	}
+}
+
+/// Write the RFC3966 `telephone-subscriber` representation of the number, a
+/// number local to a domain or private numbering plan is written as a
+/// `local-number` with its context.
+fn rfc3966<W: fmt::Write + ?Sized>(out: &mut W, number: &PhoneNumber, national: &str, meta: &Metadata, formatter: Option<&Format>) -> fmt::Result {
+	if number.domain().is_none() && !number.is_private() {
+		write!(out, "+{}-", number.country().code())?;
+	}
+
+	if let Some(formatter) = formatter {
+		let mut dashes = Dashes { out: &mut *out, pending: false };
+		replace(&mut dashes, national, meta, formatter, None, None)?;
+		dashes.finish()?;
+	}
+	else {
+		out.write_str(national)?;
+	}
+
+	if let Some(ext) = number.extension() {
+		write!(out, ";ext={}", rfc3966::escape(ext))?;
+	}
+
+	if let Some(isub) = number.subaddress() {
+		write!(out, ";isub={}", rfc3966::escape(isub))?;
+	}
+
+	if let Some(domain) = number.domain() {
+		write!(out, ";phone-context={}", rfc3966::escape(domain))?;
+	}
+	else if let Some(plan) = number.private_plan() {
+		write!(out, ";phone-context={}", rfc3966::escape(plan))?;
+	}
+
+	Ok(())
+}
+
+/// Writer replacing every run of separators with a single dash.
+struct Dashes<'w, W: ?Sized> {
+	out:     &'w mut W,
+	pending: bool,
+}
+
+impl<'w, W: fmt::Write + ?Sized> Dashes<'w, W> {
+	/// Write the dash for a run of separators at the very end.
+	fn finish(self) -> fmt::Result {
+		if self.pending {
+			self.out.write_char('-')?;
+		}
+
+		Ok(())
+	}
+}
+
+impl<'w, W: fmt::Write + ?Sized> fmt::Write for Dashes<'w, W> {
+	fn write_str(&mut self, string: &str) -> fmt::Result {
+		for ch in string.chars() {
+			if is_separator(ch) {
+				self.pending = true;
+				continue;
+			}
+
+			if self.pending {
+				self.out.write_char('-')?;
+				self.pending = false;
+			}
+
+			self.out.write_char(ch)?;
+		}
 
 		Ok(())
 	}
 }
 
-fn formatter<'a>(number: &str, formats: &'a [Format]) -> Option<&'a Format> {
+/// Whether the character is in `consts::VALID_PUNCTUATION`, which
+/// `consts::SEPARATOR_PATTERN` is made of.
+fn is_separator(ch: char) -> bool {
+	matches!(ch,
+		'-' | 'x' | '\u{2010}' ..= '\u{2015}' | '\u{2212}' | '\u{30FC}' |
+		'\u{FF0D}' ..= '\u{FF0F}' | ' ' | '\u{00A0}' | '\u{00AD}' | '\u{200B}' |
+		'\u{2060}' | '\u{3000}' | '(' | ')' | '\u{FF08}' | '\u{FF09}' | '\u{FF3B}' |
+		'\u{FF3D}' | '.' | '[' | ']' | '/' | '~' | '\u{2053}' | '\u{223C}' | '\u{FF5E}')
+}
+
+/// Write the prefix to put in front of an extension, an explicitly requested
+/// language takes precedence over the region preferences.
+fn extension_prefix<W: fmt::Write + ?Sized>(out: &mut W, meta: &Metadata, language: Option<&str>) -> fmt::Result {
+	let keyword = |language| consts::EXTN_KEYWORDS.get(language).map(|words| words[0]);
+
+	if let Some(word) = language.and_then(keyword) {
+		write!(out, " {} ", word)
+	}
+	else if let Some(prefix) = meta.preferred_extension_prefix() {
+		out.write_str(prefix)
+	}
+	else {
+		out.write_str(&consts::DEFAULT_EXTN_PREFIX)
+	}
+}
+
+/// Get the part of the formatted number holding its last `length` digits, or
+/// letters for a vanity spelling.
+fn tail(formatted: &str, length: usize) -> &str {
+	let start = formatted.char_indices().rev()
+		.filter(|&(_, c)| c.is_ascii_alphanumeric())
+		.nth(length - 1)
+		.map(|(index, _)| index)
+		.unwrap_or(0);
+
+	&formatted[start ..]
+}
+
+pub(crate) fn formatter<'a>(number: &str, formats: &'a [Format]) -> Option<&'a Format> {
 	for format in formats {
 		let leading = format.leading_digits();
 
-		if leading.is_empty() || leading.last().unwrap().find(&number).map(|m| m.start() == 0).unwrap_or(false) {
-			if format.pattern().find(&number).map(|m| m.start() == 0 && m.end() == number.len()).unwrap_or(false) {
-				return Some(format);
-			}
+		if (leading.is_empty() || leading.last().unwrap().find(number).map(|m| m.start() == 0).unwrap_or(false)) &&
+		   format.pattern().find(number).map(|m| m.start() == 0 && m.end() == number.len()).unwrap_or(false)
+		{
+			return Some(format);
 		}
 	}
 
 	None
 }
 
-fn replace(national: &str, meta: &Metadata, formatter: &Format, transform: Option<&str>, carrier: Option<&str>) -> String {
-	formatter.pattern().replace(national, &*if let Some(transform) = transform {
-		let first  = consts::FIRST_GROUP.captures(&formatter.format()).unwrap().get(1).unwrap().as_str();
-		let format = transform.replace(*consts::NP, meta.national_prefix().unwrap_or(""));
-		let format = format.replace(*consts::FG, &*format!("${}", first));
-		let format = format.replace(*consts::CC, carrier.unwrap_or(""));
+/// Write the national number formatted with the given `Format`, the first
+/// group is wrapped in the given formatting rule if any.
+fn replace<W: fmt::Write + ?Sized>(out: &mut W, national: &str, meta: &Metadata, formatter: &Format, transform: Option<&str>, carrier: Option<&str>) -> fmt::Result {
+	let groups = if let Some(groups) = formatter.pattern().captures(national) { groups } else {
+		return out.write_str(national);
+	};
+
+	let whole = groups.get(0).unwrap();
+	let group = |index: usize| groups.get(index).map(|m| m.as_str()).unwrap_or("");
+
+	out.write_str(&national[.. whole.start()])?;
+
+	let format = formatter.format();
+	let first  = transform.and_then(|t| consts::FIRST_GROUP.find(format).map(|m| (t, m)));
+
+	if let Some((transform, first)) = first {
+		expand(out, &format[.. first.start()], &group)?;
 
-		consts::FIRST_GROUP.replace(formatter.format(), &*format)
+		let value = group(first.as_str()[1 ..].parse().unwrap());
+		let rules = [
+			(*consts::NP, meta.national_prefix().unwrap_or("")),
+			(*consts::FG, value),
+			(*consts::CC, carrier.unwrap_or("")),
+		];
+
+		let mut rest = transform;
+		while let Some(index) = rest.find('$') {
+			out.write_str(&rest[.. index])?;
+			rest = &rest[index ..];
+
+			// Groups can also be referred to directly, as in "0$1".
+			if let Some(&(name, value)) = rules.iter().find(|(name, _)| rest.starts_with(name)) {
+				out.write_str(value)?;
+				rest = &rest[name.len() ..];
+			}
+			else if let Some(digit) = rest[1 ..].chars().next().and_then(|c| c.to_digit(10)) {
+				out.write_str(group(digit as usize))?;
+				rest = &rest[2 ..];
+			}
+			else {
+				out.write_char('$')?;
+				rest = &rest[1 ..];
+			}
+		}
+
+		out.write_str(rest)?;
+		expand(out, &format[first.end() ..], &group)?;
 	}
 	else {
-		Cow::Borrowed(formatter.format())
-	}).into()
+		expand(out, format, &group)?;
+	}
+
+	out.write_str(&national[whole.end() ..])
+}
+
+/// Write the template of a `Format`, replacing `$N` with the Nth group.
+fn expand<'a, W: fmt::Write + ?Sized, G: Fn(usize) -> &'a str>(out: &mut W, template: &str, group: &G) -> fmt::Result {
+	let mut rest = template;
+	while let Some(index) = rest.find('$') {
+		out.write_str(&rest[.. index])?;
+		rest = &rest[index + 1 ..];
+
+		if let Some(digit) = rest.chars().next().and_then(|c| c.to_digit(10)) {
+			out.write_str(group(digit as usize))?;
+			rest = &rest[1 ..];
+		}
+		else {
+			out.write_char('$')?;
+		}
+	}
+
+	out.write_str(rest)
 }
 
 #[cfg(test)]
 mod test {
+	use regex::Regex;
 	use crate::parser;
 	use crate::formatter::Mode;
+	use crate::metadata::Format;
 	use crate::country;
+	use crate::context::Context;
 
 	#[test]
 	fn us() {
@@ -267,4 +581,133 @@ mod test {
 			parser::parse(Some(country::GB), "+44 7912345678").unwrap()
 				.format().mode(Mode::International).to_string());
 	}
+
+	#[test]
+	fn rule() {
+		let format = Format {
+			pattern: Regex::new(r"(\d{2})(\d{4})(\d{4})").unwrap(),
+			format:  "$1 $2 $3".into(),
+
+			leading_digits:           Vec::new(),
+			national_prefix:          Some("0$1".into()),
+			national_prefix_optional: false,
+			domestic_carrier:         None,
+		};
+
+		// Formatting rules can refer to the first group directly.
+		assert_eq!("020 7031 3000",
+			parser::parse(Some(country::GB), "+44 2070313000").unwrap()
+				.format().mode(Mode::National).with(&format).to_string());
+	}
+
+	#[test]
+	fn extension() {
+		assert_eq!("8 (495) 123-45-67 ext. 123",
+			parser::parse(Some(country::RU), "+7 495 123-45-67 доб. 123").unwrap()
+				.format().mode(Mode::National).to_string());
+
+		assert_eq!("8 (495) 123-45-67 доб. 123",
+			parser::parse(Some(country::RU), "+7 495 123-45-67 доб. 123").unwrap()
+				.format().mode(Mode::National).language("ru").to_string());
+
+		assert_eq!("030 1234567 ext. 89",
+			parser::parse(Some(country::DE), "030 1234567 Durchwahl 89").unwrap()
+				.format().mode(Mode::National).to_string());
+
+		assert_eq!("030 1234567 Durchwahl 89",
+			parser::parse(Some(country::DE), "030 1234567 ext. 89").unwrap()
+				.format().mode(Mode::National).language("de").to_string());
+
+		assert_eq!("+44 20 7031 3000 x123",
+			parser::parse(Some(country::GB), "020 7031 3000 poste 123").unwrap()
+				.format().mode(Mode::International).to_string());
+
+		assert_eq!("(650) 253-0000 ext. 4567",
+			parser::parse(Some(country::US), "650 253 0000 内線 4567").unwrap()
+				.format().mode(Mode::National).to_string());
+	}
+
+	#[test]
+	fn vanity() {
+		let number = parser::parse(Some(country::US), "1-800-FLOWERS").unwrap();
+
+		assert_eq!("+1 800-FLOWERS",
+			number.format().mode(Mode::International).vanity(true).to_string());
+
+		assert_eq!("800-FLOWERS",
+			number.format().mode(Mode::National).vanity(true).to_string());
+
+		assert_eq!("+1 800-356-9377",
+			number.format().mode(Mode::International).to_string());
+
+		assert_eq!("+18003569377",
+			number.format().vanity(true).to_string());
+	}
+
+	#[test]
+	fn rfc3966() {
+		assert_eq!("tel:+64-3-331-6005;ext=12;isub=1%202",
+			parser::parse(None, "tel:+64-3-331-6005;isub=1%202;ext=12").unwrap()
+				.format().mode(Mode::Rfc3966).to_string());
+
+		assert_eq!("tel:3-331-6005;phone-context=example.com",
+			parser::parse(Some(country::NZ), "tel:03-331-6005;phone-context=example.com").unwrap()
+				.format().mode(Mode::Rfc3966).to_string());
+	}
+
+	#[test]
+	fn sip() {
+		let host = String::from("example.com");
+
+		assert_eq!("sip:+1-415-555-0100;ext=12@example.com;user=phone",
+			parser::parse(None, "sip:+14155550100;ext=12@example.com;user=phone").unwrap()
+				.format().mode(Mode::SipUri { host: &host }).to_string());
+
+		assert_eq!("sip:+44-20-7031-3000@example.com;user=phone",
+			parser::parse(None, "<sips:+44-20-7031-3000@gateway.example.org>").unwrap()
+				.format().mode(Mode::SipUri { host: &host }).to_string());
+	}
+
+	#[test]
+	fn local() {
+		let number = parser::parse(None, "+1 650 253 0000").unwrap();
+
+		let context = Context::new(country::US).with_area_code("650");
+		assert_eq!("253-0000",
+			number.format().mode(Mode::Local { context: &context }).to_string());
+
+		let context = Context::new(country::US).with_area_code("415");
+		assert_eq!("(650) 253-0000",
+			number.format().mode(Mode::Local { context: &context }).to_string());
+
+		let context = Context::new(country::GB);
+		assert_eq!("+1 650-253-0000",
+			number.format().mode(Mode::Local { context: &context }).to_string());
+	}
+
+	#[test]
+	fn write_to() {
+		let host    = String::from("example.com");
+		let context = Context::new(country::US).with_area_code("650");
+		let modes   = [
+			Mode::E164, Mode::International, Mode::National, Mode::Rfc3966,
+			Mode::SipUri { host: &host }, Mode::Local { context: &context },
+		];
+
+		for number in &["+1 650 253 0000 ext. 12", "+39 06 1234 5678", "+44 20 7031 3000"] {
+			let number = parser::parse(None, number).unwrap();
+
+			for &mode in &modes {
+				let formatter = number.format().mode(mode);
+
+				let mut written = String::from("> ");
+				formatter.write_to(&mut written).unwrap();
+				assert_eq!(format!("> {}", formatter), written);
+
+				let mut written = String::new();
+				formatter.format_into(&mut written).unwrap();
+				assert_eq!(formatter.to_string(), written);
+			}
+		}
+	}
 }
@@ -14,11 +14,12 @@
 
 macro_rules! try_opt {
 	($ret:expr; $value:expr) => (
-		if let Some(value) = $value {
-			value
-		}
-		else {
-			return $ret;
+		match $value {
+			Some(value) =>
+				value,
+
+			None =>
+				return $ret,
 		}
 	);
 }
@@ -22,7 +22,7 @@ extern crate thiserror;
 extern crate nom;
 
 extern crate regex;
-extern crate regex_cache;
+extern crate regex_syntax;
 extern crate fnv;
 extern crate quick_xml as xml;
 extern crate itertools;
@@ -31,7 +31,7 @@ extern crate either;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
-extern crate bincode;
+extern crate serde_json;
 
 #[cfg(test)]
 #[macro_use]
@@ -45,7 +45,7 @@ mod helper;
 
 /// Errors for various parts of the crate.
 mod error;
-pub use crate::error::{Metadata as MetadataError, Parse as ParseError};
+pub use crate::error::{Metadata as MetadataError, LoadMetadata as LoadMetadataError, Location as MetadataLocation, Parse as ParseError, ParseKind as ParseErrorKind, Stage as ParseStage, DialPlan as DialPlanError};
 
 /// Phone number metadata, containing patterns, formatting and other useful
 /// data about countries and phone numbers.
@@ -67,11 +67,38 @@ pub use crate::extension::Extension;
 mod carrier;
 pub use crate::carrier::Carrier;
 
+mod subaddress;
+pub use crate::subaddress::Subaddress;
+
+mod domain;
+pub use crate::domain::Domain;
+
+mod vanity;
+pub use crate::vanity::{Vanity, convert_alpha_characters};
+
 mod phone_number;
 pub use crate::phone_number::{PhoneNumber, Type};
 
+mod context;
+pub use crate::context::Context;
+
+mod trace;
+pub use crate::trace::{Trace, Decision, Syntax};
+
 mod parser;
-pub use crate::parser::{parse, parse_with};
+pub use crate::parser::{parse, parse_with, parse_explained, parse_explained_with, parse_in_context, parse_in_context_with};
+
+mod normalize;
+pub use crate::normalize::{normalize_digits_only, normalize_diallable_chars_only, extract_possible_number, is_alpha_number};
+
+mod dial_string;
+pub use crate::dial_string::{DialString, Step, Device, Dial};
+
+mod batch;
+pub use crate::batch::{BatchParser, Batch, Stream as BatchStream};
+
+mod dial_plan;
+pub use crate::dial_plan::{DialPlan, Origin, dial_plan, dial_plan_with};
 
 mod formatter;
 pub use crate::formatter::{Mode, Formatter, format, format_with};
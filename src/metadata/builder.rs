@@ -0,0 +1,626 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+use std::fs::File;
+use std::io::{BufRead, Cursor, BufReader};
+use std::sync::{Arc, OnceLock};
+
+use crate::error;
+use crate::metadata::{DATABASE, Database, PrivatePlan, Version, CLASSIFIED, loader};
+use crate::metadata::database::{Region, compile};
+use crate::metadata::matcher::Matcher;
+use crate::phone_number::Type;
+use crate::consts;
+
+/// Builder for a `Database` on top of another one, applying overlays to its
+/// regions.
+///
+/// Regions left untouched are shared with the original database, changed and
+/// added regions are compiled by `build`, which also reports the first overlay
+/// that failed.
+///
+/// Overlays work on the `loader` representation of the metadata, the one of
+/// the `PhoneNumberMetadata.xml` schema.
+///
+/// The database keeps the version name of the original one, with a new hash
+/// when anything changed.
+#[derive(Debug)]
+pub struct DatabaseBuilder {
+	regions: Vec<Entry>,
+	private: Vec<Arc<PrivatePlan>>,
+	name:    Option<String>,
+	version: OnceLock<Version>,
+	changed: bool,
+	error:   Option<error::LoadMetadata>,
+}
+
+#[derive(Debug)]
+enum Entry {
+	Kept(Arc<Region>),
+	Changed(Box<loader::Metadata>),
+}
+
+impl Entry {
+	fn is(&self, id: &str, code: Option<u16>) -> bool {
+		let (this, country_code) = match self {
+			Entry::Kept(region) =>
+				(region.id(), Some(region.country_code())),
+
+			Entry::Changed(meta) =>
+				(meta.id.as_deref().unwrap_or(""), meta.country_code),
+		};
+
+		this == id && (code.is_none() || code == country_code)
+	}
+
+	fn source(&mut self) -> &mut loader::Metadata {
+		if let Entry::Kept(region) = self {
+			*self = Entry::Changed(Box::new(region.source()));
+		}
+
+		match self {
+			Entry::Changed(meta) =>
+				meta,
+
+			Entry::Kept(..) =>
+				unreachable!(),
+		}
+	}
+}
+
+impl Default for DatabaseBuilder {
+	fn default() -> Self {
+		DatabaseBuilder::new()
+	}
+}
+
+impl DatabaseBuilder {
+	/// Create a builder on top of the default `Database`.
+	pub fn new() -> Self {
+		DatabaseBuilder::with_database(&DATABASE)
+	}
+
+	/// Create a builder on top of the given `Database`.
+	pub fn with_database(database: &Database) -> Self {
+		DatabaseBuilder {
+			regions: database.regions().into_iter().map(Entry::Kept).collect(),
+			private: database.plans(),
+			name:    database.name.clone(),
+			version: database.version.clone(),
+			changed: false,
+			error:   None,
+		}
+	}
+
+	/// Change the region with the given ID.
+	///
+	/// The non-geographical entities all share the "001" ID, use
+	/// `patch_non_geographic` for them.
+	pub fn patch<F: FnOnce(&mut loader::Metadata)>(self, id: &str, patch: F) -> Self {
+		self.change(id, None, patch)
+	}
+
+	/// Change the non-geographical entity for the given country code.
+	pub fn patch_non_geographic<F: FnOnce(&mut loader::Metadata)>(self, code: u16, patch: F) -> Self {
+		self.change("001", Some(code), patch)
+	}
+
+	/// Replace the descriptor for the given type, `Type::Unknown` being the
+	/// general descriptor.
+	///
+	/// The general descriptor is extended to cover the new pattern as well.
+	pub fn replace_descriptor(self, id: &str, kind: Type, descriptor: loader::Descriptor) -> Self {
+		self.patch(id, |meta| {
+			extend_general(meta, kind, &descriptor);
+			*descriptor_mut(meta, kind) = Some(descriptor);
+		})
+	}
+
+	/// Extend the descriptor for the given type, `Type::Unknown` being the
+	/// general descriptor, so it also matches the pattern and lengths of the
+	/// given one.
+	///
+	/// The general descriptor is extended to cover the new pattern as well.
+	pub fn extend_descriptor(self, id: &str, kind: Type, descriptor: loader::Descriptor) -> Self {
+		self.patch(id, |meta| {
+			extend_general(meta, kind, &descriptor);
+			extend(descriptor_mut(meta, kind), descriptor);
+		})
+	}
+
+	/// Add a format, used both nationally and internationally, in front of the
+	/// existing ones, with the rules of the territory unless it has its own.
+	pub fn add_format(self, id: &str, mut format: loader::Format) -> Self {
+		self.patch(id, |meta| {
+			inherit(meta, std::iter::once(&mut format));
+
+			meta.formats.insert(0, format.clone());
+			meta.international_formats.insert(0, format);
+		})
+	}
+
+	/// Override the national prefix.
+	pub fn national_prefix<S: Into<String>>(self, id: &str, prefix: Option<S>) -> Self {
+		self.patch(id, |meta| meta.national_prefix = prefix.map(Into::into))
+	}
+
+	/// Add a new territory.
+	pub fn add_territory(mut self, meta: loader::Metadata) -> Self {
+		let id = meta.id.clone().unwrap_or_default();
+
+		if self.regions.iter().any(|r| r.is(&id, meta.country_code.filter(|_| id == "001"))) {
+			return self.fail(error::Metadata::DuplicateRegion { id }.into());
+		}
+
+		self.regions.push(Entry::Changed(Box::new(meta)));
+		self.changed = true;
+		self
+	}
+
+	/// Register a private numbering plan, tried in the order of registration
+	/// before any region when parsing.
+	///
+	/// The plan is described like a territory without a country code, dialling
+	/// the outside line prefix leaves the plan for the public network.
+	pub fn private_plan<S: Into<String>>(mut self, meta: loader::Metadata, outside_line: Option<S>) -> Self {
+		let id = meta.id.clone().unwrap_or_default();
+
+		if self.private.iter().any(|p| p.id() == id) {
+			return self.fail(error::Metadata::DuplicateRegion { id }.into());
+		}
+
+		match PrivatePlan::new(meta, outside_line.map(Into::into)) {
+			Ok(plan) => {
+				self.private.push(Arc::new(plan));
+				self.changed = true;
+			}
+
+			Err(err) =>
+				return self.fail(err),
+		}
+
+		self
+	}
+
+	/// Apply the overlay in the given file, see `overlay`.
+	pub fn load_overlay<P: AsRef<Path>>(self, path: P) -> Self {
+		match File::open(path) {
+			Ok(file) =>
+				self.overlay(BufReader::new(file)),
+
+			Err(err) =>
+				self.fail(err.into()),
+		}
+	}
+
+	/// Apply the overlay in the given string, see `overlay`.
+	pub fn parse_overlay<S: AsRef<str>>(self, content: S) -> Self {
+		self.overlay(Cursor::new(content.as_ref()))
+	}
+
+	/// Apply an overlay in the `PhoneNumberMetadata.xml` schema.
+	///
+	/// Territories missing from the database are added as they are. For the
+	/// others, descriptors extend the existing ones, formats go in front of the
+	/// existing ones with the rules of the territory unless they have their
+	/// own, and attributes override the existing ones, flags can only be set.
+	///
+	/// An overlay with a `version` attribute on its root element is refused
+	/// unless the database is for that version.
+	pub fn overlay<R: BufRead>(mut self, reader: R) -> Self {
+		let loaded = match loader::load_versioned(reader) {
+			Ok(loaded) =>
+				loaded,
+
+			Err(err) =>
+				return self.fail(err),
+		};
+
+		if let (Some(expected), Some(found)) = (self.name.as_ref(), loaded.version.as_ref()) {
+			if expected != found {
+				let err = error::LoadMetadata::VersionMismatch {
+					expected: expected.into(),
+					found:    found.clone(),
+				};
+
+				return self.fail(err);
+			}
+		}
+
+		for territory in loaded.territories {
+			let id   = territory.id.clone().unwrap_or_default();
+			let code = territory.country_code.filter(|_| id == "001");
+
+			if self.regions.iter().any(|r| r.is(&id, code)) {
+				self = self.change(&id, code, |meta| merge(meta, territory));
+			}
+			else {
+				self = self.add_territory(territory);
+			}
+		}
+
+		self
+	}
+
+	/// Compile the changed regions and build the database.
+	pub fn build(self) -> Result<Database, error::LoadMetadata> {
+		if let Some(err) = self.error {
+			return Err(err);
+		}
+
+		let regions = self.regions.into_iter().map(|entry| match entry {
+			Entry::Kept(region) =>
+				Ok(region),
+
+			Entry::Changed(meta) =>
+				Ok(Arc::new(Region::Compiled(compile(*meta)?))),
+		}).collect::<Result<Vec<_>, error::LoadMetadata>>()?;
+
+		let mut database = Database::with_regions(regions, self.private, self.name);
+
+		// Nothing changed, so neither did the version, if already known.
+		if !self.changed {
+			database.version = self.version;
+		}
+
+		Ok(database)
+	}
+
+	fn change<F: FnOnce(&mut loader::Metadata)>(mut self, id: &str, code: Option<u16>, patch: F) -> Self {
+		if self.error.is_some() {
+			return self;
+		}
+
+		if let Some(entry) = self.regions.iter_mut().find(|r| r.is(id, code)) {
+			patch(entry.source());
+			self.changed = true;
+			self
+		}
+		else {
+			self.fail(error::Metadata::UnknownRegion { id: id.into() }.into())
+		}
+	}
+
+	fn fail(mut self, err: error::LoadMetadata) -> Self {
+		if self.error.is_none() {
+			self.error = Some(err);
+		}
+
+		self
+	}
+}
+
+/// Every descriptor type, `Type::Unknown` being the general descriptor.
+const DESCRIPTORS: [Type; 16] = [
+	Type::Unknown, Type::FixedLine, Type::Mobile, Type::TollFree,
+	Type::PremiumRate, Type::SharedCost, Type::PersonalNumber, Type::Voip,
+	Type::Pager, Type::Uan, Type::Emergency, Type::Voicemail, Type::ShortCode,
+	Type::StandardRate, Type::Carrier, Type::NoInternational,
+];
+
+fn descriptor_mut(meta: &mut loader::Metadata, kind: Type) -> &mut Option<loader::Descriptor> {
+	match kind {
+		Type::Unknown =>
+			&mut meta.general,
+
+		Type::FixedLine |
+		Type::FixedLineOrMobile =>
+			&mut meta.fixed_line,
+
+		Type::Mobile =>
+			&mut meta.mobile,
+
+		Type::TollFree =>
+			&mut meta.toll_free,
+
+		Type::PremiumRate =>
+			&mut meta.premium_rate,
+
+		Type::SharedCost =>
+			&mut meta.shared_cost,
+
+		Type::PersonalNumber =>
+			&mut meta.personal_number,
+
+		Type::Voip =>
+			&mut meta.voip,
+
+		Type::Pager =>
+			&mut meta.pager,
+
+		Type::Uan =>
+			&mut meta.uan,
+
+		Type::Emergency =>
+			&mut meta.emergency,
+
+		Type::Voicemail =>
+			&mut meta.voicemail,
+
+		Type::ShortCode =>
+			&mut meta.short_code,
+
+		Type::StandardRate =>
+			&mut meta.standard_rate,
+
+		Type::Carrier =>
+			&mut meta.carrier,
+
+		Type::NoInternational =>
+			&mut meta.no_international,
+	}
+}
+
+/// Extend the descriptor to also match the pattern of the given one.
+fn extend(target: &mut Option<loader::Descriptor>, descriptor: loader::Descriptor) {
+	fn union(target: &mut Vec<u16>, lengths: Vec<u16>) {
+		target.extend(lengths);
+		target.sort_unstable();
+		target.dedup();
+	}
+
+	let target = if let Some(target) = target { target } else {
+		*target = Some(descriptor);
+		return;
+	};
+
+	// No lengths means any length, which is kept, but a pattern given without
+	// lengths still needs its own lengths to be possible when the target has
+	// some, so they're derived from the pattern.
+	if !target.possible_length.is_empty() {
+		let lengths = if descriptor.possible_length.is_empty() {
+			descriptor.national_number.as_deref()
+				.map(|pattern| Matcher::new(&[Some(pattern)])
+					.map(|m| m.lengths(1, consts::MAX_LENGTH_FOR_NSN)))
+				.unwrap_or(Some(Vec::new()))
+		}
+		else {
+			Some(descriptor.possible_length)
+		};
+
+		match lengths {
+			Some(lengths) =>
+				union(&mut target.possible_length, lengths),
+
+			// The lengths of the pattern can't be known, so any length is.
+			None =>
+				target.possible_length.clear(),
+		}
+	}
+
+	target.national_number = match (target.national_number.take(), descriptor.national_number) {
+		(Some(current), Some(pattern)) =>
+			Some(format!("(?:{})|(?:{})", current, pattern)),
+
+		(current, pattern) =>
+			current.or(pattern),
+	};
+
+	union(&mut target.possible_local_length, descriptor.possible_local_length);
+
+	if target.example.is_none() {
+		target.example = descriptor.example;
+	}
+}
+
+/// Extend the general descriptor to cover a descriptor used to find the type
+/// of a number.
+fn extend_general(meta: &mut loader::Metadata, kind: Type, descriptor: &loader::Descriptor) {
+	if kind != Type::Unknown && CLASSIFIED.contains(&kind) {
+		extend(&mut meta.general, loader::Descriptor {
+			example: None,
+			.. descriptor.clone()
+		});
+	}
+}
+
+/// Give the formats without rules of their own the rules of the territory, or
+/// the ones all its formats agree on when it only has them per format.
+fn inherit<'a, I: Iterator<Item = &'a mut loader::Format>>(meta: &loader::Metadata, formats: I) {
+	fn rule<F: Fn(&loader::Format) -> Option<&String>>(meta: &loader::Metadata, territory: Option<&String>, get: F) -> Option<String> {
+		let mut rules = meta.formats.iter().map(get);
+		let first     = rules.next().flatten();
+
+		territory.or_else(|| first.filter(|&f| rules.all(|r| r == Some(f)))).cloned()
+	}
+
+	let national_prefix = rule(meta, meta.national_prefix_formatting_rule.as_ref(),
+		|f| f.national_prefix_formatting_rule.as_ref());
+	let carrier = rule(meta, meta.carrier_code_formatting_rule.as_ref(),
+		|f| f.domestic_carrier.as_ref());
+
+	for format in formats {
+		if format.national_prefix_formatting_rule.is_none() {
+			format.national_prefix_formatting_rule = national_prefix.clone();
+		}
+
+		if format.domestic_carrier.is_none() {
+			format.domestic_carrier = carrier.clone();
+		}
+
+		format.national_prefix_optional_when_formatting |= meta.national_prefix_optional_when_formatting;
+	}
+}
+
+/// Merge an overlay territory into an existing one.
+fn merge(meta: &mut loader::Metadata, mut overlay: loader::Metadata) {
+	for &kind in &DESCRIPTORS {
+		if let Some(descriptor) = descriptor_mut(&mut overlay, kind).take() {
+			extend_general(meta, kind, &descriptor);
+			extend(descriptor_mut(meta, kind), descriptor);
+		}
+	}
+
+	fn replace<T>(target: &mut Option<T>, value: Option<T>) {
+		if value.is_some() {
+			*target = value;
+		}
+	}
+
+	replace(&mut meta.international_prefix, overlay.international_prefix);
+	replace(&mut meta.preferred_international_prefix, overlay.preferred_international_prefix);
+	replace(&mut meta.national_prefix, overlay.national_prefix);
+	replace(&mut meta.preferred_extension_prefix, overlay.preferred_extension_prefix);
+	replace(&mut meta.national_prefix_for_parsing, overlay.national_prefix_for_parsing);
+	replace(&mut meta.national_prefix_transform_rule, overlay.national_prefix_transform_rule);
+	replace(&mut meta.leading_digits, overlay.leading_digits);
+	replace(&mut meta.national_prefix_formatting_rule, overlay.national_prefix_formatting_rule);
+	replace(&mut meta.carrier_code_formatting_rule, overlay.carrier_code_formatting_rule);
+
+	// Flags can only be turned on, an overlay has no way to tell a flag it
+	// doesn't set from one it turns off.
+	meta.main_country_for_code  |= overlay.main_country_for_code;
+	meta.mobile_number_portable |= overlay.mobile_number_portable;
+
+	meta.same_mobile_and_fixed_line_pattern       |= overlay.same_mobile_and_fixed_line_pattern;
+	meta.national_prefix_optional_when_formatting |= overlay.national_prefix_optional_when_formatting;
+
+	for (target, descriptor) in [
+		(&mut meta.sms_services, overlay.sms_services),
+		(&mut meta.expanded_emergency, overlay.expanded_emergency),
+		(&mut meta.area_code_optional, overlay.area_code_optional),
+	] {
+		if let Some(descriptor) = descriptor {
+			extend(target, descriptor);
+		}
+	}
+
+	meta.references.append(&mut overlay.references);
+
+	// Formats of the overlay inherited the rules of the overlay territory when
+	// loaded, which usually has none.
+	inherit(meta, overlay.formats.iter_mut().chain(overlay.international_formats.iter_mut()));
+
+	overlay.formats.append(&mut meta.formats);
+	meta.formats = overlay.formats;
+
+	overlay.international_formats.append(&mut meta.international_formats);
+	meta.international_formats = overlay.international_formats;
+}
+
+#[cfg(test)]
+mod test {
+	use crate::metadata::{DatabaseBuilder, loader};
+	use crate::phone_number::Type;
+	use crate::formatter::Mode;
+	use crate::parser;
+	use crate::validator;
+	use crate::country;
+
+	#[test]
+	fn programmatic() {
+		// 6123 isn't an allocated range in GB.
+		let number = parser::parse(Some(country::GB), "06123 456789").unwrap();
+		assert!(!number.is_valid());
+
+		let database = DatabaseBuilder::new()
+			.extend_descriptor("GB", Type::Mobile, loader::Descriptor {
+				national_number: Some("6123\\d{6}".into()),
+				.. Default::default()
+			})
+			.add_format("GB", loader::Format {
+				pattern: Some("(6123)(\\d{3})(\\d{3})".into()),
+				format:  Some("$1 $2 $3".into()),
+				.. Default::default()
+			})
+			.build().unwrap();
+
+		let number = parser::parse_with(&database, Some(country::GB), "06123 456789").unwrap();
+		assert!(number.is_valid_with(&database));
+		assert_eq!(Type::Mobile, validator::number_type(number.metadata(&database).unwrap(), "6123456789"));
+		assert_eq!("06123 456 789", number.format_with(&database).mode(Mode::National).to_string());
+
+		// Lengths missing from the descriptor are derived from its pattern.
+		let database = DatabaseBuilder::new()
+			.extend_descriptor("GB", Type::Mobile, loader::Descriptor {
+				national_number: Some("6124\\d{7}".into()),
+				.. Default::default()
+			})
+			.build().unwrap();
+
+		let number = parser::parse_with(&database, Some(country::GB), "06124 4567890").unwrap();
+		assert!(number.is_valid_with(&database));
+		assert_eq!(Type::Mobile, validator::number_type(number.metadata(&database).unwrap(), "61244567890"));
+		assert!(database.by_id("GB").unwrap().descriptors().mobile().unwrap().possible_length().contains(&11));
+
+		// Other regions are untouched.
+		let number = parser::parse_with(&database, None, "+1 650 253 0000").unwrap();
+		assert!(number.is_valid_with(&database));
+
+		let database = DatabaseBuilder::new()
+			.national_prefix("DE", Some("7"))
+			.build().unwrap();
+		assert_eq!(Some("7"), database.by_id("DE").unwrap().national_prefix());
+
+		let error = DatabaseBuilder::new()
+			.national_prefix("XX", None::<String>)
+			.build().unwrap_err();
+		assert_eq!("unknown region: \"XX\"", error.to_string());
+	}
+
+	#[test]
+	fn overlay() {
+		let database = DatabaseBuilder::new().parse_overlay(r#"
+			<phoneNumberMetadata>
+				<territories>
+					<territory id="GB" countryCode="44">
+						<availableFormats>
+							<numberFormat pattern="(6123)(\d{3})(\d{3})">
+								<leadingDigits>6123</leadingDigits>
+								<format>$1 $2 $3</format>
+							</numberFormat>
+						</availableFormats>
+						<mobile>
+							<nationalNumberPattern>6123\d{6}</nationalNumberPattern>
+						</mobile>
+					</territory>
+
+					<territory id="XK" countryCode="383" internationalPrefix="00" nationalPrefix="0">
+						<generalDesc>
+							<nationalNumberPattern>[23]\d{7}</nationalNumberPattern>
+						</generalDesc>
+						<fixedLine>
+							<possibleLengths national="8"/>
+							<nationalNumberPattern>[23]\d{7}</nationalNumberPattern>
+						</fixedLine>
+					</territory>
+				</territories>
+			</phoneNumberMetadata>
+		"#).build().unwrap();
+
+		let number = parser::parse_with(&database, Some(country::GB), "06123 456789").unwrap();
+		assert!(number.is_valid_with(&database));
+
+		// The format has the national prefix rule of the territory.
+		assert_eq!("06123 456 789", number.format_with(&database).mode(Mode::National).to_string());
+
+		let number = parser::parse_with(&database, None, "+383 38 123 456").unwrap();
+		assert_eq!(383, number.code().value());
+		assert!(number.is_valid_with(&database));
+		assert_eq!(Some("XK"), database.region(&383).and_then(|mut r| r.next()));
+
+		assert!(DatabaseBuilder::new().parse_overlay(r#"
+			<phoneNumberMetadata>
+				<territories>
+					<territory id="GB" countryCode="44">
+						<mobile>
+							<nationalNumberPattern>7400(\d{6}</nationalNumberPattern>
+						</mobile>
+					</territory>
+				</territories>
+			</phoneNumberMetadata>
+		"#).build().is_err());
+	}
+}
@@ -16,221 +16,736 @@ use std::path::Path;
 use std::fs::File;
 use std::io::{Cursor, BufReader};
 use std::borrow::Borrow;
-use std::hash::Hash;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, OnceLock};
 
-use bincode::Options;
+use either::{Left, Right};
 use fnv::FnvHashMap;
-use regex_cache::{RegexCache, CachedRegex, CachedRegexBuilder};
-use bincode;
+use regex::{Regex, RegexBuilder};
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de};
 
 use crate::error;
-use crate::metadata::loader;
-
-const DATABASE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/database.bin"));
+use crate::metadata::{loader, generated, PrivatePlan, Version};
+use crate::metadata::matcher::Matcher;
+use crate::metadata::CLASSIFIED;
+use crate::country;
 
 lazy_static! {
 	/// The Google provided metadata database, used as default.
 	pub static ref DEFAULT: Database =
-		Database::from(bincode::options()
-		.with_varint_encoding().deserialize(DATABASE).unwrap()).unwrap();
+		Database {
+			backend: Backend::Generated,
+			private: Vec::new(),
+			name:    generated::VERSION.map(String::from),
+			version: OnceLock::new(),
+		};
+}
+
+#[cfg(test)]
+lazy_static! {
+	/// The bundled metadata loaded at runtime, shared by the tests since
+	/// compiling every region takes a while.
+	pub(crate) static ref LOADED: Database =
+		Database::load("assets/PhoneNumberMetadata.xml").unwrap();
 }
 
 /// Representation of a database of metadata for phone number.
+///
+/// The patterns of a region are compiled once, and can then be matched from
+/// any number of threads at once. Loaded databases compile every region up
+/// front to report invalid patterns, the default one is made of static tables
+/// generated at build time and compiles a region the first time it is
+/// accessed.
+///
+/// A database can also hold private numbering plans, which are tried before
+/// any region when parsing.
+///
+/// Every database knows the version of its metadata, see `Version`.
 #[derive(Clone, Debug)]
 pub struct Database {
-	cache:   Arc<Mutex<RegexCache>>,
-	by_id:   FnvHashMap<String, Arc<super::Metadata>>,
-	by_code: FnvHashMap<u16, Vec<Arc<super::Metadata>>>,
-	regions: FnvHashMap<u16, Vec<String>>,
+	backend: Backend,
+	private: Vec<Arc<PrivatePlan>>,
+
+	pub(crate) name:    Option<String>,
+	pub(crate) version: OnceLock<Version>,
+}
+
+#[derive(Clone, Debug)]
+enum Backend {
+	/// The static tables in `generated`.
+	Generated,
+
+	/// Metadata loaded at runtime, or built on top of another database.
+	Loaded {
+		by_id:   FnvHashMap<String, Arc<Region>>,
+		by_code: FnvHashMap<u16, Vec<Arc<Region>>>,
+		regions: FnvHashMap<u16, Vec<String>>,
+	},
+}
+
+/// The JSON representation of a database.
+#[derive(Serialize, Deserialize)]
+struct Json {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	version: Option<Version>,
+
+	territories: Vec<loader::Metadata>,
+
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	private_plans: Vec<JsonPlan>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonPlan {
+	#[serde(flatten)]
+	metadata: loader::Metadata,
+
+	#[serde(default)]
+	outside_line: Option<String>,
+}
+
+/// A region of a loaded database, always behind an `Arc`.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Region {
+	/// Metadata compiled when the database was created.
+	Compiled(super::Metadata),
+
+	/// A region of the generated tables, compiled on first access.
+	Generated(usize),
+}
+
+impl Region {
+	pub fn get(&self) -> &super::Metadata {
+		match self {
+			Region::Compiled(meta) =>
+				meta,
+
+			Region::Generated(index) =>
+				compiled(*index),
+		}
+	}
+
+	pub fn id(&self) -> &str {
+		match self {
+			Region::Compiled(meta) =>
+				&meta.id,
+
+			Region::Generated(index) =>
+				generated::REGIONS[*index].id,
+		}
+	}
+
+	pub fn country_code(&self) -> u16 {
+		match self {
+			Region::Compiled(meta) =>
+				meta.country_code,
+
+			Region::Generated(index) =>
+				generated::REGIONS[*index].country_code,
+		}
+	}
+
+	pub fn is_main_country_for_code(&self) -> bool {
+		match self {
+			Region::Compiled(meta) =>
+				meta.main_country_for_code,
+
+			Region::Generated(index) =>
+				generated::REGIONS[*index].main_country_for_code,
+		}
+	}
+
+	/// Get the source of the region, to change and compile it again.
+	pub fn source(&self) -> loader::Metadata {
+		match self {
+			Region::Compiled(meta) =>
+				meta.into(),
+
+			Region::Generated(index) =>
+				(&generated::REGIONS[*index]).into(),
+		}
+	}
 }
 
 impl Database {
 	/// Load a database from the given file.
+	///
+	/// The version is named after the `version` attribute of the root element,
+	/// if any.
 	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, error::LoadMetadata> {
-		Database::from(loader::load(BufReader::new(File::open(path)?))?)
+		Database::from_loaded(loader::load_versioned(BufReader::new(File::open(path)?))?)
 	}
 
-	/// Parse a database from the given string.
+	/// Parse a database from the given string, see `load`.
 	pub fn parse<S: AsRef<str>>(content: S) -> Result<Self, error::LoadMetadata> {
-		Database::from(loader::load(Cursor::new(content.as_ref()))?)
+		Database::from_loaded(loader::load_versioned(Cursor::new(content.as_ref()))?)
 	}
 
-	/// Create a database from a loaded database.
-	pub fn from(meta: Vec<loader::Metadata>) -> Result<Self, error::LoadMetadata> {
-		fn tranpose<T, E>(value: Option<Result<T, E>>) -> Result<Option<T>, E> {
-			match value {
-				None =>
-					Ok(None),
+	/// Parse a database from the given JSON, as written by `to_json`.
+	///
+	/// The version is named after the one written in the JSON, if any, whose
+	/// hash must be the one of the loaded metadata.
+	pub fn from_json<S: AsRef<str>>(content: S) -> Result<Self, error::LoadMetadata> {
+		Database::from_parsed_json(serde_json::from_str(content.as_ref())?)
+	}
+
+	/// Parse a database from the given JSON, as written by `to_json`, refusing
+	/// it unless it is for the expected version.
+	pub fn from_json_checked<S: AsRef<str>>(content: S, expected: &Version) -> Result<Self, error::LoadMetadata> {
+		let json: Json = serde_json::from_str(content.as_ref())?;
+
+		if json.version.is_none() {
+			return Err(error::LoadMetadata::VersionMismatch {
+				expected: expected.to_string(),
+				found:    "none".into(),
+			});
+		}
 
-				Some(Ok(value)) =>
-					Ok(Some(value)),
+		let database = Database::from_parsed_json(json)?;
+		database.version().check(expected)?;
 
-				Some(Err(err)) =>
-					Err(err),
+		Ok(database)
+	}
+
+	fn from_parsed_json(json: Json) -> Result<Self, error::LoadMetadata> {
+		let regions = json.territories.into_iter()
+			.map(|meta| Ok(Arc::new(Region::Compiled(compile(meta)?))))
+			.collect::<Result<Vec<_>, error::LoadMetadata>>()?;
+
+		let private = json.private_plans.into_iter()
+			.map(|plan| Ok(Arc::new(PrivatePlan::new(plan.metadata, plan.outside_line)?)))
+			.collect::<Result<Vec<_>, error::LoadMetadata>>()?;
+
+		let database = Database::with_regions(regions, private,
+			json.version.as_ref().and_then(|v| v.name.clone()));
+
+		// The written version is only a claim, the content decides.
+		if let Some(claimed) = json.version {
+			if database.version().hash() != claimed.hash() {
+				return Err(error::LoadMetadata::VersionMismatch {
+					expected: claimed.to_string(),
+					found:    database.version().to_string(),
+				});
 			}
 		}
 
-		let cache = Arc::new(Mutex::new(RegexCache::new(100)));
-		let regex = |value: String| -> Result<CachedRegex, error::LoadMetadata> {
-			Ok(CachedRegexBuilder::new(cache.clone(), &value)
-				.ignore_whitespace(true).build()?)
-		};
+		Ok(database)
+	}
 
-		let descriptor = |desc: loader::Descriptor| -> Result<super::Descriptor, error::LoadMetadata> {
-			desc.national_number.as_ref().unwrap();
-			desc.national_number.as_ref().unwrap();
-
-			Ok(super::Descriptor {
-				national_number: desc.national_number.ok_or_else(||
-					error::LoadMetadata::from(error::Metadata::MissingValue {
-						phase: "descriptor".into(),
-						name:  "national_number".into(),
-					})).and_then(&regex)?,
-
-				possible_length: desc.possible_length,
-				possible_local_length: desc.possible_local_length,
-				example: desc.example,
-			})
-		};
+	/// Write the database as pretty printed JSON.
+	///
+	/// Territories are in the `loader` representation, the one of the
+	/// `PhoneNumberMetadata.xml` schema, sorted by country code with the main
+	/// country first.
+	pub fn to_json(&self) -> Result<String, error::LoadMetadata> {
+		Ok(serde_json::to_string_pretty(&Json {
+			version: Some(self.version().clone()),
+			.. self.json()
+		})?)
+	}
+
+	/// The JSON representation, without the version.
+	fn json(&self) -> Json {
+		Json {
+			version: None,
+
+			territories: self.regions().iter()
+				.map(|r| r.source())
+				.collect(),
+
+			private_plans: self.private.iter()
+				.map(|p| JsonPlan {
+					metadata:     (&p.meta).into(),
+					outside_line: p.outside_line.clone(),
+				})
+				.collect(),
+		}
+	}
+
+	/// Create a database from a loaded database.
+	///
+	/// The version has no name.
+	pub fn from(meta: Vec<loader::Metadata>) -> Result<Self, error::LoadMetadata> {
+		meta.into_iter()
+			.map(|meta| Ok(Arc::new(Region::Compiled(compile(meta)?))))
+			.collect::<Result<Vec<_>, error::LoadMetadata>>()
+			.map(|regions| Database::with_regions(regions, Vec::new(), None))
+	}
 
-		let format = |format: loader::Format| -> Result<super::Format, error::LoadMetadata> {
-			Ok(super::Format {
-				pattern: format.pattern.ok_or_else(||
-					error::LoadMetadata::from(error::Metadata::MissingValue {
-						phase: "format".into(),
-						name:  "pattern".into(),
-					})).and_then(&regex)?,
+	fn from_loaded(loaded: loader::Loaded) -> Result<Self, error::LoadMetadata> {
+		let regions = loaded.territories.into_iter()
+			.map(|meta| Ok(Arc::new(Region::Compiled(compile(meta)?))))
+			.collect::<Result<Vec<_>, error::LoadMetadata>>()?;
 
-				format: format.format.ok_or_else(||
-					error::LoadMetadata::from(error::Metadata::MissingValue {
-						phase: "format".into(),
-						name:  "format".into()
-					}))?,
+		Ok(Database::with_regions(regions, Vec::new(), loaded.version))
+	}
 
-				leading_digits: format.leading_digits.into_iter()
-					.map(&regex).collect::<Result<_, _>>()?,
+	/// The version of the metadata.
+	///
+	/// The hash is computed the first time it's needed.
+	pub fn version(&self) -> &Version {
+		self.version.get_or_init(|| Version::of(self.name.clone(), &self.json()))
+	}
 
-				national_prefix:          format.national_prefix_formatting_rule,
-				national_prefix_optional: format.national_prefix_optional_when_formatting,
+	/// Name the version of the metadata, for sources that don't say which
+	/// libphonenumber release they are.
+	pub fn with_version<S: Into<String>>(mut self, name: S) -> Self {
+		self.name = Some(name.into());
 
-				domestic_carrier: format.domestic_carrier,
-			})
-		};
+		if let Some(version) = self.version.get_mut() {
+			version.name = self.name.clone();
+		}
 
-		let metadata = |meta: loader::Metadata| -> Result<super::Metadata, error::LoadMetadata> {
-			Ok(super::Metadata {
-				descriptors: super::Descriptors {
-					general: descriptor(meta.general.ok_or_else(||
-						error::LoadMetadata::from(error::Metadata::MissingValue {
-							phase: "metadata".into(),
-							name:  "generalDesc".into(),
-						}))?)?,
-
-					fixed_line:       tranpose(meta.fixed_line.map(&descriptor))?,
-					mobile:           tranpose(meta.mobile.map(&descriptor))?,
-					toll_free:        tranpose(meta.toll_free.map(&descriptor))?,
-					premium_rate:     tranpose(meta.premium_rate.map(&descriptor))?,
-					shared_cost:      tranpose(meta.shared_cost.map(&descriptor))?,
-					personal_number:  tranpose(meta.personal_number.map(&descriptor))?,
-					voip:             tranpose(meta.voip.map(&descriptor))?,
-					pager:            tranpose(meta.pager.map(&descriptor))?,
-					uan:              tranpose(meta.uan.map(&descriptor))?,
-					emergency:        tranpose(meta.emergency.map(&descriptor))?,
-					voicemail:        tranpose(meta.voicemail.map(&descriptor))?,
-					short_code:       tranpose(meta.short_code.map(&descriptor))?,
-					standard_rate:    tranpose(meta.standard_rate.map(&descriptor))?,
-					carrier:          tranpose(meta.carrier.map(&descriptor))?,
-					no_international: tranpose(meta.no_international.map(&descriptor))?,
-				},
-
-				id: meta.id.ok_or_else(||
-					error::LoadMetadata::from(error::Metadata::MissingValue {
-						phase: "metadata".into(),
-						name:  "id".into()
-					}))?,
-
-				country_code: meta.country_code.ok_or_else(||
-					error::LoadMetadata::from(error::Metadata::MissingValue {
-						phase: "metadata".into(),
-						name: "countryCode".into(),
-					}))?,
-
-				international_prefix: tranpose(meta.international_prefix.map(&regex))?,
-				preferred_international_prefix: meta.preferred_international_prefix,
-				national_prefix: meta.national_prefix,
-				preferred_extension_prefix: meta.preferred_extension_prefix,
-				national_prefix_for_parsing: tranpose(meta.national_prefix_for_parsing.map(&regex))?,
-				national_prefix_transform_rule: meta.national_prefix_transform_rule,
-
-				formats: meta.formats.into_iter().map(&format).collect::<Result<_, _>>()?,
-				international_formats: meta.international_formats.into_iter().map(&format).collect::<Result<_, _>>()?,
-
-				main_country_for_code: meta.main_country_for_code,
-				leading_digits: tranpose(meta.leading_digits.map(&regex))?,
-				mobile_number_portable: meta.mobile_number_portable,
-			})
-		};
+		self
+	}
 
+	/// Create a database from the given regions and private plans, the main
+	/// country for a code goes first and the others keep their order.
+	pub(crate) fn with_regions(regions: Vec<Arc<Region>>, private: Vec<Arc<PrivatePlan>>, name: Option<String>) -> Self {
 		let mut by_id   = FnvHashMap::default();
-		let mut by_code = FnvHashMap::default();
-		let mut regions = FnvHashMap::default();
-
-		for meta in meta {
-			let meta = Arc::new(metadata(meta)?);
+		let mut by_code = FnvHashMap::<_, Vec<_>>::default();
+		let mut ids     = FnvHashMap::<_, Vec<_>>::default();
 
-			by_id.insert(meta.id.clone(), meta.clone());
+		for region in regions {
+			let code = region.country_code();
+			let id   = region.id().to_owned();
 
-			let by_code = by_code.entry(meta.country_code)
-				.or_insert_with(Vec::new);
+			by_id.insert(id.clone(), region.clone());
 
-			let regions = regions.entry(meta.country_code)
-				.or_insert_with(Vec::new);
+			let by_code = by_code.entry(code).or_default();
+			let ids     = ids.entry(code).or_default();
 
-			if meta.main_country_for_code {
-				by_code.insert(0, meta.clone());
-				regions.insert(0, meta.id.clone())
+			if region.is_main_country_for_code() {
+				by_code.insert(0, region);
+				ids.insert(0, id)
 			}
 			else {
-				by_code.push(meta.clone());
-				regions.push(meta.id.clone());
+				by_code.push(region);
+				ids.push(id);
 			}
 		}
 
-		Ok(Database {
-			cache:   cache.clone(),
-			by_id:   by_id,
-			by_code: by_code,
-			regions: regions,
-		})
+		Database {
+			backend: Backend::Loaded {
+				by_id,
+				by_code,
+				regions: ids,
+			},
+
+			private,
+			name,
+			version: OnceLock::new(),
+		}
+	}
+
+	/// Get every region of the database, sorted by country code with the main
+	/// country first.
+	pub(crate) fn regions(&self) -> Vec<Arc<Region>> {
+		match &self.backend {
+			Backend::Generated =>
+				generated::BY_CODE.iter()
+					.flat_map(|(_, indices)| indices.iter())
+					.map(|&index| Arc::new(Region::Generated(index as usize)))
+					.collect(),
+
+			Backend::Loaded { by_code, .. } => {
+				let mut codes = by_code.keys().collect::<Vec<_>>();
+				codes.sort();
+
+				codes.into_iter()
+					.flat_map(|code| by_code[code].iter().cloned())
+					.collect()
+			}
+		}
+	}
+
+	/// Get every private numbering plan of the database.
+	pub(crate) fn plans(&self) -> Vec<Arc<PrivatePlan>> {
+		self.private.clone()
 	}
 
-	/// Get the regular expression cache.
-	pub fn cache(&self) -> Arc<Mutex<RegexCache>> {
-		self.cache.clone()
+	/// Get the private numbering plans, in the order they are tried.
+	pub fn private_plans(&self) -> impl Iterator<Item = &PrivatePlan> {
+		self.private.iter().map(|p| &**p)
+	}
+
+	/// Get a private numbering plan by ID.
+	pub fn private_plan(&self, id: &str) -> Option<&PrivatePlan> {
+		self.private_plans().find(|p| p.id() == id)
+	}
+
+	/// Compile the metadata of the given regions now instead of on first
+	/// access.
+	pub fn warm(&self, ids: &[country::Id]) {
+		for id in ids {
+			self.by_id(id.as_ref());
+		}
 	}
 
 	/// Get a metadata entry by country ID.
 	pub fn by_id<Q>(&self, key: &Q) -> Option<&super::Metadata>
-		where Q:      ?Sized + Hash + Eq,
-		      String: Borrow<Q>,
+		where Q: ?Sized + Borrow<str>,
 	{
-		self.by_id.get(key).map(AsRef::as_ref)
+		match &self.backend {
+			Backend::Generated =>
+				generated::position(key.borrow()).map(compiled),
+
+			Backend::Loaded { by_id, .. } =>
+				by_id.get(key.borrow()).map(|r| r.get()),
+		}
 	}
 
 	/// Get metadata entries by country code.
-	pub fn by_code<Q>(&self, key: &Q) -> Option<Vec<&super::Metadata>>
-		where Q:   ?Sized + Hash + Eq,
-		      u16: Borrow<Q>,
+	///
+	/// The main country for the code comes first.
+	pub fn by_code<Q>(&self, key: &Q) -> Option<impl ExactSizeIterator<Item = &super::Metadata> + Clone>
+		where Q: ?Sized + Borrow<u16>,
 	{
-		self.by_code.get(key).map(|m| m.iter().map(AsRef::as_ref).collect())
+		match &self.backend {
+			Backend::Generated =>
+				generated::by_code(*key.borrow())
+					.map(|m| Left(m.iter().map(|&index| compiled(index as usize)))),
+
+			Backend::Loaded { by_code, .. } =>
+				by_code.get(key.borrow())
+					.map(|m| Right(m.iter().map(|r| r.get()))),
+		}
 	}
 
 	/// Get all country IDs corresponding to the given country code.
-	pub fn region<Q>(&self, code: &Q) -> Option<Vec<&str>>
-		where Q:   ?Sized + Hash + Eq,
-		      u16: Borrow<Q>
+	pub fn region<Q>(&self, code: &Q) -> Option<impl ExactSizeIterator<Item = &str> + Clone>
+		where Q: ?Sized + Borrow<u16>,
 	{
-		self.regions.get(code).map(|m| m.iter().map(AsRef::as_ref).collect())
+		match &self.backend {
+			Backend::Generated =>
+				generated::by_code(*code.borrow())
+					.map(|m| Left(m.iter().map(|&index| generated::REGIONS[index as usize].id))),
+
+			Backend::Loaded { regions, .. } =>
+				regions.get(code.borrow())
+					.map(|m| Right(m.iter().map(AsRef::as_ref))),
+		}
+	}
+}
+
+/// Get the generated region at the given index, compiling it on first access.
+fn compiled(index: usize) -> &'static super::Metadata {
+	generated::COMPILED[index].get_or_init(||
+		compile((&generated::REGIONS[index]).into())
+			.expect("the generated metadata is invalid"))
+}
+
+/// Compile the patterns of a loaded region.
+pub(crate) fn compile(meta: loader::Metadata) -> Result<super::Metadata, error::LoadMetadata> {
+	fn tranpose<T, E>(value: Option<Result<T, E>>) -> Result<Option<T>, E> {
+		match value {
+			None =>
+				Ok(None),
+
+			Some(Ok(value)) =>
+				Ok(Some(value)),
+
+			Some(Err(err)) =>
+				Err(err),
+		}
+	}
+
+	let regex = |value: String| -> Result<Regex, error::LoadMetadata> {
+		Ok(RegexBuilder::new(&value)
+			.ignore_whitespace(true).build()?)
+	};
+
+	let descriptor = |desc: loader::Descriptor| -> Result<super::Descriptor, error::LoadMetadata> {
+		let national_number = desc.national_number.ok_or_else(||
+			error::LoadMetadata::from(error::Metadata::MissingValue {
+				phase: "descriptor".into(),
+				name:  "national_number".into(),
+			}))?;
+
+		Ok(super::Descriptor {
+			matcher: Matcher::new(&[Some(&national_number)]),
+			national_number: regex(national_number)?,
+
+			possible_length: desc.possible_length,
+			possible_local_length: desc.possible_local_length,
+			example: desc.example,
+		})
+	};
+
+	let format = |format: loader::Format| -> Result<super::Format, error::LoadMetadata> {
+		Ok(super::Format {
+			pattern: format.pattern.ok_or_else(||
+				error::LoadMetadata::from(error::Metadata::MissingValue {
+					phase: "format".into(),
+					name:  "pattern".into(),
+				})).and_then(&regex)?,
+
+			format: format.format.ok_or_else(||
+				error::LoadMetadata::from(error::Metadata::MissingValue {
+					phase: "format".into(),
+					name:  "format".into()
+				}))?,
+
+			leading_digits: format.leading_digits.into_iter()
+				.map(&regex).collect::<Result<_, _>>()?,
+
+			national_prefix:          format.national_prefix_formatting_rule,
+			national_prefix_optional: format.national_prefix_optional_when_formatting,
+
+			domestic_carrier: format.domestic_carrier,
+		})
+	};
+
+	let mut descriptors = super::Descriptors {
+		general: descriptor(meta.general.ok_or_else(||
+			error::LoadMetadata::from(error::Metadata::MissingValue {
+				phase: "metadata".into(),
+				name:  "generalDesc".into(),
+			}))?)?,
+
+		fixed_line:       tranpose(meta.fixed_line.map(&descriptor))?,
+		mobile:           tranpose(meta.mobile.map(&descriptor))?,
+		toll_free:        tranpose(meta.toll_free.map(&descriptor))?,
+		premium_rate:     tranpose(meta.premium_rate.map(&descriptor))?,
+		shared_cost:      tranpose(meta.shared_cost.map(&descriptor))?,
+		personal_number:  tranpose(meta.personal_number.map(&descriptor))?,
+		voip:             tranpose(meta.voip.map(&descriptor))?,
+		pager:            tranpose(meta.pager.map(&descriptor))?,
+		uan:              tranpose(meta.uan.map(&descriptor))?,
+		emergency:        tranpose(meta.emergency.map(&descriptor))?,
+		voicemail:        tranpose(meta.voicemail.map(&descriptor))?,
+		short_code:       tranpose(meta.short_code.map(&descriptor))?,
+		standard_rate:    tranpose(meta.standard_rate.map(&descriptor))?,
+		carrier:          tranpose(meta.carrier.map(&descriptor))?,
+		no_international: tranpose(meta.no_international.map(&descriptor))?,
+
+		sms_services:       tranpose(meta.sms_services.map(&descriptor))?,
+		expanded_emergency: tranpose(meta.expanded_emergency.map(&descriptor))?,
+		area_code_optional: tranpose(meta.area_code_optional.map(&descriptor))?,
+
+		classifier: None,
+	};
+
+	descriptors.classifier = Matcher::new(&CLASSIFIED.iter()
+		.map(|&kind| descriptors.get(kind).map(|d| d.national_number.as_str()))
+		.collect::<Vec<_>>());
+
+	Ok(super::Metadata {
+		descriptors,
+
+		id: meta.id.ok_or_else(||
+			error::LoadMetadata::from(error::Metadata::MissingValue {
+				phase: "metadata".into(),
+				name:  "id".into()
+			}))?,
+
+		country_code: meta.country_code.ok_or_else(||
+			error::LoadMetadata::from(error::Metadata::MissingValue {
+				phase: "metadata".into(),
+				name: "countryCode".into(),
+			}))?,
+
+		international_prefix: tranpose(meta.international_prefix.map(&regex))?,
+		preferred_international_prefix: meta.preferred_international_prefix,
+		national_prefix: meta.national_prefix,
+		preferred_extension_prefix: meta.preferred_extension_prefix,
+		national_prefix_for_parsing: tranpose(meta.national_prefix_for_parsing.map(&regex))?,
+		national_prefix_transform_rule: meta.national_prefix_transform_rule,
+
+		formats: meta.formats.into_iter().map(&format).collect::<Result<_, _>>()?,
+		international_formats: meta.international_formats.into_iter().map(&format).collect::<Result<_, _>>()?,
+
+		main_country_for_code: meta.main_country_for_code,
+		leading_digits: tranpose(meta.leading_digits.map(&regex))?,
+		mobile_number_portable: meta.mobile_number_portable,
+		same_mobile_and_fixed_line_pattern: meta.same_mobile_and_fixed_line_pattern,
+
+		national_prefix_formatting_rule: meta.national_prefix_formatting_rule,
+		national_prefix_optional_when_formatting: meta.national_prefix_optional_when_formatting,
+		carrier_code_formatting_rule: meta.carrier_code_formatting_rule,
+
+		references: meta.references,
+	})
+}
+
+impl<'a> From<&'a super::Metadata> for loader::Metadata {
+	fn from(meta: &'a super::Metadata) -> Self {
+		let descriptor = |desc: &super::Descriptor| loader::Descriptor {
+			national_number:       Some(desc.national_number.as_str().into()),
+			possible_length:       desc.possible_length.clone(),
+			possible_local_length: desc.possible_local_length.clone(),
+			example:               desc.example.clone(),
+		};
+		let format = |format: &super::Format| loader::Format {
+			pattern:        Some(format.pattern.as_str().into()),
+			format:         Some(format.format.clone()),
+			leading_digits: format.leading_digits.iter().map(|r| r.as_str().into()).collect(),
+
+			national_prefix_formatting_rule:          format.national_prefix.clone(),
+			national_prefix_optional_when_formatting: format.national_prefix_optional,
+			domestic_carrier:                         format.domestic_carrier.clone(),
+		};
+		let descriptors = &meta.descriptors;
+
+		loader::Metadata {
+			general:          Some(descriptor(&descriptors.general)),
+			fixed_line:       descriptors.fixed_line.as_ref().map(descriptor),
+			mobile:           descriptors.mobile.as_ref().map(descriptor),
+			toll_free:        descriptors.toll_free.as_ref().map(descriptor),
+			premium_rate:     descriptors.premium_rate.as_ref().map(descriptor),
+			shared_cost:      descriptors.shared_cost.as_ref().map(descriptor),
+			personal_number:  descriptors.personal_number.as_ref().map(descriptor),
+			voip:             descriptors.voip.as_ref().map(descriptor),
+			pager:            descriptors.pager.as_ref().map(descriptor),
+			uan:              descriptors.uan.as_ref().map(descriptor),
+			emergency:        descriptors.emergency.as_ref().map(descriptor),
+			voicemail:        descriptors.voicemail.as_ref().map(descriptor),
+			short_code:       descriptors.short_code.as_ref().map(descriptor),
+			standard_rate:    descriptors.standard_rate.as_ref().map(descriptor),
+			carrier:          descriptors.carrier.as_ref().map(descriptor),
+			no_international: descriptors.no_international.as_ref().map(descriptor),
+
+			sms_services:       descriptors.sms_services.as_ref().map(descriptor),
+			expanded_emergency: descriptors.expanded_emergency.as_ref().map(descriptor),
+			area_code_optional: descriptors.area_code_optional.as_ref().map(descriptor),
+
+			id:           Some(meta.id.clone()),
+			country_code: Some(meta.country_code),
+
+			international_prefix:           meta.international_prefix.as_ref().map(|r| r.as_str().into()),
+			preferred_international_prefix: meta.preferred_international_prefix.clone(),
+			national_prefix:                meta.national_prefix.clone(),
+			preferred_extension_prefix:     meta.preferred_extension_prefix.clone(),
+
+			national_prefix_for_parsing:    meta.national_prefix_for_parsing.as_ref().map(|r| r.as_str().into()),
+			national_prefix_transform_rule: meta.national_prefix_transform_rule.clone(),
+
+			formats:               meta.formats.iter().map(format).collect(),
+			international_formats: meta.international_formats.iter().map(format).collect(),
+
+			main_country_for_code:  meta.main_country_for_code,
+			leading_digits:         meta.leading_digits.as_ref().map(|r| r.as_str().into()),
+			mobile_number_portable: meta.mobile_number_portable,
+
+			same_mobile_and_fixed_line_pattern: meta.same_mobile_and_fixed_line_pattern,
+
+			national_prefix_formatting_rule:          meta.national_prefix_formatting_rule.clone(),
+			national_prefix_optional_when_formatting: meta.national_prefix_optional_when_formatting,
+			carrier_code_formatting_rule:             meta.carrier_code_formatting_rule.clone(),
+
+			references: meta.references.clone(),
+		}
+	}
+}
+
+/// Metadata is serialized in its `loader` representation.
+impl Serialize for super::Metadata {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		loader::Metadata::from(self).serialize(serializer)
+	}
+}
+
+/// Metadata is deserialized from its `loader` representation and compiled.
+impl<'de> Deserialize<'de> for super::Metadata {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		compile(loader::Metadata::deserialize(deserializer)?)
+			.map_err(de::Error::custom)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::env;
+	use std::process::Command;
+
+	use crate::metadata::database::{DEFAULT, LOADED, Database};
+	use crate::metadata::{generated, Metadata};
+	use crate::parser;
+	use crate::formatter::Mode;
+	use crate::country;
+	use crate::error;
+
+	#[test]
+	fn generated() {
+		assert_eq!(vec!["GB", "GG", "IM", "JE"], DEFAULT.region(&44).unwrap().collect::<Vec<_>>());
+		assert_eq!(vec!["US", "AG"], DEFAULT.region(&1).unwrap().take(2).collect::<Vec<_>>());
+		assert_eq!(Some("US"), DEFAULT.by_code(&1).and_then(|mut m| m.next()).map(|m| m.id()));
+		assert!(DEFAULT.by_id("XX").is_none());
+		assert!(DEFAULT.by_code(&999).is_none());
+
+		DEFAULT.warm(&[country::TV]);
+		assert!(generated::COMPILED[generated::position("TV").unwrap()].get().is_some());
+
+		let loaded: &Database = &LOADED;
+		for number in &["+39 06 1234 5678", "+1 650 253 0000", "+44 7912 345678", "+81 3 1234 5678", "+800 1234 5678"] {
+			assert_eq!(
+				parser::parse_with(loaded, None, number).unwrap(),
+				parser::parse_with(&DEFAULT, None, number).unwrap());
+
+			assert_eq!(
+				parser::parse_with(loaded, None, number).unwrap().format_with(loaded).mode(Mode::International).to_string(),
+				parser::parse_with(&DEFAULT, None, number).unwrap().format().mode(Mode::International).to_string());
+		}
+	}
+
+	#[test]
+	fn lazy() {
+		// Compiled regions are shared by the whole process, so check them in a
+		// process running this test alone.
+		if env::var_os("PHONENUMBER_TEST_LAZY").is_none() {
+			let output = Command::new(env::current_exe().unwrap())
+				.args(["--exact", "metadata::database::test::lazy"])
+				.env("PHONENUMBER_TEST_LAZY", "1")
+				.output().unwrap();
+
+			assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+			return;
+		}
+
+		let compiled = || generated::REGIONS.iter().zip(generated::COMPILED.iter())
+			.filter(|(_, c)| c.get().is_some())
+			.map(|(r, _)| r.id)
+			.collect::<Vec<_>>();
+
+		assert!(compiled().is_empty());
+
+		assert_eq!(vec!["GB", "GG", "IM", "JE"], DEFAULT.region(&44).unwrap().collect::<Vec<_>>());
+		assert!(compiled().is_empty());
+
+		assert_eq!(39, DEFAULT.by_id("IT").unwrap().country_code());
+		assert_eq!(vec!["IT"], compiled());
+
+		DEFAULT.warm(&[country::US, country::GB]);
+		assert_eq!(vec!["GB", "IT", "US"], compiled());
+	}
+
+	#[test]
+	fn json() {
+		let json     = DEFAULT.to_json().unwrap();
+		let database = Database::from_json(&json).unwrap();
+		assert_eq!(json, database.to_json().unwrap());
+
+		for number in &["+39 06 1234 5678", "+44 7912 345678", "+1 242 365 1234", "+800 1234 5678"] {
+			let number = parser::parse_with(&database, None, number).unwrap();
+			assert_eq!(number, parser::parse(None, number.to_string()).unwrap());
+			assert!(number.is_valid_with(&database));
+
+			assert_eq!(
+				number.format_with(&database).mode(Mode::International).to_string(),
+				number.format().mode(Mode::International).to_string());
+		}
+
+		let gb   = DEFAULT.by_id("GB").unwrap();
+		let meta = serde_json::from_str::<Metadata>(&serde_json::to_string(gb).unwrap()).unwrap();
+		assert_eq!(gb.formats().len(), meta.formats().len());
+		assert_eq!(gb.leading_digits().map(|r| r.as_str()), meta.leading_digits().map(|r| r.as_str()));
+		assert_eq!(gb.formats()[0].leading_digits().len(), meta.formats()[0].leading_digits().len());
+
+		assert!(serde_json::from_str::<Metadata>(r#"{ "id": "XX" }"#).is_err());
+		assert!(Database::from_json(r#"{ "territories": [{ "id": "XX", "country_code": 999 }] }"#).is_err());
+
+		// A descriptor without a pattern is an error, not a panic.
+		let error = Database::from_json(r#"{"territories":[{"id":"XX","country_code":999,"general":{}}]}"#).unwrap_err();
+		assert!(matches!(error, error::LoadMetadata::Metadata(error::Metadata::MissingValue { .. })), "{:?}", error);
 	}
 }
@@ -12,12 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use regex_cache::CachedRegex;
+use regex::Regex;
+
+use crate::metadata::matcher::Matcher;
 
 /// Description of a phone number to parse.
 #[derive(Clone, Debug)]
 pub struct Descriptor {
-	pub(crate) national_number: CachedRegex,
+	pub(crate) national_number: Regex,
+	pub(crate) matcher: Option<Matcher>,
 
 	pub(crate) possible_length: Vec<u16>,
 	pub(crate) possible_local_length: Vec<u16>,
@@ -29,7 +32,7 @@ impl Descriptor {
 	/// The national number is the pattern that a valid national significant
 	/// number would match. This specifies information such as its total length
 	/// and leading digits.
-	pub fn national_number(&self) -> &CachedRegex {
+	pub fn national_number(&self) -> &Regex {
 		&self.national_number
 	}
 
@@ -72,13 +75,22 @@ impl Descriptor {
 
 	/// Check if the descriptor matches the given national number.
 	pub fn is_match(&self, value: &str) -> bool {
-		if !self.possible_length.is_empty() &&
-		   !self.possible_length.contains(&(value.len() as u16))
-		{
+		if !self.is_possible_length(value) {
 			return false;
 		}
 
+		if let Some(mask) = self.matcher.as_ref().and_then(|m| m.prefixes(value)) {
+			return mask != 0;
+		}
+
 		self.national_number.find(value).map(|m| m.start() == 0)
 			.unwrap_or(false)
 	}
+
+	/// Check if the length of the given national number is possible, when no
+	/// lengths are known any length is.
+	pub(crate) fn is_possible_length(&self, value: &str) -> bool {
+		self.possible_length.is_empty() ||
+		self.possible_length.contains(&(value.len() as u16))
+	}
 }
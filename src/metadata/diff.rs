@@ -0,0 +1,711 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::metadata::{Database, CLASSIFIED, loader};
+use crate::phone_number::Type;
+use crate::formatter::Mode;
+use crate::validator;
+use crate::parser;
+use crate::country;
+
+/// The differences between two databases, see `diff`.
+#[derive(Clone, Debug)]
+pub struct Report {
+	pub(crate) numbers:   Vec<Difference>,
+	pub(crate) regions:   Vec<RegionChange>,
+	pub(crate) summaries: BTreeMap<String, Summary>,
+}
+
+/// A number of the corpus that behaves differently.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Difference {
+	pub(crate) input:   String,
+	pub(crate) country: Option<country::Id>,
+	pub(crate) region:  String,
+	pub(crate) change:  Change,
+}
+
+/// How a number behaves differently.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Change {
+	/// The number parses with one database only.
+	Parse {
+		/// The error with the old database, if it failed.
+		old: Option<String>,
+
+		/// The error with the new database, if it failed.
+		new: Option<String>,
+	},
+
+	/// The number belongs to another region.
+	Region {
+		/// The old region, if any.
+		old: Option<String>,
+
+		/// The new region, if any.
+		new: Option<String>,
+	},
+
+	/// The number was valid and is not anymore.
+	BecameInvalid,
+
+	/// The number was invalid and is valid now.
+	BecameValid,
+
+	/// The number is of another type.
+	Type {
+		/// The old type.
+		old: Type,
+
+		/// The new type.
+		new: Type,
+	},
+
+	/// The number is formatted differently.
+	Format {
+		/// Whether it's the international format, or the national one.
+		international: bool,
+
+		/// The old formatting.
+		old: String,
+
+		/// The new formatting.
+		new: String,
+	},
+}
+
+/// The differences of the numbers of a region.
+#[derive(Clone, Copy, Eq, PartialEq, Default, Debug)]
+pub struct Summary {
+	pub(crate) compared:       usize,
+	pub(crate) parse_failures: usize,
+	pub(crate) region_changes: usize,
+	pub(crate) became_invalid: usize,
+	pub(crate) became_valid:   usize,
+	pub(crate) type_changes:   usize,
+	pub(crate) format_changes: usize,
+}
+
+/// A change in the definition of a region.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RegionChange {
+	pub(crate) region:       String,
+	pub(crate) country_code: u16,
+	pub(crate) change:       Definition,
+}
+
+/// How the definition of a region changed.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Definition {
+	/// The region was added.
+	Added,
+
+	/// The region was removed.
+	Removed,
+
+	/// A field of the region changed.
+	Changed {
+		/// The path of the field in the `loader` representation, formats are
+		/// keyed by their pattern.
+		field: String,
+
+		/// The old value, if any.
+		old: Option<String>,
+
+		/// The new value, if any.
+		new: Option<String>,
+	},
+}
+
+/// What a database makes of a number.
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum Outcome {
+	Failed(String),
+
+	Parsed {
+		region:        Option<String>,
+		valid:         bool,
+		kind:          Type,
+		international: String,
+		national:      String,
+	},
+}
+
+/// The ID used for numbers without a region.
+const UNKNOWN: &str = "ZZ";
+
+/// Compare the behaviour of two databases on a corpus, and the definitions of
+/// their regions.
+///
+/// Each number of the corpus is parsed with the given default country, then
+/// validated, classified and formatted with both databases. Differences are
+/// reported under the region the number belongs to in the old database, or
+/// the new one if it didn't belong anywhere.
+pub fn diff<I, S>(old: &Database, new: &Database, corpus: I) -> Report
+	where I: IntoIterator<Item = (Option<country::Id>, S)>,
+	      S: AsRef<str>,
+{
+	let mut numbers   = Vec::new();
+	let mut summaries = BTreeMap::<String, Summary>::new();
+
+	for (country, input) in corpus {
+		let input  = input.as_ref();
+		let before = outcome(old, country, input);
+		let after  = outcome(new, country, input);
+
+		let region = before.region().or_else(|| after.region()).unwrap_or(UNKNOWN).to_owned();
+		let summary = summaries.entry(region.clone()).or_default();
+		summary.compared += 1;
+
+		for change in changes(&before, &after) {
+			match change {
+				Change::Parse { .. } => summary.parse_failures += 1,
+				Change::Region { .. } => summary.region_changes += 1,
+				Change::BecameInvalid => summary.became_invalid += 1,
+				Change::BecameValid => summary.became_valid += 1,
+				Change::Type { .. } => summary.type_changes += 1,
+				Change::Format { .. } => summary.format_changes += 1,
+			}
+
+			numbers.push(Difference {
+				input: input.into(),
+				country,
+				region: region.clone(),
+				change,
+			});
+		}
+	}
+
+	Report {
+		numbers,
+		regions: definitions(old, new),
+		summaries,
+	}
+}
+
+/// The example numbers of every region of the database, to use as a corpus.
+///
+/// Examples of non-geographical entities are written with their country code.
+pub fn examples(database: &Database) -> Vec<(Option<country::Id>, String)> {
+	let mut result = Vec::new();
+
+	for region in database.regions() {
+		let meta    = region.get();
+		let country = meta.id.parse().ok();
+		let types   = CLASSIFIED.iter().filter_map(|&kind| meta.descriptors.get(kind));
+
+		for example in types.filter_map(|d| d.example()) {
+			if country.is_some() {
+				result.push((country, example.to_owned()));
+			}
+			else {
+				result.push((None, format!("+{} {}", meta.country_code, example)));
+			}
+		}
+	}
+
+	result
+}
+
+impl Report {
+	/// The numbers behaving differently, in the order of the corpus.
+	pub fn numbers(&self) -> &[Difference] {
+		&self.numbers
+	}
+
+	/// The changes in the definitions of the regions, sorted by country code.
+	pub fn regions(&self) -> &[RegionChange] {
+		&self.regions
+	}
+
+	/// The differences aggregated by region, for every region with numbers in
+	/// the corpus.
+	pub fn summaries(&self) -> &BTreeMap<String, Summary> {
+		&self.summaries
+	}
+
+	/// Whether both databases behave the same.
+	pub fn is_empty(&self) -> bool {
+		self.numbers.is_empty() && self.regions.is_empty()
+	}
+}
+
+impl Difference {
+	/// The number as written in the corpus.
+	pub fn input(&self) -> &str {
+		&self.input
+	}
+
+	/// The default country it was parsed with.
+	pub fn country(&self) -> Option<country::Id> {
+		self.country
+	}
+
+	/// The ID of the region the difference is reported under.
+	pub fn region(&self) -> &str {
+		&self.region
+	}
+
+	/// The difference.
+	pub fn change(&self) -> &Change {
+		&self.change
+	}
+}
+
+impl Summary {
+	/// The numbers of the region in the corpus.
+	pub fn compared(&self) -> usize {
+		self.compared
+	}
+
+	/// The numbers parsing with one database only.
+	pub fn parse_failures(&self) -> usize {
+		self.parse_failures
+	}
+
+	/// The numbers belonging to another region.
+	pub fn region_changes(&self) -> usize {
+		self.region_changes
+	}
+
+	/// The numbers which became invalid.
+	pub fn became_invalid(&self) -> usize {
+		self.became_invalid
+	}
+
+	/// The numbers which became valid.
+	pub fn became_valid(&self) -> usize {
+		self.became_valid
+	}
+
+	/// The numbers of another type.
+	pub fn type_changes(&self) -> usize {
+		self.type_changes
+	}
+
+	/// The numbers formatted differently, once per format.
+	pub fn format_changes(&self) -> usize {
+		self.format_changes
+	}
+
+	/// Whether every number behaves the same.
+	pub fn is_empty(&self) -> bool {
+		self.parse_failures == 0 && self.region_changes == 0 &&
+			self.became_invalid == 0 && self.became_valid == 0 &&
+			self.type_changes == 0 && self.format_changes == 0
+	}
+}
+
+impl RegionChange {
+	/// The ID of the region.
+	pub fn region(&self) -> &str {
+		&self.region
+	}
+
+	/// The country code of the region.
+	pub fn country_code(&self) -> u16 {
+		self.country_code
+	}
+
+	/// The change.
+	pub fn change(&self) -> &Definition {
+		&self.change
+	}
+}
+
+impl Outcome {
+	fn region(&self) -> Option<&str> {
+		match self {
+			Outcome::Parsed { region, .. } =>
+				region.as_ref().map(AsRef::as_ref),
+
+			Outcome::Failed(..) =>
+				None,
+		}
+	}
+}
+
+fn outcome(database: &Database, country: Option<country::Id>, input: &str) -> Outcome {
+	let number = match parser::parse_with(database, country, input) {
+		Ok(number) =>
+			number,
+
+		Err(err) =>
+			return Outcome::Failed(err.to_string()),
+	};
+
+	let meta = number.metadata(database);
+	let kind = meta.map(|m| validator::number_type(m, &number.national().digits()))
+		.unwrap_or(Type::Unknown);
+
+	// Invalid numbers only have a region when their country code has one.
+	let region = meta.map(|m| m.id.clone()).or_else(||
+		database.region(&number.code().value())
+			.filter(|ids| ids.len() == 1)
+			.and_then(|mut ids| ids.next())
+			.map(String::from));
+
+	Outcome::Parsed {
+		region,
+		valid:         number.is_valid_with(database),
+		kind,
+		international: number.format_with(database).mode(Mode::International).to_string(),
+		national:      number.format_with(database).mode(Mode::National).to_string(),
+	}
+}
+
+fn changes(old: &Outcome, new: &Outcome) -> Vec<Change> {
+	let (old, new) = match (old, new) {
+		(Outcome::Failed(old), Outcome::Failed(new)) if old == new =>
+			return Vec::new(),
+
+		(Outcome::Failed(..), _) | (_, Outcome::Failed(..)) => {
+			let error = |outcome: &Outcome| if let Outcome::Failed(error) = outcome {
+				Some(error.clone())
+			}
+			else {
+				None
+			};
+
+			return vec![Change::Parse { old: error(old), new: error(new) }];
+		}
+
+		(old, new) =>
+			(old, new),
+	};
+
+	let mut result = Vec::new();
+
+	if let (Outcome::Parsed { region: old_region, valid: old_valid, kind: old_kind, international: old_international, national: old_national },
+	        Outcome::Parsed { region: new_region, valid: new_valid, kind: new_kind, international: new_international, national: new_national }) = (old, new)
+	{
+		if old_region != new_region {
+			result.push(Change::Region { old: old_region.clone(), new: new_region.clone() });
+		}
+
+		match (old_valid, new_valid) {
+			(true, false) => result.push(Change::BecameInvalid),
+			(false, true) => result.push(Change::BecameValid),
+			_ => (),
+		}
+
+		if old_kind != new_kind {
+			result.push(Change::Type { old: *old_kind, new: *new_kind });
+		}
+
+		if old_international != new_international {
+			result.push(Change::Format {
+				international: true,
+				old:           old_international.clone(),
+				new:           new_international.clone(),
+			});
+		}
+
+		if old_national != new_national {
+			result.push(Change::Format {
+				international: false,
+				old:           old_national.clone(),
+				new:           new_national.clone(),
+			});
+		}
+	}
+
+	result
+}
+
+/// Compare the definitions of the regions, keyed by country code and ID since
+/// the non-geographical entities share theirs.
+fn definitions(old: &Database, new: &Database) -> Vec<RegionChange> {
+	let sources = |database: &Database| database.regions().iter()
+		.map(|r| ((r.country_code(), r.id().to_owned()), r.source()))
+		.collect::<BTreeMap<_, _>>();
+
+	let old = sources(old);
+	let new = sources(new);
+
+	let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+	keys.sort();
+	keys.dedup();
+
+	let mut result = Vec::new();
+
+	for key in keys {
+		let (country_code, region) = key.clone();
+		let mut report = |change| result.push(RegionChange {
+			region: region.clone(),
+			country_code,
+			change,
+		});
+
+		match (old.get(key), new.get(key)) {
+			(Some(old), Some(new)) => {
+				let old = fields(old);
+				let new = fields(new);
+
+				let mut names = old.keys().chain(new.keys()).collect::<Vec<_>>();
+				names.sort();
+				names.dedup();
+
+				for name in names {
+					if old.get(name) != new.get(name) {
+						report(Definition::Changed {
+							field: name.clone(),
+							old:   old.get(name).cloned(),
+							new:   new.get(name).cloned(),
+						});
+					}
+				}
+			}
+
+			(None, Some(_)) =>
+				report(Definition::Added),
+
+			(Some(_), None) =>
+				report(Definition::Removed),
+
+			(None, None) =>
+				unreachable!(),
+		}
+	}
+
+	result
+}
+
+/// Flatten a region in its `loader` representation, missing and default
+/// values are left out.
+fn fields(meta: &loader::Metadata) -> BTreeMap<String, String> {
+	fn flatten(path: String, value: &Value, out: &mut BTreeMap<String, String>) {
+		let join = |key: &str| if path.is_empty() { key.to_owned() } else { format!("{}.{}", path, key) };
+
+		match value {
+			Value::Null | Value::Bool(false) =>
+				(),
+
+			Value::Array(values) if values.is_empty() =>
+				(),
+
+			Value::String(value) => {
+				out.insert(path, value.clone());
+			}
+
+			Value::Object(map) => {
+				for (key, value) in map {
+					flatten(join(key), value, out);
+				}
+			}
+
+			// Formats are keyed by pattern so adding one doesn't change the others.
+			Value::Array(values) if values.iter().all(|v| v["pattern"].is_string()) => {
+				for (index, value) in values.iter().enumerate() {
+					let key = format!("{}[{}]", path, value["pattern"].as_str().unwrap());
+
+					if out.keys().any(|k| k.starts_with(&key)) {
+						flatten(format!("{}[{}]", path, index), value, out);
+					}
+					else {
+						flatten(key, value, out);
+					}
+				}
+			}
+
+			value => {
+				out.insert(path, value.to_string());
+			}
+		}
+	}
+
+	let mut result = BTreeMap::new();
+	flatten(String::new(), &serde_json::to_value(meta).unwrap_or(Value::Null), &mut result);
+	result
+}
+
+impl fmt::Display for Difference {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} {:?}: {}", self.region, self.input, self.change)
+	}
+}
+
+impl fmt::Display for Change {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Change::Parse { old: Some(old), new: None } =>
+				write!(f, "parses now, failed with {:?}", old),
+
+			Change::Parse { old: None, new: Some(new) } =>
+				write!(f, "fails to parse with {:?}", new),
+
+			Change::Parse { old, new } =>
+				write!(f, "parse error changed from {:?} to {:?}", old, new),
+
+			Change::Region { old, new } =>
+				write!(f, "region changed from {} to {}",
+					old.as_ref().map(AsRef::as_ref).unwrap_or(UNKNOWN),
+					new.as_ref().map(AsRef::as_ref).unwrap_or(UNKNOWN)),
+
+			Change::BecameInvalid =>
+				write!(f, "became invalid"),
+
+			Change::BecameValid =>
+				write!(f, "became valid"),
+
+			Change::Type { old, new } =>
+				write!(f, "type changed from {:?} to {:?}", old, new),
+
+			Change::Format { international, old, new } =>
+				write!(f, "{} format changed from {:?} to {:?}",
+					if *international { "international" } else { "national" }, old, new),
+		}
+	}
+}
+
+impl fmt::Display for Summary {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} compared", self.compared)?;
+
+		for &(count, what) in &[
+			(self.parse_failures, "parse differently"),
+			(self.region_changes, "changed region"),
+			(self.became_invalid, "became invalid"),
+			(self.became_valid, "became valid"),
+			(self.type_changes, "changed type"),
+			(self.format_changes, "changed formatting"),
+		] {
+			if count > 0 {
+				write!(f, ", {} {}", count, what)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+impl fmt::Display for RegionChange {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} (+{}): {}", self.region, self.country_code, self.change)
+	}
+}
+
+impl fmt::Display for Definition {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Definition::Added =>
+				write!(f, "added"),
+
+			Definition::Removed =>
+				write!(f, "removed"),
+
+			Definition::Changed { field, old: Some(old), new: Some(new) } =>
+				write!(f, "{} changed from {:?} to {:?}", field, old, new),
+
+			Definition::Changed { field, old: None, new: Some(new) } =>
+				write!(f, "{} set to {:?}", field, new),
+
+			Definition::Changed { field, old: Some(old), new: None } =>
+				write!(f, "{} unset, was {:?}", field, old),
+
+			Definition::Changed { field, .. } =>
+				write!(f, "{} changed", field),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::metadata::{DATABASE, DatabaseBuilder, diff, loader};
+	use crate::metadata::diff::{Change, Definition};
+	use crate::phone_number::Type;
+	use crate::country;
+
+	#[test]
+	fn diff() {
+		let new = DatabaseBuilder::new()
+			.replace_descriptor("GB", Type::Mobile, loader::Descriptor {
+				national_number: Some("7[1-3]\\d{8}".into()),
+				possible_length: vec![10],
+				.. Default::default()
+			})
+			.add_format("DE", loader::Format {
+				pattern: Some("(\\d{2})(\\d{4,11})".into()),
+				format:  Some("$1/$2".into()),
+				leading_digits: vec!["30".into()],
+				national_prefix_formatting_rule: Some("$NP$FG".into()),
+				.. Default::default()
+			})
+			.add_territory(loader::Metadata {
+				id:           Some("XA".into()),
+				country_code: Some(999),
+
+				general: Some(loader::Descriptor {
+					national_number: Some("\\d{8}".into()),
+					.. Default::default()
+				}),
+
+				.. Default::default()
+			})
+			.build().unwrap();
+
+		let corpus = vec![
+			(Some(country::GB), "07912 345678"),
+			(Some(country::GB), "07123 456789"),
+			(Some(country::DE), "030 123456"),
+			(None, "+1 650 253 0000"),
+			(None, "+999 12345678"),
+		];
+
+		let report = diff::diff(&DATABASE, &new, corpus);
+		assert!(!report.is_empty());
+
+		// The mobile range shrunk, the number belongs nowhere now.
+		let gb = report.numbers().iter().filter(|d| d.input() == "07912 345678").collect::<Vec<_>>();
+		assert_eq!(vec![
+			&Change::Region { old: Some("GB".into()), new: None },
+			&Change::BecameInvalid,
+			&Change::Type { old: Type::Mobile, new: Type::Unknown },
+		], gb.iter().map(|d| d.change()).collect::<Vec<_>>());
+		assert!(report.numbers().iter().all(|d| d.input() != "07123 456789"));
+
+		// The new format is picked.
+		assert!(report.numbers().iter().any(|d| d.region() == "DE" && d.change() == &Change::Format {
+			international: false,
+			old:           "030 123456".into(),
+			new:           "030/123456".into(),
+		}));
+
+		// The new region takes numbers which didn't parse.
+		assert!(report.numbers().iter().any(|d| d.region() == "XA" && matches!(d.change(), Change::Parse { old: Some(_), new: None })));
+
+		let summaries = report.summaries();
+		assert_eq!(2, summaries["GB"].compared());
+		assert_eq!(1, summaries["GB"].became_invalid());
+		assert_eq!(2, summaries["DE"].format_changes());
+		assert!(summaries["US"].is_empty());
+
+		// The definitions changed too.
+		let regions = report.regions();
+		assert!(regions.iter().any(|r| r.region() == "XA" && r.change() == &Definition::Added));
+		assert!(regions.iter().any(|r| r.region() == "GB" && matches!(r.change(),
+			Definition::Changed { field, .. } if field == "mobile.national_number")));
+		assert!(regions.iter().any(|r| r.region() == "DE" && matches!(r.change(),
+			Definition::Changed { field, old: None, .. } if field == "formats[(\\d{2})(\\d{4,11})].format")));
+		assert!(regions.iter().all(|r| r.region() != "US"));
+
+		// Nothing changes against itself.
+		assert!(diff::diff(&DATABASE, &DATABASE, diff::examples(&DATABASE)).is_empty());
+	}
+}
@@ -0,0 +1,220 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Write;
+
+use serde_json::{Value, Map};
+
+use crate::error;
+use crate::metadata::{Database, loader};
+
+/// The version of the libphonenumber-js metadata layout written.
+pub const LIBPHONENUMBER_JS_VERSION: u64 = 4;
+
+/// The descriptors of a country in libphonenumber-js, in the order of its
+/// `types` array.
+const TYPES: [fn(&loader::Metadata) -> Option<&loader::Descriptor>; 10] = [
+	|m| m.fixed_line.as_ref(),
+	|m| m.mobile.as_ref(),
+	|m| m.toll_free.as_ref(),
+	|m| m.premium_rate.as_ref(),
+	|m| m.personal_number.as_ref(),
+	|m| m.voicemail.as_ref(),
+	|m| m.uan.as_ref(),
+	|m| m.pager.as_ref(),
+	|m| m.voip.as_ref(),
+	|m| m.shared_cost.as_ref(),
+];
+
+/// Write the database in the `metadata.json` layout of libphonenumber-js.
+///
+/// Countries are written as the positional arrays libphonenumber-js reads,
+/// with the number types so the output works as its "max" metadata, and the
+/// non-geographical entities go in `nonGeographic`. Private numbering plans
+/// have no equivalent and are left out.
+pub fn libphonenumber_js<W: Write>(database: &Database, out: W) -> Result<(), error::LoadMetadata> {
+	let mut codes          = Map::new();
+	let mut countries      = Map::new();
+	let mut non_geographic = Map::new();
+
+	for region in database.regions() {
+		let meta = region.source();
+		let id   = meta.id.clone().unwrap_or_default();
+		let code = meta.country_code.unwrap_or_default().to_string();
+
+		if let Value::Array(ids) = codes.entry(code.clone()).or_insert_with(|| Value::Array(Vec::new())) {
+			ids.push(id.clone().into());
+		}
+
+		if id == "001" {
+			non_geographic.insert(code, country(&meta));
+		}
+		else {
+			countries.insert(id, country(&meta));
+		}
+	}
+
+	let mut root = Map::new();
+	root.insert("version".into(), LIBPHONENUMBER_JS_VERSION.into());
+	root.insert("country_calling_codes".into(), codes.into());
+	root.insert("countries".into(), countries.into());
+	root.insert("nonGeographic".into(), non_geographic.into());
+
+	serde_json::to_writer(out, &root).map_err(|e| if e.is_io() {
+		error::LoadMetadata::Io(e.into())
+	}
+	else {
+		e.into()
+	})
+}
+
+/// Build the array for a country.
+fn country(meta: &loader::Metadata) -> Value {
+	let general = meta.general.as_ref();
+
+	// The general descriptor may carry no lengths, libphonenumber-js expects
+	// every length of the country there.
+	let mut lengths = general.map(|d| d.possible_length.clone()).unwrap_or_default();
+	if lengths.is_empty() {
+		for descriptor in TYPES.iter().filter_map(|get| get(meta)) {
+			lengths.extend(&descriptor.possible_length);
+		}
+
+		lengths.sort_unstable();
+		lengths.dedup();
+	}
+
+	let types = TYPES.iter().map(|get| get(meta).map(|d| trim(vec![
+		d.national_number.as_deref().map(pattern).into(),
+		lengths_of(&d.possible_length),
+	])).unwrap_or(Value::Null)).collect();
+
+	trim(vec![
+		meta.country_code.unwrap_or_default().to_string().into(),
+		meta.international_prefix.as_deref().map(pattern).into(),
+		general.and_then(|d| d.national_number.as_deref()).map(pattern).into(),
+		lengths_of(&lengths),
+		meta.formats.iter().map(|f| format(meta, f)).collect::<Vec<_>>().into(),
+		meta.national_prefix.clone().into(),
+		Value::Null,
+		meta.national_prefix_for_parsing.as_deref().map(pattern).into(),
+		meta.national_prefix_transform_rule.clone().into(),
+		Value::Null,
+		meta.leading_digits.as_deref().map(pattern).into(),
+		trim(types),
+		meta.preferred_international_prefix.clone().into(),
+		meta.preferred_extension_prefix.clone().into(),
+	])
+}
+
+/// Build the array for a national format, with the international format when
+/// it differs or "NA" when the format isn't used internationally.
+fn format(meta: &loader::Metadata, format: &loader::Format) -> Value {
+	let international = if meta.international_formats.is_empty() {
+		Value::Null
+	}
+	else {
+		match meta.international_formats.iter().find(|f| f.pattern == format.pattern) {
+			Some(international) if international.format == format.format =>
+				Value::Null,
+
+			Some(international) =>
+				international.format.clone().into(),
+
+			None =>
+				"NA".into(),
+		}
+	};
+
+	// libphonenumber-js expects the rule with the national prefix and first
+	// group already in place.
+	let rule = format.national_prefix_formatting_rule.as_ref().map(|rule|
+		rule.replace("$NP", meta.national_prefix.as_deref().unwrap_or(""))
+			.replace("$FG", "$1"));
+
+	trim(vec![
+		format.pattern.as_deref().map(pattern).into(),
+		format.format.clone().into(),
+		if format.leading_digits.is_empty() {
+			Value::Null
+		}
+		else {
+			format.leading_digits.iter().map(|p| pattern(p)).collect::<Vec<_>>().into()
+		},
+		rule.into(),
+		if format.national_prefix_optional_when_formatting { true.into() } else { Value::Null },
+		international,
+	])
+}
+
+/// Patterns are written in free spacing mode in the metadata, JavaScript has
+/// no such mode.
+fn pattern(value: &str) -> Value {
+	value.chars().filter(|c| !c.is_whitespace()).collect::<String>().into()
+}
+
+fn lengths_of(lengths: &[u16]) -> Value {
+	if lengths.is_empty() {
+		Value::Null
+	}
+	else {
+		lengths.to_vec().into()
+	}
+}
+
+/// Drop the trailing missing values of an array, as libphonenumber-js does.
+fn trim(mut values: Vec<Value>) -> Value {
+	while values.last().map(Value::is_null).unwrap_or(false) {
+		values.pop();
+	}
+
+	values.into()
+}
+
+#[cfg(test)]
+mod test {
+	use serde_json::Value;
+
+	use crate::metadata::DATABASE;
+	use crate::metadata::export;
+
+	#[test]
+	fn libphonenumber_js() {
+		let mut out = Vec::new();
+		export::libphonenumber_js(&DATABASE, &mut out).unwrap();
+
+		let json: Value = serde_json::from_slice(&out).unwrap();
+		assert_eq!(4, json["version"]);
+		assert_eq!("US", json["country_calling_codes"]["1"][0]);
+		assert_eq!("001", json["country_calling_codes"]["800"][0]);
+
+		let gb = &json["countries"]["GB"];
+		assert_eq!("44", gb[0]);
+		assert_eq!("00", gb[1]);
+		assert_eq!("0", gb[5]);
+		assert!(gb[3].as_array().unwrap().contains(&Value::from(10)));
+		assert!(!gb[2].as_str().unwrap().contains(char::is_whitespace));
+
+		// The national prefix rule has its placeholders replaced.
+		let formats = gb[4].as_array().unwrap();
+		assert!(formats.iter().any(|f| f[3] == "0$1"));
+		assert!(formats.iter().all(|f| !f[3].as_str().unwrap_or("").contains("$NP")));
+
+		// Mobile numbers are the second type.
+		let mobile = gb[11][1][0].as_str().unwrap();
+		assert!(regex::Regex::new(&format!("^(?:{})$", mobile)).unwrap().is_match("7912345678"));
+
+		assert_eq!("800", json["nonGeographic"]["800"][0]);
+	}
+}
@@ -0,0 +1,174 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::OnceLock;
+
+use crate::metadata::loader;
+
+#[derive(Debug)]
+pub(crate) struct Region {
+	pub general:          Descriptor,
+	pub fixed_line:       Option<Descriptor>,
+	pub mobile:           Option<Descriptor>,
+	pub toll_free:        Option<Descriptor>,
+	pub premium_rate:     Option<Descriptor>,
+	pub shared_cost:      Option<Descriptor>,
+	pub personal_number:  Option<Descriptor>,
+	pub voip:             Option<Descriptor>,
+	pub pager:            Option<Descriptor>,
+	pub uan:              Option<Descriptor>,
+	pub emergency:        Option<Descriptor>,
+	pub voicemail:        Option<Descriptor>,
+	pub short_code:       Option<Descriptor>,
+	pub standard_rate:    Option<Descriptor>,
+	pub carrier:          Option<Descriptor>,
+	pub no_international: Option<Descriptor>,
+
+	pub sms_services:       Option<Descriptor>,
+	pub expanded_emergency: Option<Descriptor>,
+	pub area_code_optional: Option<Descriptor>,
+
+	pub id:           &'static str,
+	pub country_code: u16,
+
+	pub international_prefix:           Option<&'static str>,
+	pub preferred_international_prefix: Option<&'static str>,
+	pub national_prefix:                Option<&'static str>,
+	pub preferred_extension_prefix:     Option<&'static str>,
+
+	pub national_prefix_for_parsing:    Option<&'static str>,
+	pub national_prefix_transform_rule: Option<&'static str>,
+
+	pub formats:               &'static [Format],
+	pub international_formats: &'static [Format],
+
+	pub main_country_for_code:  bool,
+	pub leading_digits:         Option<&'static str>,
+	pub mobile_number_portable: bool,
+
+	pub same_mobile_and_fixed_line_pattern: bool,
+
+	pub national_prefix_formatting_rule:          Option<&'static str>,
+	pub national_prefix_optional_when_formatting: bool,
+	pub carrier_code_formatting_rule:             Option<&'static str>,
+
+	pub references: &'static [&'static str],
+}
+
+#[derive(Debug)]
+pub(crate) struct Descriptor {
+	pub national_number:       &'static str,
+	pub possible_length:       &'static [u16],
+	pub possible_local_length: &'static [u16],
+	pub example:               Option<&'static str>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Format {
+	pub pattern:                  &'static str,
+	pub format:                   &'static str,
+	pub leading_digits:           &'static [&'static str],
+	pub national_prefix:          Option<&'static str>,
+	pub national_prefix_optional: bool,
+	pub domestic_carrier:         Option<&'static str>,
+}
+
+include!(concat!(env!("OUT_DIR"), "/metadata.rs"));
+
+/// The compiled regions, filled on first access.
+///
+/// The tables only hold the patterns as strings, compiling a region builds
+/// its regexes, so the first lookup of a region pays for it.
+pub(crate) static COMPILED: [OnceLock<super::Metadata>; COUNT] = [const { OnceLock::new() }; COUNT];
+
+/// Find the index of a region by its ID.
+pub(crate) fn position(id: &str) -> Option<usize> {
+	REGIONS.binary_search_by(|r| r.id.cmp(id)).ok()
+}
+
+/// Find the indices of the regions for a country code.
+pub(crate) fn by_code(code: u16) -> Option<&'static [u16]> {
+	BY_CODE.binary_search_by_key(&code, |&(c, _)| c).ok()
+		.map(|index| BY_CODE[index].1)
+}
+
+impl<'a> From<&'a Region> for loader::Metadata {
+	fn from(region: &'a Region) -> Self {
+		let string = |value: Option<&str>| value.map(String::from);
+		let descriptor = |desc: &Descriptor| loader::Descriptor {
+			national_number:       Some(desc.national_number.into()),
+			possible_length:       desc.possible_length.into(),
+			possible_local_length: desc.possible_local_length.into(),
+			example:               string(desc.example),
+		};
+		let format = |format: &Format| loader::Format {
+			pattern:        Some(format.pattern.into()),
+			format:         Some(format.format.into()),
+			leading_digits: format.leading_digits.iter().map(|&l| l.into()).collect(),
+
+			national_prefix_formatting_rule:          string(format.national_prefix),
+			national_prefix_optional_when_formatting: format.national_prefix_optional,
+			domestic_carrier:                         string(format.domestic_carrier),
+		};
+
+		loader::Metadata {
+			general:          Some(descriptor(&region.general)),
+			fixed_line:       region.fixed_line.as_ref().map(descriptor),
+			mobile:           region.mobile.as_ref().map(descriptor),
+			toll_free:        region.toll_free.as_ref().map(descriptor),
+			premium_rate:     region.premium_rate.as_ref().map(descriptor),
+			shared_cost:      region.shared_cost.as_ref().map(descriptor),
+			personal_number:  region.personal_number.as_ref().map(descriptor),
+			voip:             region.voip.as_ref().map(descriptor),
+			pager:            region.pager.as_ref().map(descriptor),
+			uan:              region.uan.as_ref().map(descriptor),
+			emergency:        region.emergency.as_ref().map(descriptor),
+			voicemail:        region.voicemail.as_ref().map(descriptor),
+			short_code:       region.short_code.as_ref().map(descriptor),
+			standard_rate:    region.standard_rate.as_ref().map(descriptor),
+			carrier:          region.carrier.as_ref().map(descriptor),
+			no_international: region.no_international.as_ref().map(descriptor),
+
+			sms_services:       region.sms_services.as_ref().map(descriptor),
+			expanded_emergency: region.expanded_emergency.as_ref().map(descriptor),
+			area_code_optional: region.area_code_optional.as_ref().map(descriptor),
+
+			id:           Some(region.id.into()),
+			country_code: Some(region.country_code),
+
+			international_prefix:           string(region.international_prefix),
+			preferred_international_prefix: string(region.preferred_international_prefix),
+			national_prefix:                string(region.national_prefix),
+			preferred_extension_prefix:     string(region.preferred_extension_prefix),
+
+			national_prefix_for_parsing:    string(region.national_prefix_for_parsing),
+			national_prefix_transform_rule: string(region.national_prefix_transform_rule),
+
+			formats:               region.formats.iter().map(format).collect(),
+			international_formats: region.international_formats.iter().map(format).collect(),
+
+			main_country_for_code:  region.main_country_for_code,
+			leading_digits:         string(region.leading_digits),
+			mobile_number_portable: region.mobile_number_portable,
+
+			same_mobile_and_fixed_line_pattern: region.same_mobile_and_fixed_line_pattern,
+
+			national_prefix_formatting_rule:          string(region.national_prefix_formatting_rule),
+			national_prefix_optional_when_formatting: region.national_prefix_optional_when_formatting,
+			carrier_code_formatting_rule:             string(region.carrier_code_formatting_rule),
+
+			references: region.references.iter().map(|&r| r.into()).collect(),
+		}
+	}
+}
@@ -0,0 +1,529 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::metadata::{Database, Metadata, Descriptor, Format, CLASSIFIED, loader};
+use crate::metadata::database::{Region, compile};
+use crate::metadata::matcher::Matcher;
+use crate::phone_number::Type;
+use crate::validator;
+use crate::parser;
+use crate::consts;
+
+/// A finding of `lint`, for a region.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Diagnostic {
+	pub(crate) region:       String,
+	pub(crate) country_code: u16,
+	pub(crate) lint:         Lint,
+}
+
+/// The kind of finding.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Lint {
+	/// The region could not be compiled.
+	Invalid {
+		/// The compilation error.
+		error: String,
+	},
+
+	/// An example number isn't matched whole by its own descriptor.
+	ExampleMismatch {
+		/// The type of the descriptor.
+		kind: Type,
+
+		/// The example number.
+		example: String,
+	},
+
+	/// An example number is classified as another type.
+	ExampleMisclassified {
+		/// The type of the descriptor.
+		kind: Type,
+
+		/// The example number.
+		example: String,
+
+		/// The type `number_type` gives.
+		found: Type,
+	},
+
+	/// A possible length no number of the pattern has.
+	ImpossibleLength {
+		/// The type of the descriptor, `Type::Unknown` for the general one.
+		kind: Type,
+
+		/// The length.
+		length: u16,
+	},
+
+	/// A length of numbers of the pattern missing from the possible lengths.
+	MissingLength {
+		/// The type of the descriptor, `Type::Unknown` for the general one.
+		kind: Type,
+
+		/// The length.
+		length: u16,
+	},
+
+	/// The leading digits of two regions sharing a country code match the same
+	/// numbers.
+	OverlappingLeadingDigits {
+		/// The other region.
+		other: String,
+
+		/// The shortest number both match.
+		prefix: String,
+	},
+
+	/// A format can't be picked, the formats before it take every number it
+	/// matches.
+	UnreachableFormat {
+		/// The pattern of the format.
+		pattern: String,
+
+		/// Whether it's an international format.
+		international: bool,
+	},
+
+	/// The `nationalPrefixTransformRule` refers to a group missing from the
+	/// `nationalPrefixForParsing` pattern.
+	TransformRule {
+		/// The transform rule.
+		rule: String,
+
+		/// The number of groups of the pattern.
+		groups: usize,
+	},
+
+	/// An example number written with the national prefix doesn't parse back
+	/// to itself.
+	NationalPrefixForParsing {
+		/// The number as written.
+		input: String,
+
+		/// The national number it parsed to, if it parsed at all.
+		national: Option<String>,
+	},
+}
+
+impl Diagnostic {
+	/// The ID of the region.
+	pub fn region(&self) -> &str {
+		&self.region
+	}
+
+	/// The country code of the region.
+	pub fn country_code(&self) -> u16 {
+		self.country_code
+	}
+
+	/// The finding.
+	pub fn lint(&self) -> &Lint {
+		&self.lint
+	}
+}
+
+/// Every descriptor type but the general one.
+const TYPES: [Type; 15] = [
+	Type::FixedLine, Type::Mobile, Type::TollFree, Type::PremiumRate,
+	Type::SharedCost, Type::PersonalNumber, Type::Voip, Type::Pager, Type::Uan,
+	Type::Emergency, Type::Voicemail, Type::ShortCode, Type::StandardRate,
+	Type::Carrier, Type::NoInternational,
+];
+
+/// Formats are checked against at most this many formats before them, to fit
+/// the patterns and leading digits of all of them in one automaton.
+const FORMATS: usize = 15;
+
+/// Check the regions of the database for inconsistencies.
+///
+/// Patterns the digit automaton can't represent are left unchecked where an
+/// automaton is needed.
+pub fn lint(database: &Database) -> Vec<Diagnostic> {
+	let regions     = database.regions();
+	let mut results = Vec::new();
+
+	for (index, region) in regions.iter().enumerate() {
+		let meta = region.get();
+		let mut report = |lint| results.push(Diagnostic {
+			region:       meta.id.clone(),
+			country_code: meta.country_code,
+			lint,
+		});
+
+		examples(meta, &mut report);
+		lengths(meta, &mut report);
+		formats(&meta.formats, false, &mut report);
+		formats(&meta.international_formats, true, &mut report);
+		national_prefix(database, meta, &mut report);
+
+		// Regions are grouped by country code, only the later ones are checked
+		// to report every overlap once.
+		for other in regions[index + 1 ..].iter().map(|r| r.get())
+			.filter(|other| other.country_code == meta.country_code)
+		{
+			if let Some(lint) = overlap(meta, other) {
+				report(lint);
+			}
+		}
+	}
+
+	results
+}
+
+/// Check the given loaded regions for inconsistencies, see `lint`, regions
+/// that fail to compile are reported as `Lint::Invalid`.
+pub fn lint_loaded(meta: Vec<loader::Metadata>) -> Vec<Diagnostic> {
+	let mut invalid = Vec::new();
+	let mut regions = Vec::new();
+
+	for meta in meta {
+		let region       = meta.id.clone().unwrap_or_default();
+		let country_code = meta.country_code.unwrap_or_default();
+
+		match compile(meta) {
+			Ok(meta) =>
+				regions.push(Arc::new(Region::Compiled(meta))),
+
+			Err(err) =>
+				invalid.push(Diagnostic {
+					region,
+					country_code,
+					lint: Lint::Invalid { error: err.to_string() },
+				}),
+		}
+	}
+
+	invalid.extend(lint(&Database::with_regions(regions, Vec::new(), None)));
+	invalid
+}
+
+fn examples<F: FnMut(Lint)>(meta: &Metadata, report: &mut F) {
+	for &kind in &TYPES {
+		let descriptor = if let Some(descriptor) = meta.descriptors.get(kind) { descriptor } else {
+			continue;
+		};
+
+		let example = if let Some(example) = descriptor.example() { example } else {
+			continue;
+		};
+
+		if !is_whole_match(descriptor, example) {
+			report(Lint::ExampleMismatch { kind, example: example.into() });
+			continue;
+		}
+
+		if CLASSIFIED.contains(&kind) {
+			let found = validator::number_type(meta, example);
+			let same  = found == kind || (found == Type::FixedLineOrMobile &&
+				(kind == Type::FixedLine || kind == Type::Mobile));
+
+			if !same {
+				report(Lint::ExampleMisclassified { kind, example: example.into(), found });
+			}
+		}
+	}
+}
+
+fn is_whole_match(descriptor: &Descriptor, value: &str) -> bool {
+	if !descriptor.is_possible_length(value) {
+		return false;
+	}
+
+	if let Some(mask) = descriptor.matcher.as_ref().and_then(|m| m.matches(value)) {
+		return mask != 0;
+	}
+
+	descriptor.national_number.find(value)
+		.map(|m| m.start() == 0 && m.end() == value.len())
+		.unwrap_or(false)
+}
+
+fn lengths<F: FnMut(Lint)>(meta: &Metadata, report: &mut F) {
+	for kind in Some(Type::Unknown).into_iter().chain(TYPES.iter().cloned()) {
+		let descriptor = if let Some(descriptor) = meta.descriptors.get(kind) { descriptor } else {
+			continue;
+		};
+
+		// Descriptors without lengths take those of the general one.
+		let matcher = descriptor.matcher.as_ref();
+		let (matcher, possible) = match matcher {
+			Some(matcher) if !descriptor.possible_length.is_empty() =>
+				(matcher, &descriptor.possible_length),
+
+			_ =>
+				continue,
+		};
+
+		let actual = matcher.lengths(1, consts::MAX_LENGTH_FOR_NSN);
+
+		for &length in possible {
+			if !actual.contains(&length) {
+				report(Lint::ImpossibleLength { kind, length });
+			}
+		}
+
+		for &length in &actual {
+			if !possible.contains(&length) {
+				report(Lint::MissingLength { kind, length });
+			}
+		}
+	}
+}
+
+/// Formats are picked by the last of their leading digits patterns and a
+/// whole match of their pattern, in order.
+fn formats<F: FnMut(Lint)>(formats: &[Format], international: bool, report: &mut F) {
+	for (index, format) in formats.iter().enumerate() {
+		let earlier  = &formats[index.saturating_sub(FORMATS) .. index];
+		let patterns = earlier.iter().chain(Some(format))
+			.flat_map(|f| vec![Some(f.pattern.as_str()), f.leading_digits.last().map(Regex::as_str)])
+			.collect::<Vec<_>>();
+
+		let matcher = if let Some(matcher) = Matcher::new(&patterns) { matcher } else {
+			continue;
+		};
+
+		// Format `i` uses bits `2i` and `2i + 1` for its pattern and leading digits.
+		let picks = |i: usize, seen: u32, whole: u32| {
+			whole & (1 << (2 * i)) != 0 &&
+				(patterns[2 * i + 1].is_none() || seen & (1 << (2 * i + 1)) != 0)
+		};
+
+		let last = earlier.len();
+		let reachable = matcher.search(consts::MAX_LENGTH_FOR_NSN, |seen, whole|
+			picks(last, seen, whole) && (0 .. last).all(|i| !picks(i, seen, whole)));
+
+		if reachable.is_none() {
+			report(Lint::UnreachableFormat {
+				pattern: format.pattern.as_str().into(),
+				international,
+			});
+		}
+	}
+}
+
+fn national_prefix<F: FnMut(Lint)>(database: &Database, meta: &Metadata, report: &mut F) {
+	if let (Some(pattern), Some(rule)) = (meta.national_prefix_for_parsing.as_ref(), meta.national_prefix_transform_rule.as_ref()) {
+		let groups = pattern.captures_len() - 1;
+		let refers = rule.char_indices()
+			.filter(|&(_, c)| c == '$')
+			.filter_map(|(i, _)| rule[i + 1 ..].chars().next().and_then(|c| c.to_digit(10)))
+			.any(|group| group as usize > groups);
+
+		if refers {
+			report(Lint::TransformRule { rule: rule.clone(), groups });
+		}
+	}
+
+	let country = if let Ok(country) = meta.id.parse() { country } else {
+		return;
+	};
+
+	let prefix = meta.national_prefix.as_deref().unwrap_or("");
+	for &kind in &CLASSIFIED[1 ..] {
+		let example = if let Some(example) = meta.descriptors.get(kind).and_then(|d| d.example()) { example } else {
+			continue;
+		};
+
+		let input    = format!("{}{}", prefix, example);
+		let national = parser::parse_with(database, Some(country), &input).ok()
+			.map(|number| number.national().to_string());
+
+		if national.as_deref() != Some(example) {
+			report(Lint::NationalPrefixForParsing { input, national });
+		}
+	}
+}
+
+fn overlap(meta: &Metadata, other: &Metadata) -> Option<Lint> {
+	let (a, b) = match (meta.leading_digits.as_ref(), other.leading_digits.as_ref()) {
+		(Some(a), Some(b)) => (a, b),
+		_ => return None,
+	};
+
+	let matcher = Matcher::new(&[Some(a.as_str()), Some(b.as_str())])?;
+	let prefix  = matcher.search(consts::MAX_LENGTH_FOR_NSN, |seen, _| seen == 0b11)?;
+
+	Some(Lint::OverlappingLeadingDigits {
+		other: other.id.clone(),
+		prefix,
+	})
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} (+{}): {}", self.region, self.country_code, self.lint)
+	}
+}
+
+impl fmt::Display for Lint {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Lint::Invalid { error } =>
+				write!(f, "invalid metadata: {}", error),
+
+			Lint::ExampleMismatch { kind, example } =>
+				write!(f, "example {:?} doesn't match the {:?} descriptor", example, kind),
+
+			Lint::ExampleMisclassified { kind, example, found } =>
+				write!(f, "example {:?} of the {:?} descriptor is classified as {:?}", example, kind, found),
+
+			Lint::ImpossibleLength { kind, length } =>
+				write!(f, "possible length {} of the {:?} descriptor matches no number", length, kind),
+
+			Lint::MissingLength { kind, length } =>
+				write!(f, "the {:?} descriptor matches numbers of length {} missing from its possible lengths", kind, length),
+
+			Lint::OverlappingLeadingDigits { other, prefix } =>
+				write!(f, "leading digits overlap with {} on {:?}", other, prefix),
+
+			Lint::UnreachableFormat { pattern, international } =>
+				write!(f, "{} format {:?} is never picked",
+					if *international { "international" } else { "national" }, pattern),
+
+			Lint::TransformRule { rule, groups } =>
+				write!(f, "transform rule {:?} refers to a missing group, the pattern has {} groups", rule, groups),
+
+			Lint::NationalPrefixForParsing { input, national: Some(national) } =>
+				write!(f, "{:?} parses to {:?}", input, national),
+
+			Lint::NationalPrefixForParsing { input, national: None } =>
+				write!(f, "{:?} doesn't parse", input),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::io::Cursor;
+
+	use crate::metadata::{DATABASE, lint, lint_loaded, loader, Lint};
+	use crate::phone_number::Type;
+
+	#[test]
+	fn lints() {
+		let diagnostics = lint_loaded(loader::load(Cursor::new(r#"
+			<phoneNumberMetadata>
+				<territories>
+					<territory id="XK" countryCode="383" leadingDigits="[23]" nationalPrefix="0"
+					           nationalPrefixForParsing="0(\d)" nationalPrefixTransformRule="$2">
+						<availableFormats>
+							<numberFormat pattern="(\d{2})(\d{3})(\d{3})">
+								<format>$1 $2 $3</format>
+							</numberFormat>
+							<numberFormat pattern="(\d{2})(\d{6})">
+								<leadingDigits>3</leadingDigits>
+								<format>$1 $2</format>
+							</numberFormat>
+						</availableFormats>
+						<generalDesc>
+							<nationalNumberPattern>[23]\d{7}</nationalNumberPattern>
+						</generalDesc>
+						<fixedLine>
+							<possibleLengths national="8,9"/>
+							<exampleNumber>38123456</exampleNumber>
+							<nationalNumberPattern>3\d{7}</nationalNumberPattern>
+						</fixedLine>
+						<mobile>
+							<possibleLengths national="8"/>
+							<exampleNumber>41234567</exampleNumber>
+							<nationalNumberPattern>[24]\d{7}</nationalNumberPattern>
+						</mobile>
+						<tollFree>
+							<possibleLengths national="7"/>
+							<exampleNumber>9001234</exampleNumber>
+							<nationalNumberPattern>800\d{4}</nationalNumberPattern>
+						</tollFree>
+						<premiumRate>
+							<possibleLengths national="7"/>
+							<exampleNumber>9001234</exampleNumber>
+							<nationalNumberPattern>900\d{4,5}</nationalNumberPattern>
+						</premiumRate>
+					</territory>
+
+					<territory id="XA" countryCode="383" leadingDigits="3[0-5]">
+						<generalDesc>
+							<nationalNumberPattern>3\d{7}</nationalNumberPattern>
+						</generalDesc>
+					</territory>
+
+					<territory id="XB" countryCode="383">
+						<generalDesc>
+							<nationalNumberPattern>(</nationalNumberPattern>
+						</generalDesc>
+					</territory>
+				</territories>
+			</phoneNumberMetadata>
+		"#)).unwrap());
+
+		let lints = diagnostics.iter().map(|d| (d.region(), d.lint().clone())).collect::<Vec<_>>();
+		let has   = |region, lint| lints.contains(&(region, lint));
+
+		assert!(matches!(diagnostics[0].lint(), Lint::Invalid { .. }));
+		assert_eq!("XB", diagnostics[0].region());
+
+		assert!(has("XK", Lint::ExampleMisclassified { kind: Type::Mobile, example: "41234567".into(), found: Type::Unknown }));
+		assert!(has("XK", Lint::ExampleMismatch { kind: Type::TollFree, example: "9001234".into() }));
+		assert!(has("XK", Lint::ImpossibleLength { kind: Type::FixedLine, length: 9 }));
+		assert!(has("XK", Lint::MissingLength { kind: Type::PremiumRate, length: 8 }));
+		assert!(has("XK", Lint::OverlappingLeadingDigits { other: "XA".into(), prefix: "30".into() }));
+		assert!(has("XK", Lint::UnreachableFormat { pattern: r"(\d{2})(\d{6})".into(), international: false }));
+		assert!(has("XK", Lint::TransformRule { rule: "$2".into(), groups: 1 }));
+		assert!(lints.iter().any(|(region, lint)| *region == "XK" &&
+			matches!(lint, Lint::NationalPrefixForParsing { input, .. } if input == "038123456")));
+
+		assert_eq!("XK (+383): leading digits overlap with XA on \"30\"",
+			diagnostics.iter().find(|d| matches!(d.lint(), Lint::OverlappingLeadingDigits { .. })).unwrap().to_string());
+	}
+
+	#[test]
+	fn bundled() {
+		let mut parsing = Vec::new();
+
+		for diagnostic in lint(&DATABASE) {
+			match diagnostic.lint() {
+				Lint::ImpossibleLength { .. } =>
+					(),
+
+				Lint::NationalPrefixForParsing { input, national } =>
+					parsing.push((diagnostic.region().to_owned(), input.clone(), national.clone())),
+
+				_ =>
+					panic!("{}", diagnostic),
+			}
+		}
+
+		// Ambiguities of the bundled metadata: in CO the national prefix for
+		// parsing takes a carrier code, which eats the start of mobile numbers,
+		// and in IL the national prefix followed by a number starting with 1 is
+		// an international prefix.
+		let expected = [
+			("CO", "03211234567", Some("211234567")),
+			("IL", "015112340000", Some("12340000")),
+			("IL", "01599123456", None),
+			("IL", "01919123456", Some("9123456")),
+		].iter().map(|&(region, input, national)|
+			(region.to_owned(), input.to_owned(), national.map(String::from)))
+		.collect::<Vec<_>>();
+
+		parsing.sort();
+		assert_eq!(expected, parsing);
+	}
+}
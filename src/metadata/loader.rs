@@ -13,7 +13,10 @@
 // limitations under the License.
 
 use std::str;
-use std::io::{BufRead};
+use std::str::FromStr;
+use std::io::{self, Read, BufRead};
+use std::rc::Rc;
+use std::cell::RefCell;
 
 use crate::xml::Reader;
 use crate::xml::events::{self, Event};
@@ -21,15 +24,9 @@ use crate::xml::events::attributes::Attribute;
 
 use crate::error;
 
-/// Temporary defaults for `Format` and `Descriptor`.
-#[derive(Clone, Default, Serialize, Deserialize, Debug)]
-pub struct Defaults {
-	format:     Format,
-	descriptor: Descriptor,
-}
-
 /// Parsed version of `metadata::Metadata`.
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Metadata {
 	pub general:          Option<Descriptor>,
 	pub fixed_line:       Option<Descriptor>,
@@ -48,6 +45,10 @@ pub struct Metadata {
 	pub carrier:          Option<Descriptor>,
 	pub no_international: Option<Descriptor>,
 
+	pub sms_services:       Option<Descriptor>,
+	pub expanded_emergency: Option<Descriptor>,
+	pub area_code_optional: Option<Descriptor>,
+
 	pub id:           Option<String>,
 	pub country_code: Option<u16>,
 
@@ -65,12 +66,18 @@ pub struct Metadata {
 	pub main_country_for_code: bool,
 	pub leading_digits: Option<String>,
 	pub mobile_number_portable: bool,
+	pub same_mobile_and_fixed_line_pattern: bool,
+
+	pub national_prefix_formatting_rule: Option<String>,
+	pub national_prefix_optional_when_formatting: bool,
+	pub carrier_code_formatting_rule: Option<String>,
 
-	pub defaults: Defaults,
+	pub references: Vec<String>,
 }
 
 /// Parsed version of `metadata::Format`.
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Format {
 	pub pattern: Option<String>,
 	pub format: Option<String>,
@@ -82,6 +89,7 @@ pub struct Format {
 
 /// Parsed version of `metadata::Descriptor`.
 #[derive(Clone, Default, Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Descriptor {
 	pub national_number: Option<String>,
 	pub possible_length: Vec<u16>,
@@ -89,15 +97,215 @@ pub struct Descriptor {
 	pub example: Option<String>,
 }
 
+/// An issue found by `load_lenient`.
+#[derive(Debug)]
+pub struct Issue {
+	pub severity: Severity,
+
+	/// The error, with its location.
+	pub error: error::LoadMetadata,
+}
+
+/// The severity of an `Issue`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Severity {
+	/// Something the loader doesn't know about was skipped.
+	Warning,
+
+	/// Something failed to load and was skipped, or the loading stopped.
+	Error,
+}
+
+/// Metadata loaded along with the release it's from.
+#[derive(Clone, Debug)]
+pub struct Loaded {
+	pub territories: Vec<Metadata>,
+
+	/// The version in the `version` attribute of the root element, if any.
+	pub version: Option<String>,
+}
+
 /// Load XML metadata from the given reader.
+///
+/// Errors carry their location in the XML and the territory being loaded.
 pub fn load<R: BufRead>(reader: R) -> Result<Vec<Metadata>, error::LoadMetadata> {
-	metadata(&mut Reader::from_reader(reader))
+	load_versioned(reader).map(|loaded| loaded.territories)
 }
 
-fn metadata<R: BufRead>(reader: &mut Reader<R>) -> Result<Vec<Metadata>, error::LoadMetadata> {
-	let mut buffer = Vec::new();
+/// Load XML metadata from the given reader, along with its version.
+pub fn load_versioned<R: BufRead>(reader: R) -> Result<Loaded, error::LoadMetadata> {
+	let mut source = Source::new(reader, false);
 	let mut result = Vec::new();
 
+	metadata(&mut source, &mut result)
+		.map_err(|e| source.locate(e))?;
+
+	Ok(Loaded {
+		territories: result,
+		version:     source.version,
+	})
+}
+
+/// Load XML metadata from the given reader, collecting every issue instead of
+/// stopping at the first one.
+///
+/// Unknown elements are reported as warnings, elements and attributes failing
+/// to load are reported as errors and skipped. Malformed XML still stops the
+/// loading, keeping the territories loaded until then.
+pub fn load_lenient<R: BufRead>(reader: R) -> (Vec<Metadata>, Vec<Issue>) {
+	let mut source = Source::new(reader, true);
+	let mut result = Vec::new();
+
+	if let Err(err) = metadata(&mut source, &mut result) {
+		let error = source.locate(err);
+		source.issues.push(Issue { severity: Severity::Error, error });
+	}
+
+	(result, source.issues)
+}
+
+/// The XML reader along with what's needed to locate and collect issues.
+struct Source<R: BufRead> {
+	reader:    Reader<Lines<R>>,
+	lines:     Rc<RefCell<Vec<usize>>>,
+	version:   Option<String>,
+	territory: Option<String>,
+	lenient:   bool,
+	issues:    Vec<Issue>,
+}
+
+impl<R: BufRead> Source<R> {
+	fn new(reader: R, lenient: bool) -> Self {
+		let lines = Rc::new(RefCell::new(Vec::new()));
+
+		Source {
+			reader: Reader::from_reader(Lines {
+				inner:  reader,
+				offset: 0,
+				starts: lines.clone(),
+			}),
+
+			lines,
+			version: None,
+			territory: None,
+			lenient,
+			issues: Vec::new(),
+		}
+	}
+
+	fn read_event<'b>(&mut self, buffer: &'b mut Vec<u8>) -> Result<Event<'b>, crate::xml::Error> {
+		self.reader.read_event(buffer)
+	}
+
+	/// Attach the current location to the error.
+	fn locate(&self, error: error::LoadMetadata) -> error::LoadMetadata {
+		if error.location().is_some() {
+			return error;
+		}
+
+		let offset = self.reader.buffer_position();
+		let lines  = self.lines.borrow();
+		let line   = lines.partition_point(|&start| start <= offset);
+		let start  = if line == 0 { 0 } else { lines[line - 1] };
+
+		error.at(error::Location {
+			offset,
+			line:      line + 1,
+			column:    offset - start + 1,
+			territory: self.territory.clone(),
+		})
+	}
+
+	/// Record the error and go on when lenient, fail with it otherwise.
+	fn recover(&mut self, error: error::LoadMetadata) -> Result<(), error::LoadMetadata> {
+		if !self.lenient {
+			return Err(error);
+		}
+
+		let error = self.locate(error);
+		self.issues.push(Issue { severity: Severity::Error, error });
+
+		Ok(())
+	}
+
+	/// Record an unknown element as a warning and go on when lenient, fail with
+	/// it otherwise.
+	fn unhandled(&mut self, error: error::LoadMetadata) -> Result<(), error::LoadMetadata> {
+		if !self.lenient {
+			return Err(error);
+		}
+
+		self.warn(error);
+		Ok(())
+	}
+
+	/// Record a warning when lenient.
+	fn warn(&mut self, error: error::LoadMetadata) {
+		if self.lenient {
+			let error = self.locate(error);
+			self.issues.push(Issue { severity: Severity::Warning, error });
+		}
+	}
+
+	/// Parse an attribute value, `None` if it failed and the loader recovered.
+	fn parse<T>(&mut self, value: &str) -> Result<Option<T>, error::LoadMetadata>
+		where T: FromStr, error::LoadMetadata: From<T::Err>
+	{
+		match value.parse() {
+			Ok(value) =>
+				Ok(Some(value)),
+
+			Err(err) =>
+				self.recover(err.into()).map(|_| None),
+		}
+	}
+}
+
+/// A reader keeping the offsets where lines start, to locate errors.
+struct Lines<R> {
+	inner:  R,
+	offset: usize,
+	starts: Rc<RefCell<Vec<usize>>>,
+}
+
+impl<R: BufRead> Read for Lines<R> {
+	fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		let amount = {
+			let available = self.fill_buf()?;
+			let amount    = available.len().min(buffer.len());
+			buffer[.. amount].copy_from_slice(&available[.. amount]);
+
+			amount
+		};
+
+		self.consume(amount);
+		Ok(amount)
+	}
+}
+
+impl<R: BufRead> BufRead for Lines<R> {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
+		self.inner.fill_buf()
+	}
+
+	fn consume(&mut self, amount: usize) {
+		if let Ok(buffer) = self.inner.fill_buf() {
+			let buffer     = &buffer[.. amount.min(buffer.len())];
+			let mut starts = self.starts.borrow_mut();
+
+			for (index, _) in buffer.iter().enumerate().filter(|&(_, &b)| b == b'\n') {
+				starts.push(self.offset + index + 1);
+			}
+		}
+
+		self.inner.consume(amount);
+		self.offset += amount;
+	}
+}
+
+fn metadata<R: BufRead>(reader: &mut Source<R>, result: &mut Vec<Metadata>) -> Result<(), error::LoadMetadata> {
+	let mut buffer = Vec::new();
+
 	loop {
 		match reader.read_event(&mut buffer)? {
 			Event::Text(_) |
@@ -107,23 +315,42 @@ fn metadata<R: BufRead>(reader: &mut Reader<R>) -> Result<Vec<Metadata>, error::
 
 			Event::Start(ref e) => {
 				match e.name() {
-					b"phoneNumberMetadata" =>
-						continue,
+					b"phoneNumberMetadata" => {
+						for attr in e.attributes() {
+							let Attribute { key, value } = attr?;
+
+							if key == b"version" {
+								reader.version = Some(str::from_utf8(&value)?.into());
+							}
+						}
+					}
 
 					b"territories" =>
-						result.extend(territories(reader)?),
+						territories(reader, result)?,
 
-					name =>
-						ignore(reader, name)?,
+					name => {
+						reader.warn(error::Metadata::UnhandledElement {
+							phase: "metadata".into(),
+							name:  str::from_utf8(name)?.into(),
+						}.into());
+
+						ignore(reader, name)?;
+					}
 				}
 			}
 
+			Event::Empty(ref e) =>
+				reader.warn(error::Metadata::UnhandledElement {
+					phase: "metadata".into(),
+					name:  str::from_utf8(e.name())?.into(),
+				}.into()),
+
 			Event::End(ref e) if e.name() != b"phoneNumberMetadata" =>
 				return Err(error::Metadata::MismatchedTag(
 					str::from_utf8(e.name())?.into()).into()),
 
 			Event::End(ref e) if e.name() == b"phoneNumberMetadata" =>
-				return Ok(result),
+				return Ok(()),
 
 			event =>
 				return Err(error::Metadata::UnhandledEvent {
@@ -134,9 +361,8 @@ fn metadata<R: BufRead>(reader: &mut Reader<R>) -> Result<Vec<Metadata>, error::
 	}
 }
 
-fn territories<R: BufRead>(reader: &mut Reader<R>) -> Result<Vec<Metadata>, error::LoadMetadata> {
+fn territories<R: BufRead>(reader: &mut Source<R>, result: &mut Vec<Metadata>) -> Result<(), error::LoadMetadata> {
 	let mut buffer = Vec::new();
-	let mut result = Vec::new();
 
 	loop {
 		match reader.read_event(&mut buffer)? {
@@ -149,13 +375,25 @@ fn territories<R: BufRead>(reader: &mut Reader<R>) -> Result<Vec<Metadata>, erro
 					b"territory" =>
 						result.push(territory(reader, e)?),
 
-					name =>
-						ignore(reader, name)?,
+					name => {
+						reader.warn(error::Metadata::UnhandledElement {
+							phase: "territories".into(),
+							name:  str::from_utf8(name)?.into(),
+						}.into());
+
+						ignore(reader, name)?;
+					}
 				}
 			}
 
+			Event::Empty(ref e) =>
+				reader.warn(error::Metadata::UnhandledElement {
+					phase: "territories".into(),
+					name:  str::from_utf8(e.name())?.into(),
+				}.into()),
+
 			Event::End(ref e) if e.name() == b"territories" =>
-				return Ok(result),
+				return Ok(()),
 
 			Event::End(ref e) =>
 				return Err(error::Metadata::MismatchedTag(
@@ -173,19 +411,31 @@ fn territories<R: BufRead>(reader: &mut Reader<R>) -> Result<Vec<Metadata>, erro
 	}
 }
 
-fn territory<'a, R: BufRead>(reader: &mut Reader<R>, e: &events::BytesStart<'a>) -> Result<Metadata, error::LoadMetadata> {
+fn territory<'a, R: BufRead>(reader: &mut Source<R>, e: &events::BytesStart<'a>) -> Result<Metadata, error::LoadMetadata> {
 	let mut buffer = Vec::new();
 	let mut meta   = Metadata::default();
 
+	// The ID is read first, so issues with the other attributes are located in
+	// the territory whatever their order.
+	for attr in e.attributes() {
+		let Attribute { key, value } = attr?;
+
+		if key == b"id" {
+			meta.id = Some(str::from_utf8(&value)?.into());
+		}
+	}
+
+	reader.territory = meta.id.clone();
+
 	for attr in e.attributes() {
 		let Attribute { key, value } = attr?;
 
 		match (str::from_utf8(key)?, str::from_utf8(&value)?) {
-			("id", value) =>
-				meta.id = Some(value.into()),
+			("id", _) =>
+				(),
 
 			("countryCode", value) =>
-				meta.country_code = Some(value.parse()?),
+				meta.country_code = reader.parse(value)?,
 
 			("internationalPrefix", value) =>
 				meta.international_prefix = Some(value.into()),
@@ -206,29 +456,32 @@ fn territory<'a, R: BufRead>(reader: &mut Reader<R>, e: &events::BytesStart<'a>)
 				meta.national_prefix_transform_rule = Some(value.into()),
 
 			("mainCountryForCode", value) =>
-				meta.main_country_for_code = value.parse()?,
+				meta.main_country_for_code = reader.parse(value)?.unwrap_or_default(),
 
 			("leadingDigits", value) =>
 				meta.leading_digits = Some(value.into()),
 
 			("mobileNumberPortableRegion", value) =>
-				meta.mobile_number_portable = value.parse()?,
+				meta.mobile_number_portable = reader.parse(value)?.unwrap_or_default(),
+
+			("sameMobileAndFixedLinePattern", value) =>
+				meta.same_mobile_and_fixed_line_pattern = reader.parse(value)?.unwrap_or_default(),
 
 			("nationalPrefixFormattingRule", value) =>
-				meta.defaults.format.national_prefix_formatting_rule = Some(value.into()),
+				meta.national_prefix_formatting_rule = Some(value.into()),
 
 			("nationalPrefixOptionalWhenFormatting", value) =>
-				meta.defaults.format.national_prefix_optional_when_formatting = value.parse()?,
+				meta.national_prefix_optional_when_formatting = reader.parse(value)?.unwrap_or_default(),
 
 			("carrierCodeFormattingRule", value) =>
-				meta.defaults.format.domestic_carrier = Some(value.into()),
+				meta.carrier_code_formatting_rule = Some(value.into()),
 
 			(name, value) =>
-				return Err(error::Metadata::UnhandledAttribute {
-					phase: "format".into(),
+				reader.recover(error::Metadata::UnhandledAttribute {
+					phase: "territory".into(),
 					name:  name.into(),
 					value: value.into()
-				}.into())
+				}.into())?
 		}
 	}
 
@@ -240,48 +493,65 @@ fn territory<'a, R: BufRead>(reader: &mut Reader<R>, e: &events::BytesStart<'a>)
 
 			Event::Start(ref e) => {
 				match e.name() {
-					name @ b"references" |
+					name @ b"references" =>
+						meta.references = references(reader, name)?,
+
 					name @ b"areaCodeOptional" =>
-						ignore(reader, name)?,
+						meta.area_code_optional = Some(descriptor(reader, name)?),
 
 					name @ b"generalDesc" =>
-						meta.general = Some(descriptor(reader, &meta, name)?),
+						meta.general = Some(descriptor(reader, name)?),
 
 					name @ b"fixedLine" =>
-						meta.fixed_line = Some(descriptor(reader, &meta, name)?),
+						meta.fixed_line = Some(descriptor(reader, name)?),
 
 					name @ b"mobile" =>
-						meta.mobile = Some(descriptor(reader, &meta, name)?),
+						meta.mobile = Some(descriptor(reader, name)?),
 
 					name @ b"tollFree" =>
-						meta.toll_free = Some(descriptor(reader, &meta, name)?),
+						meta.toll_free = Some(descriptor(reader, name)?),
 
 					name @ b"premiumRate" =>
-						meta.premium_rate = Some(descriptor(reader, &meta, name)?),
+						meta.premium_rate = Some(descriptor(reader, name)?),
 
 					name @ b"sharedCost" =>
-						meta.shared_cost = Some(descriptor(reader, &meta, name)?),
+						meta.shared_cost = Some(descriptor(reader, name)?),
 
 					name @ b"personalNumber" =>
-						meta.personal_number = Some(descriptor(reader, &meta, name)?),
+						meta.personal_number = Some(descriptor(reader, name)?),
 
 					name @ b"voip" =>
-						meta.voip = Some(descriptor(reader, &meta, name)?),
+						meta.voip = Some(descriptor(reader, name)?),
 
 					name @ b"pager" =>
-						meta.pager = Some(descriptor(reader, &meta, name)?),
+						meta.pager = Some(descriptor(reader, name)?),
 
 					name @ b"uan" =>
-						meta.uan = Some(descriptor(reader, &meta, name)?),
+						meta.uan = Some(descriptor(reader, name)?),
 
 					name @ b"emergency" =>
-						meta.emergency = Some(descriptor(reader, &meta, name)?),
+						meta.emergency = Some(descriptor(reader, name)?),
 
 					name @ b"voicemail" =>
-						meta.voicemail = Some(descriptor(reader, &meta, name)?),
+						meta.voicemail = Some(descriptor(reader, name)?),
 
 					name @ b"noInternationalDialling" =>
-						meta.no_international = Some(descriptor(reader, &meta, name)?),
+						meta.no_international = Some(descriptor(reader, name)?),
+
+					name @ b"shortCode" =>
+						meta.short_code = Some(descriptor(reader, name)?),
+
+					name @ b"standardRate" =>
+						meta.standard_rate = Some(descriptor(reader, name)?),
+
+					name @ b"carrierSpecific" =>
+						meta.carrier = Some(descriptor(reader, name)?),
+
+					name @ b"smsServices" =>
+						meta.sms_services = Some(descriptor(reader, name)?),
+
+					name @ b"expandedEmergency" =>
+						meta.expanded_emergency = Some(descriptor(reader, name)?),
 
 					name @ b"availableFormats" => {
 						let (national, international) = formats(reader, &meta, name)?;
@@ -290,16 +560,21 @@ fn territory<'a, R: BufRead>(reader: &mut Reader<R>, e: &events::BytesStart<'a>)
 						meta.international_formats = international;
 					}
 
-					name =>
-						return Err(error::Metadata::UnhandledElement {
+					name => {
+						reader.unhandled(error::Metadata::UnhandledElement {
 							phase: "territory".into(),
 							name:  str::from_utf8(name)?.into(),
-						}.into())
+						}.into())?;
+
+						ignore(reader, name)?;
+					}
 				}
 			}
 
-			Event::End(ref e) if e.name() == b"territory" =>
-				return Ok(meta),
+			Event::End(ref e) if e.name() == b"territory" => {
+				reader.territory = None;
+				return Ok(meta);
+			}
 
 			Event::End(ref e) =>
 				return Err(error::Metadata::MismatchedTag(
@@ -317,25 +592,37 @@ fn territory<'a, R: BufRead>(reader: &mut Reader<R>, e: &events::BytesStart<'a>)
 	}
 }
 
-fn descriptor<R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8]) -> Result<Descriptor, error::LoadMetadata> {
+fn descriptor<R: BufRead>(reader: &mut Source<R>, name: &[u8]) -> Result<Descriptor, error::LoadMetadata> {
 	let mut buffer     = Vec::new();
-	let mut descriptor = meta.defaults.descriptor.clone();
+	let mut descriptor = Descriptor::default();
+
+	/// Parse a list of lengths and ranges of lengths, e.g. "4,[6-8]".
+	fn lengths(name: &str, value: &str) -> Result<Vec<u16>, error::LoadMetadata> {
+		let malformed = || error::LoadMetadata::from(error::Metadata::MalformedValue {
+			phase: "descriptor::possibleLength".into(),
+			name:  name.into(),
+			value: value.into(),
+		});
 
-	fn lengths(value: &str) -> Result<Vec<u16>, error::LoadMetadata> {
 		let mut result = Vec::new();
 
 		for part in value.split(',').map(str::trim) {
-			if part.as_bytes()[0] == b'[' {
-				let mut parts = part.split('-');
+			if let Some(range) = part.strip_prefix('[') {
+				let (start, end) = range.strip_suffix(']')
+					.and_then(|r| r.split_once('-'))
+					.ok_or_else(malformed)?;
 
-				if let (Some(start), Some(end)) = (parts.next(), parts.next()) {
-					let start = start[1 ..].parse::<u16>()?;
-					let end   = end[.. end.len() - 1].parse::<u16>()?;
+				let start = start.trim().parse::<u16>()?;
+				let end   = end.trim().parse::<u16>()?;
 
-					for i in start .. end + 1 {
-						result.push(i);
-					}
+				if start > end {
+					return Err(malformed());
 				}
+
+				result.extend(start ..= end);
+			}
+			else if part.is_empty() {
+				return Err(malformed());
 			}
 			else {
 				result.push(part.parse()?);
@@ -359,11 +646,14 @@ fn descriptor<R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8])
 					name @ b"exampleNumber" =>
 						descriptor.example = Some(text(reader, name)?),
 
-					name =>
-						return Err(error::Metadata::UnhandledElement {
+					name => {
+						reader.unhandled(error::Metadata::UnhandledElement {
 							phase: "descriptor".into(),
 							name:  str::from_utf8(name)?.into(),
-						}.into())
+						}.into())?;
+
+						ignore(reader, name)?;
+					}
 				}
 			}
 
@@ -374,28 +664,32 @@ fn descriptor<R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8])
 							let Attribute {key, value } = attr?;
 
 							match (str::from_utf8(key)?, str::from_utf8(&value)?) {
-								("national", value) =>
-									descriptor.possible_length = lengths(value)?,
+								("national", value) => match lengths("national", value) {
+									Ok(value) => descriptor.possible_length = value,
+									Err(err)  => reader.recover(err)?,
+								},
 
-								("localOnly", value) =>
-									descriptor.possible_local_length = lengths(value)?,
+								("localOnly", value) => match lengths("localOnly", value) {
+									Ok(value) => descriptor.possible_local_length = value,
+									Err(err)  => reader.recover(err)?,
+								},
 
 								(name, value) =>
-									return Err(error::Metadata::UnhandledAttribute {
+									reader.recover(error::Metadata::UnhandledAttribute {
 										phase: "descriptor::possibleLength".into(),
 										name:  name.into(),
 										value: value.into()
-									}.into())
+									}.into())?
 
 							}
 						}
 					}
 
 					name =>
-						return Err(error::Metadata::UnhandledElement {
+						reader.unhandled(error::Metadata::UnhandledElement {
 							phase: "descriptor".into(),
 							name:  str::from_utf8(name)?.into(),
-						}.into())
+						}.into())?,
 				}
 			}
 
@@ -418,7 +712,7 @@ fn descriptor<R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8])
 	}
 }
 
-fn formats<R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8]) -> Result<(Vec<Format>, Vec<Format>), error::LoadMetadata> {
+fn formats<R: BufRead>(reader: &mut Source<R>, meta: &Metadata, name: &[u8]) -> Result<(Vec<Format>, Vec<Format>), error::LoadMetadata> {
 	let mut buffer        = Vec::new();
 	let mut national      = Vec::new();
 	let mut international = Vec::new();
@@ -441,11 +735,14 @@ fn formats<R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8]) ->
 						}
 					}
 
-					name =>
-						return Err(error::Metadata::UnhandledElement {
+					name => {
+						reader.unhandled(error::Metadata::UnhandledElement {
 							phase: "formats".into(),
 							name:  str::from_utf8(name)?.into(),
-						}.into())
+						}.into())?;
+
+						ignore(reader, name)?;
+					}
 				}
 			}
 
@@ -468,13 +765,17 @@ fn formats<R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8]) ->
 	}
 }
 
-fn format<'a, R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8], e: &events::BytesStart<'a>) -> Result<(Format, Option<Format>), error::LoadMetadata> {
+fn format<'a, R: BufRead>(reader: &mut Source<R>, meta: &Metadata, name: &[u8], e: &events::BytesStart<'a>) -> Result<(Format, Option<Format>), error::LoadMetadata> {
 	let mut buffer = Vec::new();
 
-	let mut format        = meta.defaults.format.clone();
-	let mut international = None;
+	let mut format        = Format {
+		national_prefix_formatting_rule:          meta.national_prefix_formatting_rule.clone(),
+		national_prefix_optional_when_formatting: meta.national_prefix_optional_when_formatting,
+		domestic_carrier:                         meta.carrier_code_formatting_rule.clone(),
 
-	println!("{:?}", format);
+		.. Default::default()
+	};
+	let mut international = None;
 
 	for attr in e.attributes() {
 		let Attribute { key, value } = attr?;
@@ -487,17 +788,17 @@ fn format<'a, R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8],
 				format.national_prefix_formatting_rule = Some(value.into()),
 
 			("nationalPrefixOptionalWhenFormatting", value) =>
-				format.national_prefix_optional_when_formatting = value.parse()?,
+				format.national_prefix_optional_when_formatting = reader.parse(value)?.unwrap_or_default(),
 
 			("carrierCodeFormattingRule", value) =>
 				format.domestic_carrier = Some(value.into()),
 
 			(name, value) =>
-				return Err(error::Metadata::UnhandledAttribute {
+				reader.recover(error::Metadata::UnhandledAttribute {
 					phase: "format".into(),
 					name:  name.into(),
 					value: value.into()
-				}.into())
+				}.into())?
 		}
 	}
 
@@ -530,11 +831,14 @@ fn format<'a, R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8],
 						}
 					}
 
-					name =>
-						return Err(error::Metadata::UnhandledElement {
+					name => {
+						reader.unhandled(error::Metadata::UnhandledElement {
 							phase: "format".into(),
 							name:  str::from_utf8(name)?.into(),
-						}.into())
+						}.into())?;
+
+						ignore(reader, name)?;
+					}
 				}
 			}
 
@@ -564,7 +868,7 @@ fn format<'a, R: BufRead>(reader: &mut Reader<R>, meta: &Metadata, name: &[u8],
 	}
 }
 
-fn ignore<R: BufRead>(reader: &mut Reader<R>, name: &[u8]) -> Result<(), error::LoadMetadata> {
+fn ignore<R: BufRead>(reader: &mut Source<R>, name: &[u8]) -> Result<(), error::LoadMetadata> {
 	let mut buffer = Vec::new();
 
 	loop {
@@ -574,15 +878,56 @@ fn ignore<R: BufRead>(reader: &mut Reader<R>, name: &[u8]) -> Result<(), error::
 			Event::Empty(_) =>
 				(),
 
+			Event::Start(ref e) =>
+				ignore(reader, e.name())?,
+
+			Event::End(ref e) if e.name() == name =>
+				return Ok(()),
+
+			Event::End(ref e) =>
+				return Err(error::Metadata::MismatchedTag(
+					str::from_utf8(e.name())?.into()).into()),
+
+			Event::Eof =>
+				return Err(error::Metadata::UnexpectedEof.into()),
+
+			event =>
+				return Err(error::Metadata::UnhandledEvent {
+					phase: "ignore".into(),
+					event: format!("{:?}", event),
+				}.into())
+		}
+	}
+}
+
+fn references<R: BufRead>(reader: &mut Source<R>, name: &[u8]) -> Result<Vec<String>, error::LoadMetadata> {
+	let mut buffer = Vec::new();
+	let mut result = Vec::new();
+
+	loop {
+		match reader.read_event(&mut buffer)? {
+			Event::Text(_) |
+			Event::Comment(_) =>
+				(),
+
 			Event::Start(ref e) => {
 				match e.name() {
-					name =>
-						ignore(reader, name)?,
+					name @ b"sourceUrl" =>
+						result.push(text(reader, name)?),
+
+					name => {
+						reader.unhandled(error::Metadata::UnhandledElement {
+							phase: "references".into(),
+							name:  str::from_utf8(name)?.into(),
+						}.into())?;
+
+						ignore(reader, name)?;
+					}
 				}
 			}
 
 			Event::End(ref e) if e.name() == name =>
-				return Ok(()),
+				return Ok(result),
 
 			Event::End(ref e) =>
 				return Err(error::Metadata::MismatchedTag(
@@ -593,14 +938,14 @@ fn ignore<R: BufRead>(reader: &mut Reader<R>, name: &[u8]) -> Result<(), error::
 
 			event =>
 				return Err(error::Metadata::UnhandledEvent {
-					phase: "ignore".into(),
+					phase: "references".into(),
 					event: format!("{:?}", event),
 				}.into())
 		}
 	}
 }
 
-fn text<R: BufRead>(reader: &mut Reader<R>, name: &[u8]) -> Result<String, error::LoadMetadata> {
+fn text<R: BufRead>(reader: &mut Source<R>, name: &[u8]) -> Result<String, error::LoadMetadata> {
 	let mut buffer = Vec::new();
 	let mut result = String::new();
 
@@ -627,3 +972,183 @@ fn text<R: BufRead>(reader: &mut Reader<R>, name: &[u8]) -> Result<String, error
 		}
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use crate::metadata::loader::{self, Severity};
+	use crate::metadata::Database;
+
+	const BROKEN: &str = r#"<phoneNumberMetadata>
+  <territories>
+    <territory id="XA" countryCode="999">
+      <generalDesc>
+        <nationalNumberPattern>\d{4}</nationalNumberPattern>
+      </generalDesc>
+      <fixedLine>
+        <possibleLengths national="4-x"/>
+        <nationalNumberPattern>\d{4}</nationalNumberPattern>
+      </fixedLine>
+      <mobile>
+        <colour>green</colour>
+      </mobile>
+    </territory>
+    <territory id="XB" countryCode="nine">
+      <generalDesc>
+        <nationalNumberPattern>\d{4}</nationalNumberPattern>
+      </generalDesc>
+    </territory>
+    <shibboleth/>
+  </territories>
+  <footnotes>none</footnotes>
+</phoneNumberMetadata>"#;
+
+	#[test]
+	fn located() {
+		let error = loader::load(BROKEN.as_bytes()).unwrap_err();
+		let location = error.location().unwrap();
+
+		assert_eq!(8, location.line());
+		assert_eq!(42, location.column());
+		assert_eq!(Some("XA"), location.territory());
+		assert!(BROKEN[.. location.offset()].ends_with(r#"national="4-x"/>"#));
+		assert!(error.to_string().starts_with("line 8, column 42 in territory \"XA\": "));
+	}
+
+
+	#[test]
+	fn schema() {
+		let metadata = loader::load(r#"<phoneNumberMetadata>
+  <territories>
+    <territory id="XA" countryCode="999" nationalPrefix="0"
+               nationalPrefixFormattingRule="$NP$FG" carrierCodeFormattingRule="$NP $CC $FG"
+               nationalPrefixOptionalWhenFormatting="true" sameMobileAndFixedLinePattern="true">
+      <references>
+        <sourceUrl>http://example.com/plan.pdf</sourceUrl>
+        <sourceUrl>http://example.com/update.pdf</sourceUrl>
+      </references>
+      <availableFormats>
+        <numberFormat pattern="(\d{2})(\d{2})">
+          <format>$1 $2</format>
+        </numberFormat>
+        <numberFormat pattern="(\d)(\d{3})" nationalPrefixFormattingRule="$FG">
+          <format>$1-$2</format>
+        </numberFormat>
+      </availableFormats>
+      <generalDesc>
+        <nationalNumberPattern>\d{4}</nationalNumberPattern>
+      </generalDesc>
+      <areaCodeOptional>
+        <nationalNumberPattern>\d{4}</nationalNumberPattern>
+      </areaCodeOptional>
+      <shortCode>
+        <nationalNumberPattern>11\d{2}</nationalNumberPattern>
+      </shortCode>
+      <standardRate>
+        <nationalNumberPattern>12\d{2}</nationalNumberPattern>
+      </standardRate>
+      <carrierSpecific>
+        <nationalNumberPattern>13\d{2}</nationalNumberPattern>
+      </carrierSpecific>
+      <smsServices>
+        <nationalNumberPattern>14\d{2}</nationalNumberPattern>
+      </smsServices>
+      <expandedEmergency>
+        <nationalNumberPattern>15\d{2}</nationalNumberPattern>
+      </expandedEmergency>
+    </territory>
+  </territories>
+</phoneNumberMetadata>"#.as_bytes()).unwrap();
+
+		let meta = &metadata[0];
+		assert_eq!(vec!["http://example.com/plan.pdf", "http://example.com/update.pdf"], meta.references);
+		assert_eq!(Some("$NP$FG"), meta.national_prefix_formatting_rule.as_deref());
+		assert_eq!(Some("$NP $CC $FG"), meta.carrier_code_formatting_rule.as_deref());
+		assert!(meta.national_prefix_optional_when_formatting);
+		assert!(meta.same_mobile_and_fixed_line_pattern);
+
+		// Formats inherit the rules of the territory unless they have their own.
+		assert_eq!(Some("$NP$FG"), meta.formats[0].national_prefix_formatting_rule.as_deref());
+		assert_eq!(Some("$FG"), meta.formats[1].national_prefix_formatting_rule.as_deref());
+		assert_eq!(Some("$NP $CC $FG"), meta.formats[1].domestic_carrier.as_deref());
+
+		assert!(meta.area_code_optional.is_some());
+		assert_eq!(Some("11\\d{2}"), meta.short_code.as_ref().and_then(|d| d.national_number.as_deref()));
+		assert_eq!(Some("12\\d{2}"), meta.standard_rate.as_ref().and_then(|d| d.national_number.as_deref()));
+		assert_eq!(Some("13\\d{2}"), meta.carrier.as_ref().and_then(|d| d.national_number.as_deref()));
+		assert_eq!(Some("14\\d{2}"), meta.sms_services.as_ref().and_then(|d| d.national_number.as_deref()));
+		assert_eq!(Some("15\\d{2}"), meta.expanded_emergency.as_ref().and_then(|d| d.national_number.as_deref()));
+
+		// Everything makes it to the compiled metadata, and back.
+		let database = Database::from(vec![meta.clone()]).unwrap();
+		let compiled = database.by_id("XA").unwrap();
+		assert_eq!(2, compiled.references().len());
+		assert_eq!(Some("$NP$FG"), compiled.national_prefix_formatting_rule());
+		assert_eq!(Some("$NP $CC $FG"), compiled.carrier_code_formatting_rule());
+		assert!(compiled.is_national_prefix_optional_when_formatting());
+		assert!(compiled.is_same_mobile_and_fixed_line_pattern());
+		assert!(compiled.descriptors().sms_services().unwrap().is_match("1412"));
+		assert!(compiled.descriptors().expanded_emergency().unwrap().is_match("1512"));
+		assert!(compiled.descriptors().area_code_optional().is_some());
+
+		let json = database.to_json().unwrap();
+		assert_eq!(json, Database::from_json(&json).unwrap().to_json().unwrap());
+
+		// The short number metadata loads as a whole.
+		let metadata = loader::load(&include_bytes!("../../assets/ShortNumberMetadata.xml")[..]).unwrap();
+		let fr = metadata.iter().find(|m| m.id.as_deref() == Some("FR")).unwrap();
+		assert!(fr.short_code.is_some());
+		assert!(fr.sms_services.is_some());
+	}
+
+	#[test]
+	fn lenient() {
+		let (metadata, issues) = loader::load_lenient(BROKEN.as_bytes());
+
+		assert_eq!(2, metadata.len());
+		assert_eq!(Some(999), metadata[0].country_code);
+		assert_eq!(None, metadata[1].country_code);
+		assert_eq!(Some("XB".into()), metadata[1].id);
+
+		let summary = issues.iter().map(|issue| {
+			let location = issue.error.location().unwrap();
+			(issue.severity, location.line(), location.territory().map(String::from))
+		}).collect::<Vec<_>>();
+
+		assert_eq!(vec![
+			(Severity::Error, 8, Some("XA".into())),
+			(Severity::Warning, 12, Some("XA".into())),
+			(Severity::Error, 15, Some("XB".into())),
+			(Severity::Warning, 20, None),
+			(Severity::Warning, 22, None),
+		], summary);
+
+		// Malformed lengths are reported instead of panicking, and attributes
+		// before the ID are located in the territory.
+		let xml = r#"<phoneNumberMetadata><territories>
+<territory countryCode="38x" id="XK">
+<generalDesc><nationalNumberPattern>\d{4}</nationalNumberPattern></generalDesc>
+<fixedLine><possibleLengths national=""/></fixedLine>
+<mobile><possibleLengths national="[4-]"/></mobile>
+</territory>
+</territories></phoneNumberMetadata>"#;
+
+		let (_, issues) = loader::load_lenient(xml.as_bytes());
+		let summary = issues.iter().map(|issue| {
+			let location = issue.error.location().unwrap();
+			(issue.severity, location.line(), location.territory().map(String::from))
+		}).collect::<Vec<_>>();
+
+		assert_eq!(vec![
+			(Severity::Error, 2, Some("XK".into())),
+			(Severity::Error, 4, Some("XK".into())),
+			(Severity::Error, 5, Some("XK".into())),
+		], summary);
+
+		assert!(loader::load(xml.replace("38x", "383").as_bytes()).is_err());
+
+		// Well formed metadata has nothing to report.
+		let (metadata, issues) = loader::load_lenient(&include_bytes!("../../assets/PhoneNumberMetadata.xml")[..]);
+		assert!(metadata.len() > 200);
+		assert!(issues.is_empty());
+	}
+}
@@ -0,0 +1,438 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::hash_map::Entry;
+
+use fnv::FnvHashMap;
+use regex_syntax::ParserBuilder;
+use regex_syntax::hir::{Hir, HirKind, Class};
+
+/// Limit on the states of the automata, patterns that would need more are left
+/// to the regex engine.
+const STATES: usize = 16 * 1024;
+
+/// The state with no way out.
+const DEAD: u32 = 0;
+
+/// A deterministic automaton over ASCII digits matching several patterns at
+/// once.
+///
+/// Patterns are written in the subset of regex syntax metadata uses: digits,
+/// digit classes, groups, alternations and repetitions. It tells which of the
+/// patterns match a prefix of a number, like `Descriptor::is_match` does with
+/// a regex.
+#[derive(Clone, Debug)]
+pub(crate) struct Matcher {
+	next:   Vec<[u32; 10]>,
+	accept: Vec<u32>,
+}
+
+impl Matcher {
+	/// Compile the given patterns, pattern `i` sets bit `i` in the result of
+	/// `prefixes`, missing patterns never match.
+	///
+	/// Returns `None` if any pattern uses syntax the automaton can't represent.
+	pub fn new(patterns: &[Option<&str>]) -> Option<Self> {
+		debug_assert!(patterns.len() <= 32);
+
+		let mut nfa   = Nfa::default();
+		let     start = nfa.state();
+
+		for (index, pattern) in patterns.iter().enumerate() {
+			if let Some(pattern) = pattern {
+				let hir = ParserBuilder::new().ignore_whitespace(true).build()
+					.parse(pattern).ok()?;
+
+				let end = nfa.compile(&hir, start)?;
+				nfa.accept[end] |= 1 << index;
+			}
+		}
+
+		nfa.determinize(start)
+	}
+
+	/// Get the mask of the patterns matching a prefix of the value.
+	///
+	/// Returns `None` if the value isn't made of ASCII digits only, since the
+	/// patterns could match other characters there.
+	pub fn prefixes(&self, value: &str) -> Option<u32> {
+		if !value.bytes().all(|b| b.is_ascii_digit()) {
+			return None;
+		}
+
+		let mut state = 1;
+		let mut mask  = self.accept[state];
+
+		for digit in value.bytes() {
+			state = self.next[state][(digit - b'0') as usize] as usize;
+
+			if state == DEAD as usize {
+				break;
+			}
+
+			mask |= self.accept[state];
+		}
+
+		Some(mask)
+	}
+
+	/// Get the mask of the patterns matching the whole value.
+	///
+	/// Returns `None` if the value isn't made of ASCII digits only.
+	pub fn matches(&self, value: &str) -> Option<u32> {
+		if !value.bytes().all(|b| b.is_ascii_digit()) {
+			return None;
+		}
+
+		let state = value.bytes().try_fold(1, |state, digit| {
+			match self.next[state][(digit - b'0') as usize] {
+				DEAD => None,
+				next => Some(next as usize),
+			}
+		});
+
+		Some(state.map(|s| self.accept[s]).unwrap_or(0))
+	}
+
+	/// Get the lengths, up to the given maximum, of the numbers matched whole
+	/// by any of the patterns in the mask.
+	pub fn lengths(&self, mask: u32, max: usize) -> Vec<u16> {
+		let mut lengths = Vec::new();
+		let mut current = vec![false; self.next.len()];
+		current[1] = true;
+
+		for length in 0 ..= max {
+			if current.iter().enumerate().any(|(s, &on)| on && self.accept[s] & mask != 0) {
+				lengths.push(length as u16);
+			}
+
+			let mut next = vec![false; self.next.len()];
+			for state in (0 .. current.len()).filter(|&s| current[s]) {
+				for &target in &self.next[state] {
+					next[target as usize] = true;
+				}
+			}
+
+			next[DEAD as usize] = false;
+			current = next;
+		}
+
+		lengths
+	}
+
+	/// Find the shortest number, up to the given length, for which `goal`
+	/// holds given the mask of the patterns matching any of its prefixes and
+	/// the mask of the patterns matching it whole.
+	pub fn search<F: Fn(u32, u32) -> bool>(&self, max: usize, goal: F) -> Option<String> {
+		// Nodes of the search, as state, prefixes mask, parent and digit.
+		let mut nodes = vec![(1u32, self.accept[1], usize::MAX, 0u8)];
+		let mut known = FnvHashMap::default();
+		known.insert((1u32, self.accept[1]), 0usize);
+
+		let mut start = 0;
+		for _ in 0 ..= max {
+			let end = nodes.len();
+
+			for index in start .. end {
+				let (state, seen, _, _) = nodes[index];
+
+				if goal(seen, self.accept[state as usize]) {
+					let mut digits = Vec::new();
+					let mut index  = index;
+
+					while nodes[index].2 != usize::MAX {
+						digits.push(b'0' + nodes[index].3);
+						index = nodes[index].2;
+					}
+
+					digits.reverse();
+					return String::from_utf8(digits).ok();
+				}
+
+				for digit in 0 .. 10u8 {
+					let next = self.next[state as usize][digit as usize];
+					if next == DEAD {
+						continue;
+					}
+
+					let seen = seen | self.accept[next as usize];
+					if let Entry::Vacant(entry) = known.entry((next, seen)) {
+						entry.insert(nodes.len());
+						nodes.push((next, seen, index, digit));
+					}
+				}
+			}
+
+			start = end;
+		}
+
+		None
+	}
+}
+
+/// A Thompson automaton over ASCII digits.
+#[derive(Default)]
+struct Nfa {
+	empty:  Vec<Vec<usize>>,
+	digits: Vec<Vec<(u16, usize)>>,
+	accept: Vec<u32>,
+}
+
+impl Nfa {
+	fn state(&mut self) -> usize {
+		self.empty.push(Vec::new());
+		self.digits.push(Vec::new());
+		self.accept.push(0);
+
+		self.empty.len() - 1
+	}
+
+	/// Compile the expression starting at the given state, returning the state
+	/// it ends at.
+	fn compile(&mut self, hir: &Hir, from: usize) -> Option<usize> {
+		if self.empty.len() > STATES {
+			return None;
+		}
+
+		match hir.kind() {
+			HirKind::Empty =>
+				Some(from),
+
+			HirKind::Literal(literal) => {
+				let mut current = from;
+
+				for &byte in literal.0.iter() {
+					if !byte.is_ascii_digit() {
+						return None;
+					}
+
+					let next = self.state();
+					self.digits[current].push((1 << (byte - b'0'), next));
+					current = next;
+				}
+
+				Some(current)
+			}
+
+			// Only the ASCII digits of a class matter, numbers with anything else
+			// are left to the regex engine.
+			HirKind::Class(class) => {
+				let mut mask = 0u16;
+
+				for digit in 0 .. 10u8 {
+					let contained = match class {
+						Class::Unicode(class) => class.ranges().iter()
+							.any(|r| r.start() <= (b'0' + digit) as char && (b'0' + digit) as char <= r.end()),
+
+						Class::Bytes(class) => class.ranges().iter()
+							.any(|r| r.start() <= b'0' + digit && b'0' + digit <= r.end()),
+					};
+
+					if contained {
+						mask |= 1 << digit;
+					}
+				}
+
+				let next = self.state();
+				if mask != 0 {
+					self.digits[from].push((mask, next));
+				}
+
+				Some(next)
+			}
+
+			HirKind::Look(_) =>
+				None,
+
+			HirKind::Capture(capture) =>
+				self.compile(&capture.sub, from),
+
+			HirKind::Concat(hirs) =>
+				hirs.iter().try_fold(from, |current, hir| self.compile(hir, current)),
+
+			HirKind::Alternation(hirs) => {
+				let start = self.state();
+				let end   = self.state();
+				self.empty[from].push(start);
+
+				for hir in hirs {
+					let branch = self.compile(hir, start)?;
+					self.empty[branch].push(end);
+				}
+
+				Some(end)
+			}
+
+			HirKind::Repetition(repetition) => {
+				let mut current = from;
+
+				for _ in 0 .. repetition.min {
+					let next = self.state();
+					self.empty[current].push(next);
+					current = self.compile(&repetition.sub, next)?;
+				}
+
+				if let Some(max) = repetition.max {
+					for _ in repetition.min .. max {
+						let start = self.state();
+						let end   = self.state();
+						self.empty[current].push(start);
+						self.empty[current].push(end);
+
+						let body = self.compile(&repetition.sub, start)?;
+						self.empty[body].push(end);
+						current = end;
+					}
+				}
+				else {
+					let start = self.state();
+					self.empty[current].push(start);
+
+					let body = self.compile(&repetition.sub, start)?;
+					self.empty[body].push(start);
+					current = start;
+				}
+
+				Some(current)
+			}
+		}
+	}
+
+	/// Add the states reachable without consuming a digit, `seen` marks the
+	/// states already in the set and is cleared on return.
+	fn closure(&self, states: &mut Vec<usize>, seen: &mut [bool]) {
+		for &state in states.iter() {
+			seen[state] = true;
+		}
+
+		let mut index = 0;
+		while index < states.len() {
+			for &next in &self.empty[states[index]] {
+				if !seen[next] {
+					seen[next] = true;
+					states.push(next);
+				}
+			}
+
+			index += 1;
+		}
+
+		for &state in states.iter() {
+			seen[state] = false;
+		}
+
+		states.sort_unstable();
+	}
+
+	/// Turn the automaton into a deterministic one by subset construction.
+	fn determinize(&self, start: usize) -> Option<Matcher> {
+		let mut matcher = Matcher {
+			next:   vec![[DEAD; 10]],
+			accept: vec![0],
+		};
+
+		let mut seen    = vec![false; self.empty.len()];
+		let mut initial = vec![start];
+		self.closure(&mut initial, &mut seen);
+
+		let mut known = FnvHashMap::default();
+		known.insert(Vec::new(), DEAD);
+		known.insert(initial.clone(), 1);
+
+		let mut pending = vec![initial];
+		matcher.next.push([DEAD; 10]);
+		matcher.accept.push(0);
+
+		while let Some(states) = pending.pop() {
+			let id = known[&states] as usize;
+			matcher.accept[id] = states.iter().fold(0, |mask, &s| mask | self.accept[s]);
+
+			for digit in 0 .. 10 {
+				let mut next = Vec::new();
+
+				for &state in &states {
+					for &(mask, target) in &self.digits[state] {
+						if mask & (1 << digit) != 0 && !seen[target] {
+							seen[target] = true;
+							next.push(target);
+						}
+					}
+				}
+
+				self.closure(&mut next, &mut seen);
+
+				let target = match known.entry(next) {
+					Entry::Occupied(entry) =>
+						*entry.get(),
+
+					Entry::Vacant(entry) => {
+						if matcher.next.len() >= STATES {
+							return None;
+						}
+
+						let target = matcher.next.len() as u32;
+						pending.push(entry.key().clone());
+						entry.insert(target);
+
+						matcher.next.push([DEAD; 10]);
+						matcher.accept.push(0);
+
+						target
+					}
+				};
+
+				matcher.next[id][digit] = target;
+			}
+		}
+
+		Some(matcher)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::metadata::matcher::Matcher;
+
+	#[test]
+	fn prefixes() {
+		let matcher = Matcher::new(&[
+			Some(r"(?:1[0-3]|2\d)\d{3}"),
+			None,
+			Some(r"
+				800 \d{4,5} |
+				2 (?: 1 | 99 ) \d?
+			"),
+		]).unwrap();
+
+		assert_eq!(Some(0b001), matcher.prefixes("12345"));
+		assert_eq!(Some(0b001), matcher.prefixes("1234567"));
+		assert_eq!(Some(0b000), matcher.prefixes("1234"));
+		assert_eq!(Some(0b000), matcher.prefixes("14000"));
+		assert_eq!(Some(0b101), matcher.prefixes("21000"));
+		assert_eq!(Some(0b100), matcher.prefixes("8001234"));
+		assert_eq!(Some(0b100), matcher.prefixes("299"));
+		assert_eq!(None, matcher.prefixes("2 1"));
+
+		assert_eq!(Some(0b001), matcher.matches("12345"));
+		assert_eq!(Some(0b000), matcher.matches("1234567"));
+		assert_eq!(Some(0b100), matcher.matches("21"));
+		assert_eq!(vec![2, 3, 4, 5, 7, 8], matcher.lengths(0b101, 10));
+		assert_eq!(Some("21".into()), matcher.search(10, |_, whole| whole & 0b100 != 0));
+		assert_eq!(Some("21000".into()), matcher.search(10, |seen, _| seen == 0b101));
+		assert_eq!(None, matcher.search(10, |_, whole| whole == 0b101));
+
+		assert!(Matcher::new(&[Some(r"^12")]).is_none());
+		assert!(Matcher::new(&[Some(r"1a")]).is_none());
+	}
+}
@@ -12,12 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use regex_cache::CachedRegex;
+use regex::Regex;
 use crate::{
-	metadata::{Format, Descriptor},
+	metadata::{Format, Descriptor, matcher::Matcher},
 	phone_number::Type
 };
 
+/// The descriptors `Descriptors::classifier` matches, in the order of its bits,
+/// `Type::Unknown` stands for the general descriptor.
+pub(crate) const CLASSIFIED: [Type; 11] = [
+	Type::Unknown, Type::PremiumRate, Type::TollFree, Type::SharedCost,
+	Type::Voip, Type::PersonalNumber, Type::Pager, Type::Uan, Type::Voicemail,
+	Type::FixedLine, Type::Mobile,
+];
+
 /// Phone number metadata.
 #[derive(Clone, Debug)]
 pub struct Metadata {
@@ -25,18 +33,25 @@ pub struct Metadata {
 	pub(crate) id: String,
 	pub(crate) country_code: u16,
 
-	pub(crate) international_prefix: Option<CachedRegex>,
+	pub(crate) international_prefix: Option<Regex>,
 	pub(crate) preferred_international_prefix: Option<String>,
 	pub(crate) national_prefix: Option<String>,
 	pub(crate) preferred_extension_prefix: Option<String>,
-	pub(crate) national_prefix_for_parsing: Option<CachedRegex>,
+	pub(crate) national_prefix_for_parsing: Option<Regex>,
 	pub(crate) national_prefix_transform_rule: Option<String>,
 
 	pub(crate) formats: Vec<Format>,
 	pub(crate) international_formats: Vec<Format>,
 	pub(crate) main_country_for_code: bool,
-	pub(crate) leading_digits: Option<CachedRegex>,
+	pub(crate) leading_digits: Option<Regex>,
 	pub(crate) mobile_number_portable: bool,
+	pub(crate) same_mobile_and_fixed_line_pattern: bool,
+
+	pub(crate) national_prefix_formatting_rule: Option<String>,
+	pub(crate) national_prefix_optional_when_formatting: bool,
+	pub(crate) carrier_code_formatting_rule: Option<String>,
+
+	pub(crate) references: Vec<String>,
 }
 
 /// Descriptors for various types of phone number.
@@ -58,6 +73,13 @@ pub struct Descriptors {
 	pub(crate) standard_rate:    Option<Descriptor>,
 	pub(crate) carrier:          Option<Descriptor>,
 	pub(crate) no_international: Option<Descriptor>,
+
+	pub(crate) sms_services:       Option<Descriptor>,
+	pub(crate) expanded_emergency: Option<Descriptor>,
+	pub(crate) area_code_optional: Option<Descriptor>,
+
+	/// Matcher for all the descriptors in `CLASSIFIED` at once.
+	pub(crate) classifier: Option<Matcher>,
 }
 
 impl Metadata {
@@ -87,7 +109,7 @@ impl Metadata {
 	/// by the country code for country B. Note that some countries may have more
 	/// than one international prefix, and for those cases, a regular expression
 	/// matching the international prefixes will be stored in this field.
-	pub fn international_prefix(&self) -> Option<&CachedRegex> {
+	pub fn international_prefix(&self) -> Option<&Regex> {
 		self.international_prefix.as_ref()
 	}
 
@@ -96,7 +118,7 @@ impl Metadata {
 	/// is not present, and multiple international prefixes are present, then "+"
 	/// will be used instead.
 	pub fn preferred_international_prefix(&self) -> Option<&str> {
-		self.preferred_extension_prefix.as_ref().map(AsRef::as_ref)
+		self.preferred_international_prefix.as_ref().map(AsRef::as_ref)
 	}
 
 	/// The national prefix of country A is the number that needs to be dialled
@@ -130,7 +152,7 @@ impl Metadata {
 	///
 	/// When it is missing from the XML file, this field inherits the value of
 	/// national prefix, if that is present.
-	pub fn national_prefix_for_parsing(&self) -> Option<&CachedRegex> {
+	pub fn national_prefix_for_parsing(&self) -> Option<&Regex> {
 		self.national_prefix_for_parsing.as_ref()
 	}
 
@@ -209,7 +231,7 @@ impl Metadata {
 	/// It is used merely as a short-cut for working out which region a number
 	/// comes from in the case that there is only one, so leading digit prefixes
 	/// should not overlap.
-	pub fn leading_digits(&self) -> Option<&CachedRegex> {
+	pub fn leading_digits(&self) -> Option<&Regex> {
 		self.leading_digits.as_ref()
 	}
 
@@ -220,6 +242,36 @@ impl Metadata {
 	pub fn is_mobile_number_portable(&self) -> bool {
 		self.mobile_number_portable
 	}
+
+	/// This field is set when the fixed-line and mobile patterns of the country
+	/// are the same, so the type of a number can't be told apart between the
+	/// two.
+	pub fn is_same_mobile_and_fixed_line_pattern(&self) -> bool {
+		self.same_mobile_and_fixed_line_pattern
+	}
+
+	/// The national prefix formatting rule of the territory, which the formats
+	/// without their own rule inherit.
+	pub fn national_prefix_formatting_rule(&self) -> Option<&str> {
+		self.national_prefix_formatting_rule.as_ref().map(AsRef::as_ref)
+	}
+
+	/// Whether the national prefix is optional when formatting, the default for
+	/// the formats of the territory.
+	pub fn is_national_prefix_optional_when_formatting(&self) -> bool {
+		self.national_prefix_optional_when_formatting
+	}
+
+	/// The carrier code formatting rule of the territory, which the formats
+	/// without their own rule inherit.
+	pub fn carrier_code_formatting_rule(&self) -> Option<&str> {
+		self.carrier_code_formatting_rule.as_ref().map(AsRef::as_ref)
+	}
+
+	/// The source URLs the metadata of the territory was taken from.
+	pub fn references(&self) -> &[String] {
+		&self.references
+	}
 }
 
 impl Descriptors {
@@ -340,4 +392,17 @@ impl Descriptors {
 	pub fn no_international(&self) -> Option<&Descriptor> {
 		self.no_international.as_ref()
 	}
+
+	pub fn sms_services(&self) -> Option<&Descriptor> {
+		self.sms_services.as_ref()
+	}
+
+	pub fn expanded_emergency(&self) -> Option<&Descriptor> {
+		self.expanded_emergency.as_ref()
+	}
+
+	/// The numbers which can be dialled without their area code.
+	pub fn area_code_optional(&self) -> Option<&Descriptor> {
+		self.area_code_optional.as_ref()
+	}
 }
@@ -12,17 +12,46 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod matcher;
+
 mod format;
 pub use self::format::Format;
 
 mod descriptor;
 pub use self::descriptor::Descriptor;
 
+#[allow(clippy::module_inception)]
 mod metadata;
 pub use self::metadata::{Metadata, Descriptors};
+pub(crate) use self::metadata::CLASSIFIED;
+
+/// Static tables generated by the build script from the bundled metadata,
+/// `REGIONS` is sorted by ID and `BY_CODE` maps every country code to the
+/// indices of its regions, main country first.
+mod generated;
 
 mod database;
 pub use self::database::{Database, DEFAULT as DATABASE};
+#[cfg(test)]
+pub(crate) use self::database::LOADED;
+
+mod builder;
+pub use self::builder::DatabaseBuilder;
+
+mod version;
+pub use self::version::Version;
+
+mod private;
+pub use self::private::PrivatePlan;
+
+mod lint;
+pub use self::lint::{lint, lint_loaded, Diagnostic, Lint};
 
 /// XML loading helpers.
 pub mod loader;
+
+/// Exporters of the metadata to the layouts of other libraries.
+pub mod export;
+
+/// Behavioural differences between two databases.
+pub mod diff;
@@ -0,0 +1,172 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use regex::{Regex, RegexBuilder};
+
+use crate::error;
+use crate::metadata::{Metadata, loader};
+use crate::metadata::database::compile;
+
+/// A private numbering plan, like the extensions and site codes of a PBX.
+///
+/// The plan is a pseudo-territory without a country code: its general
+/// descriptor tells which numbers belong to it, the other descriptors give
+/// their type and its formats how they are written. Numbers starting with the
+/// outside line prefix are dialled out of the plan and are public numbers.
+#[derive(Clone, Debug)]
+pub struct PrivatePlan {
+	pub(crate) meta:         Metadata,
+	pub(crate) pattern:      Regex,
+	pub(crate) outside_line: Option<String>,
+}
+
+impl PrivatePlan {
+	/// Compile a plan from its metadata, the country code is ignored.
+	pub(crate) fn new(mut meta: loader::Metadata, outside_line: Option<String>) -> Result<Self, error::LoadMetadata> {
+		meta.country_code          = Some(0);
+		meta.main_country_for_code = false;
+
+		let meta    = compile(meta)?;
+		let pattern = RegexBuilder::new(&format!("^(?:{})$", meta.descriptors.general.national_number.as_str()))
+			.ignore_whitespace(true).build()?;
+
+		Ok(PrivatePlan { meta, pattern, outside_line })
+	}
+
+	/// The ID of the plan, which private numbers refer to.
+	pub fn id(&self) -> &str {
+		&self.meta.id
+	}
+
+	/// The metadata of the plan.
+	pub fn metadata(&self) -> &Metadata {
+		&self.meta
+	}
+
+	/// The prefix to dial to get an outside line, if any.
+	pub fn outside_line(&self) -> Option<&str> {
+		self.outside_line.as_ref().map(AsRef::as_ref)
+	}
+
+	/// Check if the given digits are a whole number of the plan.
+	pub fn is_match(&self, value: &str) -> bool {
+		self.meta.descriptors.general.is_possible_length(value) &&
+			self.pattern.is_match(value)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::metadata::{DatabaseBuilder, loader};
+	use crate::trace::Decision;
+	use crate::formatter::Mode;
+	use crate::parser;
+	use crate::country;
+	use crate::error;
+
+	#[test]
+	fn private() {
+		let plan = loader::Metadata {
+			id: Some("ACME".into()),
+
+			general: Some(loader::Descriptor {
+				national_number: Some("[1-7]\\d{3,4}|8\\d{7}".into()),
+				possible_length: vec![4, 5, 8],
+				.. Default::default()
+			}),
+
+			fixed_line: Some(loader::Descriptor {
+				national_number: Some("[1-7]\\d{3,4}|8\\d{7}".into()),
+				.. Default::default()
+			}),
+
+			formats: vec![loader::Format {
+				pattern: Some("(8)(\\d{3})(\\d{4})".into()),
+				format:  Some("$1-$2-$3".into()),
+				.. Default::default()
+			}],
+
+			.. Default::default()
+		};
+
+		let database = DatabaseBuilder::new()
+			.private_plan(plan.clone(), Some("9"))
+			.build().unwrap();
+
+		let number = parser::parse_with(&database, Some(country::US), "8-123-4567").unwrap();
+		assert_eq!(Some("ACME"), number.private_plan());
+		assert_eq!(81234567, number.national().value());
+		assert!(number.is_valid_with(&database));
+		assert!(!number.is_valid());
+		assert_eq!("8-123-4567", number.format_with(&database).mode(Mode::National).to_string());
+		assert_eq!("8-123-4567", number.format_with(&database).mode(Mode::International).to_string());
+		assert_eq!("tel:8-123-4567;phone-context=ACME", number.format_with(&database).mode(Mode::Rfc3966).to_string());
+		assert_eq!("81234567", number.format_with(&database).mode(Mode::E164).to_string());
+		assert_eq!("81234567", number.to_string());
+
+		let number = parser::parse_with(&database, None, "4567").unwrap();
+		assert!(number.is_private());
+		assert_eq!("4567", number.format_with(&database).mode(Mode::National).to_string());
+		assert_eq!("4567", number.to_string());
+
+		// Dialling out.
+		let (number, trace) = parser::parse_explained_with(&database, Some(country::US), "9 650 253 0000").unwrap();
+		assert!(!number.is_private());
+		assert_eq!(number, parser::parse(Some(country::US), "650 253 0000").unwrap());
+		assert_eq!(Decision::OutsideLine { plan: "ACME".into(), prefix: "9".into() }, trace[0]);
+
+		let error = parser::parse_with(&database, Some(country::US), "9 011 9991 123456").unwrap_err();
+		assert_eq!(6 .. 9, error.span());
+
+		// Public numbers and short codes starting with the prefix keep it.
+		let number = parser::parse_with(&database, Some(country::US), "972 555 1234").unwrap();
+		assert_eq!(9725551234, number.national().value());
+		assert!(number.is_valid_with(&database));
+
+		assert_eq!(parser::parse(Some(country::US), "911").ok(),
+			parser::parse_with(&database, Some(country::US), "911").ok());
+
+		// Public numbers are left alone.
+		let number = parser::parse_with(&database, None, "+1 650 253 0000").unwrap();
+		assert!(!number.is_private());
+		assert!(number.is_valid_with(&database));
+
+		// Plans are kept by builders on top of the database.
+		let database = DatabaseBuilder::with_database(&database).build().unwrap();
+		assert!(database.private_plan("ACME").is_some());
+
+		assert!(DatabaseBuilder::with_database(&database)
+			.private_plan(plan, None::<String>)
+			.build().is_err());
+
+		// Numbers of the plan too long to be held are errors, not public numbers.
+		let database = DatabaseBuilder::new()
+			.private_plan(loader::Metadata {
+				id: Some("LONG".into()),
+
+				general: Some(loader::Descriptor {
+					national_number: Some("7\\d{24}".into()),
+					possible_length: vec![25],
+					.. Default::default()
+				}),
+
+				.. Default::default()
+			}, None::<String>)
+			.build().unwrap();
+
+		let error = parser::parse_with(&database, None, "ext 7000000000000000000000000").unwrap_err();
+		assert_eq!(error::Stage::Length, error.stage());
+		assert_eq!(4 .. 29, error.span());
+	}
+}
@@ -0,0 +1,151 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::io;
+use std::hash::Hasher;
+
+use fnv::FnvHasher;
+use serde::Serialize;
+
+use crate::error;
+
+/// The version of the metadata in a `Database`.
+///
+/// The name is the libphonenumber release the metadata comes from, when
+/// known. The hash identifies the exact metadata: it's the FNV-1a hash of its
+/// JSON representation, whatever the metadata was made from, so databases
+/// with changes on top of the same release differ.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
+pub struct Version {
+	pub(crate) name: Option<String>,
+	pub(crate) hash: u64,
+}
+
+impl Version {
+	pub(crate) fn new(name: Option<String>, hash: u64) -> Self {
+		Version { name, hash }
+	}
+
+	/// The version of the JSON representation of the value with the given
+	/// name, the JSON is hashed as it's written.
+	pub(crate) fn of<T: Serialize>(name: Option<String>, value: &T) -> Self {
+		struct Hashing(FnvHasher);
+
+		impl io::Write for Hashing {
+			fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+				self.0.write(buffer);
+				Ok(buffer.len())
+			}
+
+			fn flush(&mut self) -> io::Result<()> {
+				Ok(())
+			}
+		}
+
+		let mut hasher = Hashing(FnvHasher::default());
+		serde_json::to_writer(&mut hasher, value)
+			.expect("metadata is always representable as JSON");
+
+		Version::new(name, hasher.0.finish())
+	}
+
+	/// The libphonenumber release, if known.
+	pub fn name(&self) -> Option<&str> {
+		self.name.as_ref().map(AsRef::as_ref)
+	}
+
+	/// The hash of the metadata.
+	pub fn hash(&self) -> u64 {
+		self.hash
+	}
+
+	/// Fail unless the version is the expected one.
+	pub fn check(&self, expected: &Version) -> Result<(), error::LoadMetadata> {
+		if self == expected {
+			Ok(())
+		}
+		else {
+			Err(error::LoadMetadata::VersionMismatch {
+				expected: expected.to_string(),
+				found:    self.to_string(),
+			})
+		}
+	}
+}
+
+impl fmt::Display for Version {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} ({:016x})", self.name().unwrap_or("unknown"), self.hash)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::metadata::{Database, DatabaseBuilder, DATABASE, LOADED, loader};
+
+	const OVERLAY: &str = r#"<phoneNumberMetadata version="VERSION">
+  <territories>
+    <territory id="XK" countryCode="383">
+      <generalDesc>
+        <nationalNumberPattern>\d{8}</nationalNumberPattern>
+      </generalDesc>
+    </territory>
+  </territories>
+</phoneNumberMetadata>"#;
+
+	#[test]
+	fn version() {
+		let version = DATABASE.version();
+		assert_eq!(Some("8.12.9"), version.name());
+		assert!(version.to_string().starts_with("8.12.9 ("));
+
+		// The same metadata gives the same version, whatever it's made from.
+		assert_eq!(version.hash(), LOADED.version().hash());
+		assert_eq!(None, LOADED.version().name());
+		assert_eq!(Some("8.12.9"), LOADED.clone().with_version("8.12.9").version().name());
+
+		let xml = OVERLAY.replace("VERSION", "8.12.9");
+		assert_eq!(
+			Database::parse(&xml).unwrap().version().hash(),
+			Database::from(loader::load(xml.as_bytes()).unwrap()).unwrap().version().hash());
+
+		// Changes keep the name but not the hash.
+		let built = DatabaseBuilder::new().build().unwrap();
+		assert_eq!(version, built.version());
+
+		let built = DatabaseBuilder::new()
+			.parse_overlay(OVERLAY.replace("VERSION", "8.12.9"))
+			.build().unwrap();
+		assert_eq!(Some("8.12.9"), built.version().name());
+		assert_ne!(version.hash(), built.version().hash());
+
+		// Overlays for another release are refused.
+		assert!(DatabaseBuilder::new()
+			.parse_overlay(OVERLAY.replace("VERSION", "8.10.0"))
+			.build().is_err());
+
+		// So are artefacts of other metadata.
+		let small = Database::parse(&xml).unwrap();
+		let json  = small.to_json().unwrap();
+		assert_eq!(small.version(), Database::from_json_checked(&json, small.version()).unwrap().version());
+		assert!(Database::from_json_checked(&json, version).is_err());
+
+		// Artefacts whose content was changed are refused, whatever they claim.
+		let tampered = json.replace(r#""country_code": 383"#, r#""country_code": 384"#);
+		assert_ne!(json, tampered);
+		assert!(Database::from_json_checked(&tampered, small.version()).is_err());
+		assert!(Database::from_json(&tampered).is_err());
+	}
+}
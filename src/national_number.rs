@@ -13,6 +13,8 @@
 // limitations under the License.
 
 use std::fmt;
+use std::ops::Deref;
+use std::str;
 
 /// The national number part of a phone number.
 #[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Debug)]
@@ -48,11 +50,59 @@ impl NationalNumber {
 	pub fn zeros(&self) -> u8 {
 		self.zeros
 	}
+
+	/// Write the digits of the number, including leading zeroes, to a buffer on
+	/// the stack.
+	pub(crate) fn digits(&self) -> Digits {
+		let mut digits = Digits {
+			buffer: [b'0'; DIGITS],
+			length: 0,
+		};
+
+		// Write the value backwards from the end, then move it after the zeros.
+		let mut value = self.value;
+		let mut start = DIGITS;
+		loop {
+			start -= 1;
+			digits.buffer[start] = b'0' + (value % 10) as u8;
+			value /= 10;
+
+			if value == 0 {
+				break;
+			}
+		}
+
+		let zeros  = (self.zeros as usize).min(start);
+		let length = DIGITS - start;
+		digits.buffer.copy_within(start .., zeros);
+		digits.buffer[.. zeros].fill(b'0');
+		digits.length = (zeros + length) as u8;
+
+		digits
+	}
 }
 
-impl Into<u64> for NationalNumber {
-	fn into(self) -> u64 {
-		self.value
+/// Room for the 20 digits of a `u64` and any reasonable amount of zeros.
+const DIGITS: usize = 48;
+
+/// The digits of a `NationalNumber`, kept on the stack.
+#[derive(Copy, Clone)]
+pub(crate) struct Digits {
+	buffer: [u8; DIGITS],
+	length: u8,
+}
+
+impl Deref for Digits {
+	type Target = str;
+
+	fn deref(&self) -> &str {
+		str::from_utf8(&self.buffer[.. self.length as usize]).unwrap()
+	}
+}
+
+impl From<NationalNumber> for u64 {
+	fn from(number: NationalNumber) -> u64 {
+		number.value
 	}
 }
 
@@ -65,3 +115,21 @@ impl fmt::Display for NationalNumber {
 		write!(f, "{}", self.value)
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use crate::national_number::NationalNumber;
+
+	#[test]
+	fn digits() {
+		let number = NationalNumber { value: 612345678, zeros: 1 };
+		assert_eq!("0612345678", &*number.digits());
+		assert_eq!(number.to_string(), &*number.digits());
+
+		let number = NationalNumber { value: 0, zeros: 2 };
+		assert_eq!("000", &*number.digits());
+
+		let number = NationalNumber { value: u64::MAX, zeros: 0 };
+		assert_eq!(u64::MAX.to_string(), &*number.digits());
+	}
+}
@@ -0,0 +1,132 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+
+use crate::consts;
+use crate::parser::helper::{self, AsCharExt};
+use crate::validator;
+
+/// Normalize a string to only contain ASCII digits, converting any Unicode
+/// decimal digit to its ASCII counterpart and dropping everything else.
+///
+/// The input is borrowed back if it is already normalized.
+pub fn normalize_digits_only(value: &str) -> Cow<'_, str> {
+	filter_map(value, |c| c.as_dec_digit())
+}
+
+/// Normalize a string to only contain the characters that are meaningful when
+/// dialling, i.e. ASCII digits, "+", "*" and "#", converting any Unicode
+/// decimal digit or plus sign to its ASCII counterpart and dropping everything
+/// else.
+///
+/// The input is borrowed back if it is already normalized.
+pub fn normalize_diallable_chars_only(value: &str) -> Cow<'_, str> {
+	filter_map(value, |c| {
+		if c.is_plus() {
+			Some(consts::PLUS_SIGN)
+		}
+		else {
+			c.as_dec_digit().or_else(|| consts::DIALLABLE_CHAR_MAPPINGS.get(&c).cloned())
+		}
+	})
+}
+
+/// Extract the part of the string that could be a phone number, stripping any
+/// leading characters that cannot start a phone number and any trailing
+/// characters that cannot end one, see `parse` for details.
+///
+/// An empty string is returned if no possible number is present.
+pub fn extract_possible_number(value: &str) -> &str {
+	helper::extract(value).map(|(_, number)| number).unwrap_or("")
+}
+
+/// Check if the string is a viable phone number written with at least three
+/// letters, like "1-800-FLOWERS", any extension is ignored.
+pub fn is_alpha_number(value: &str) -> bool {
+	let number = extract_possible_number(value);
+
+	if !validator::is_viable(number) {
+		return false;
+	}
+
+	let number = consts::EXTN_PATTERN.find(number)
+		.map(|m| &number[.. m.start()])
+		.unwrap_or(number);
+
+	consts::VALID_ALPHA_PHONE.is_match(number)
+}
+
+/// Map every character of the string, dropping the ones that map to nothing,
+/// without allocating if every character maps to itself.
+fn filter_map<F: Fn(char) -> Option<char>>(value: &str, map: F) -> Cow<'_, str> {
+	let index = value.char_indices()
+		.find(|&(_, c)| map(c) != Some(c))
+		.map(|(index, _)| index);
+
+	if let Some(index) = index {
+		let mut result = String::with_capacity(value.len());
+		result.push_str(&value[.. index]);
+		result.extend(value[index ..].chars().filter_map(map));
+
+		Cow::Owned(result)
+	}
+	else {
+		Cow::Borrowed(value)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::borrow::Cow;
+	use crate::normalize;
+
+	#[test]
+	fn normalize_digits_only() {
+		assert_eq!("034562", normalize::normalize_digits_only("034-56&+#2"));
+		assert_eq!("420", normalize::normalize_digits_only("４2０"));
+		assert_eq!("0123", normalize::normalize_digits_only("٠١٢٣"));
+
+		assert!(matches!(normalize::normalize_digits_only("6502530000"), Cow::Borrowed(_)));
+	}
+
+	#[test]
+	fn normalize_diallable_chars_only() {
+		assert_eq!("03*456+1#234", normalize::normalize_diallable_chars_only("03*4-56&+1a#234"));
+		assert_eq!("+420", normalize::normalize_diallable_chars_only("＋４2０"));
+
+		assert!(matches!(normalize::normalize_diallable_chars_only("+1*23#"), Cow::Borrowed(_)));
+	}
+
+	#[test]
+	fn extract_possible_number() {
+		assert_eq!("0800-345-600", normalize::extract_possible_number("Tel:0800-345-600"));
+		assert_eq!("650) 253-0000", normalize::extract_possible_number("(650) 253-0000..- .."));
+		assert_eq!("", normalize::extract_possible_number("Num-...."));
+	}
+
+	#[test]
+	fn is_alpha_number() {
+		assert!(normalize::is_alpha_number("1800 six-flags"));
+		assert!(normalize::is_alpha_number("1800 six-flags ext. 1234"));
+		assert!(normalize::is_alpha_number("+800 six-flags"));
+		assert!(normalize::is_alpha_number("180 six-flags"));
+
+		assert!(!normalize::is_alpha_number("1800 123-1234"));
+		assert!(!normalize::is_alpha_number("1 six-flags"));
+		assert!(!normalize::is_alpha_number("18 six-flags"));
+		assert!(!normalize::is_alpha_number("1800 123-1234 extension: 1234"));
+		assert!(!normalize::is_alpha_number("+800 1234-1234"));
+	}
+}
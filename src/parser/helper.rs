@@ -13,10 +13,11 @@
 // limitations under the License.
 
 use std::borrow::Cow;
+use std::ops::Range;
 use nom::{self, AsChar, IResult, error::{make_error, ErrorKind}, character::complete::*, combinator::*, multi::*};
 
 use fnv::FnvHashMap;
-use regex_cache::CachedRegex;
+use regex::Regex;
 
 use crate::error;
 use crate::consts;
@@ -24,6 +25,7 @@ use crate::metadata::{Database, Metadata};
 use crate::country;
 use crate::phone_number::Type;
 use crate::validator;
+use crate::trace::{Tracer, Decision};
 
 macro_rules! parse {
 	($input:ident => ) => ();
@@ -60,8 +62,10 @@ pub struct Number<'a> {
 	pub country:   country::Source,
 	pub national:  Cow<'a, str>,
 	pub prefix:    Option<Cow<'a, str>>,
-	pub extension: Option<Cow<'a, str>>,
-	pub carrier:   Option<Cow<'a, str>>,
+	pub extension:  Option<Cow<'a, str>>,
+	pub carrier:    Option<Cow<'a, str>>,
+	pub subaddress: Option<Cow<'a, str>>,
+	pub domain:     Option<Cow<'a, str>>,
 }
 
 pub fn eof(i: &str) -> IResult<&str, ()> {
@@ -109,7 +113,7 @@ pub fn ignore_plus(i: &str) -> IResult<&str, &str> {
 /// x302 and (530) 583-6985 x2303. We remove the second extension so that the
 /// first number is parsed correctly.
 pub fn extract(value: &str) -> IResult<&str, &str> {
-	let (mut result, start) = if let Some(index) = consts::VALID_START_CHAR.find(&value) {
+	let (mut result, start) = if let Some(index) = consts::VALID_START_CHAR.find(value) {
 		(&value[index.start() ..], index.start())
 	}
 	else {
@@ -125,37 +129,89 @@ pub fn extract(value: &str) -> IResult<&str, &str> {
 	}
 
 	if result.is_empty() {
-		return Err(nom::Err::Error(make_error(value, ErrorKind::RegexpMatch)))
+		Err(nom::Err::Error(make_error(value, ErrorKind::RegexpMatch)))
 	}
 	else {
 		Ok((&value[start + result.len() ..], result))
 	}
 }
 
+/// Maps the digits of a number, as they end up after normalization, back to
+/// the bytes they were written as.
+pub struct Locator {
+	length: usize,
+	digits: Vec<Range<usize>>,
+}
+
+impl Locator {
+	pub fn new(value: &str) -> Self {
+		Locator {
+			length: value.len(),
+			digits: value.char_indices()
+				.filter(|&(_, c)| c.as_dec_digit().is_some() || consts::ALPHA_MAPPINGS.contains_key(&c))
+				.map(|(i, c)| i .. i + c.len_utf8())
+				.collect(),
+		}
+	}
+
+	/// Get the span of the whole value.
+	pub fn all(&self) -> Range<usize> {
+		0 .. self.length
+	}
+
+	/// Get the span of `count` digits, starting from the first of the last
+	/// `remaining` ones, falling back to the whole value if the digits cannot be
+	/// located.
+	pub fn span(&self, remaining: usize, count: usize) -> Range<usize> {
+		let total = self.digits.len();
+
+		if remaining == 0 || remaining > total {
+			return self.all();
+		}
+
+		let start = total - remaining;
+		let end   = (start + count.max(1)).min(total);
+
+		self.digits[start].start .. self.digits[end - 1].end
+	}
+}
+
 /// Parse and insert the proper country code.
-pub fn country_code<'a>(database: &Database, country: Option<country::Id>, mut number: Number<'a>) -> Result<Number<'a>, error::Parse> {
+///
+/// The span of any error is relative to the national part of the given
+/// `Number`.
+pub fn country_code<'a>(database: &Database, country: Option<country::Id>, mut number: Number<'a>, tracer: &mut Tracer) -> Result<Number<'a>, error::Parse> {
+	let locator = Locator::new(&number.national);
 	let idd = country
 		.and_then(|c| database.by_id(c.as_ref()))
 		.and_then(|m| m.international_prefix.as_ref());
 
-	number = international_prefix(idd, number);
+	number = international_prefix(idd, number, tracer);
 
 	match number.country {
 		// The country source was found from the initial PLUS or it was extract
 		// from the number already.
 		country::Source::Plus | country::Source::Idd | country::Source::Number => {
-			if number.national.len() <= consts::MIN_LENGTH_FOR_NSN {
-				return Err(error::Parse::TooShortNsn.into());
+			let length = number.national.len();
+
+			if length <= consts::MIN_LENGTH_FOR_NSN {
+				return Err(error::Parse::new(error::ParseKind::TooShortAfterIdd,
+					error::Stage::Idd, locator.span(length, length)));
 			}
 
 			// If the prefix was already extracted, check it is valid.
-			if number.prefix.is_some() {
-				let prefix = number.prefix.as_ref().unwrap().parse()?;
+			if let Some(prefix) = number.prefix.as_ref() {
+				let prefix = prefix.parse()
+					.map_err(|e| error::Parse::new(error::ParseKind::MalformedInteger(e),
+						error::Stage::CountryCode, locator.all()))?;
 
 				if database.by_code(&prefix).is_none() {
-					return Err(error::Parse::InvalidCountryCode.into());
+					return Err(error::Parse::new(error::ParseKind::InvalidCountryCode,
+						error::Stage::CountryCode, locator.all())
+						.with_code(Some(prefix)));
 				}
 				else {
+					tracer.record(|| Decision::CountryCode { code: prefix, source: number.country });
 					return Ok(number)
 				}
 			}
@@ -163,7 +219,8 @@ pub fn country_code<'a>(database: &Database, country: Option<country::Id>, mut n
 				// Check the possible country code does not start with a 0 since those
 				// are invalid.
 				if number.national.starts_with('0') {
-					return Err(error::Parse::InvalidCountryCode.into());
+					return Err(error::Parse::new(error::ParseKind::InvalidCountryCode,
+						error::Stage::CountryCode, locator.span(length, 1)));
 				}
 
 				// Try to find the first available country code.
@@ -171,6 +228,8 @@ pub fn country_code<'a>(database: &Database, country: Option<country::Id>, mut n
 					let code = number.national[.. len].parse().unwrap();
 
 					if database.by_code(&code).is_some() {
+						tracer.record(|| Decision::CountryCode { code, source: number.country });
+
 						number.national = trim(number.national, len);
 						number.prefix   = Some(code.to_string().into());
 
@@ -181,18 +240,28 @@ pub fn country_code<'a>(database: &Database, country: Option<country::Id>, mut n
 		}
 
 		country::Source::Default => {
-			if let Some(country) = country {
-				let meta = database.by_id(country.as_ref()).unwrap();
+			// A default region missing from the database is an invalid country
+			// code like no default region at all.
+			if let Some(meta) = country.and_then(|c| database.by_id(c.as_ref())) {
 				let code = meta.country_code.to_string();
 
-				if number.national.starts_with(&code) &&
-				   (!meta.descriptors().general().is_match(&number.national) ||
-				    !validator::length(meta, &number, Type::Unknown).is_possible())
-				{
-					number.country  = country::Source::Number;
-					number.national = trim(number.national, code.len());
+				if number.national.starts_with(&code) {
+					let general  = meta.descriptors().general().is_match(&number.national);
+					let possible = validator::length(meta, &number, Type::Unknown).is_possible();
+
+					if !general || !possible {
+						tracer.record(|| Decision::CountryCodeInNumber {
+							region: meta.id.clone(),
+							general,
+							possible,
+						});
+
+						number.country  = country::Source::Number;
+						number.national = trim(number.national, code.len());
+					}
 				}
 
+				tracer.record(|| Decision::CountryCode { code: meta.country_code, source: number.country });
 				number.prefix = Some(code.into());
 
 				return Ok(number);
@@ -200,7 +269,9 @@ pub fn country_code<'a>(database: &Database, country: Option<country::Id>, mut n
 		}
 	}
 
-	Err(error::Parse::InvalidCountryCode.into())
+	let length = number.national.len();
+	Err(error::Parse::new(error::ParseKind::InvalidCountryCode, error::Stage::CountryCode,
+		locator.span(length, consts::MAX_LENGTH_FOR_COUNTRY_CODE)))
 }
 
 /// Strip the IDD from a `Number`, update the country code source, and
@@ -208,7 +279,7 @@ pub fn country_code<'a>(database: &Database, country: Option<country::Id>, mut n
 ///
 /// Note that since the IDD comes from a passed default region, we can find the
 /// country code from the given default if the country source is from the IDD.
-pub fn international_prefix<'a>(idd: Option<&CachedRegex>, mut number: Number<'a>) -> Number<'a> {
+pub fn international_prefix<'a>(idd: Option<&Regex>, mut number: Number<'a>, tracer: &mut Tracer) -> Number<'a> {
 	// If there's a prefix already, i.e. RFC3966, just change the country source.
 	if number.prefix.is_some() {
 		number.country = country::Source::Plus;
@@ -248,6 +319,11 @@ pub fn international_prefix<'a>(idd: Option<&CachedRegex>, mut number: Number<'a
 				number.country = country::Source::Idd;
 			}
 
+			tracer.record(|| Decision::Idd {
+				pattern: idd.unwrap().as_str().into(),
+				prefix:  number.national[.. end].into(),
+			});
+
 			number.national = trim(number.national, end);
 		}
 	}
@@ -256,14 +332,25 @@ pub fn international_prefix<'a>(idd: Option<&CachedRegex>, mut number: Number<'a
 }
 
 /// Strip national prefix and extract carrier.
-pub fn national_number<'a>(meta: &Metadata, mut number: Number<'a>) -> Number<'a> {
+pub fn national_number<'a>(meta: &Metadata, mut number: Number<'a>, tracer: &mut Tracer) -> Number<'a> {
 	let transform = meta.national_prefix_transform_rule.as_ref();
 	let parsing   = if let Some(re) = meta.national_prefix_for_parsing.as_ref() {
 		re
 	}
 	else {
 		if let Some(prefix) = meta.national_prefix.as_ref() {
-			if number.national.starts_with(prefix) {
+			// Like the national prefix for parsing, it's kept if stripping it would
+			// make a valid number invalid.
+			let viable = || meta.descriptors.general.is_match(&number.national);
+
+			if number.national.starts_with(prefix) &&
+			   (meta.descriptors.general.is_match(&number.national[prefix.len() ..]) || !viable())
+			{
+				tracer.record(|| Decision::NationalPrefix {
+					region: meta.id.clone(),
+					prefix: prefix.clone(),
+				});
+
 				number.national = trim(number.national, prefix.len());
 			}
 		}
@@ -291,26 +378,42 @@ pub fn national_number<'a>(meta: &Metadata, mut number: Number<'a>) -> Number<'a
 		 c.get(c.len() - 1).map(|m| m.as_str().to_owned()))
 	}).unwrap();
 
-	if transform.is_none() || last.is_none() {
-		if viable && !meta.descriptors.general.is_match(&number.national[start ..]) {
+	if let (Some(transform), Some(_)) = (transform, last.as_ref()) {
+		let transformed = parsing.replace(&number.national, &**transform).into_owned();
+
+		if viable && !meta.descriptors.general.is_match(&transformed) {
 			return number;
 		}
 
-		if groups > 0 && last.is_some() {
-			number.carrier = Some(last.unwrap().into());
-		}
+		number.carrier  = Some(first.unwrap().into());
+		number.national = transformed.into();
 
-		number.national = trim(number.national, end);
+		tracer.record(|| Decision::NationalPrefixForParsing {
+			region:    meta.id.clone(),
+			pattern:   parsing.as_str().into(),
+			transform: Some(transform.clone()),
+			carrier:   number.carrier.as_ref().map(|c| c.to_string()),
+			national:  number.national.to_string(),
+		});
 	}
 	else {
-		let transformed = parsing.replace(&number.national, &**transform.unwrap()).into_owned();
-
-		if viable && !meta.descriptors.general.is_match(&transformed) {
+		if viable && !meta.descriptors.general.is_match(&number.national[start ..]) {
 			return number;
 		}
 
-		number.carrier  = Some(first.unwrap().into());
-		number.national = transformed.into();
+		if let Some(last) = last.filter(|_| groups > 0) {
+			number.carrier = Some(last.into());
+		}
+
+		number.national = trim(number.national, end);
+
+		tracer.record(|| Decision::NationalPrefixForParsing {
+			region:    meta.id.clone(),
+			pattern:   parsing.as_str().into(),
+			transform: None,
+			carrier:   number.carrier.as_ref().map(|c| c.to_string()),
+			national:  number.national.to_string(),
+		});
 	}
 
 	number
@@ -338,7 +441,7 @@ pub fn normalize<'a>(mut number: Number<'a>, mappings: &FnvHashMap<char, char>)
 						string.push(ch);
 					}
 
-					while let Some((_, ch)) = chars.next() {
+					for (_, ch) in chars.by_ref() {
 						if let Some(ch) = ch.as_dec_digit() {
 							string.push(ch);
 						}
@@ -374,6 +477,7 @@ pub fn trim(value: Cow<str>, start: usize) -> Cow<str> {
 	}
 }
 
+#[allow(dead_code, clippy::wrong_self_convention)]
 pub trait AsCharExt {
 	fn is_wide_digit(self) -> bool;
 	fn is_punctuation(self) -> bool;
@@ -387,7 +491,7 @@ pub trait AsCharExt {
 impl<T: AsChar> AsCharExt for T {
 	fn is_wide_digit(self) -> bool {
 		let ch = self.as_char();
-		ch >= '０' && ch <= '９'
+		('０'..='９').contains(&ch)
 	}
 
 	fn is_punctuation(self) -> bool {
@@ -457,13 +561,14 @@ impl<T: AsChar> AsCharExt for T {
 
 #[cfg(test)]
 mod test {
-	use regex_cache::CachedRegex;
+	use regex::Regex;
 
 	use crate::consts;
 	use crate::parser::helper;
 	use crate::parser::helper::*;
 	use crate::country;
 	use crate::metadata::{DATABASE};
+	use crate::trace::Tracer;
 
 	#[test]
 	fn punctuation() {
@@ -502,7 +607,7 @@ mod test {
     // Dashes are not possible start values and should be removed.
     assert_eq!("\u{FF11}\u{FF12}\u{FF13}", helper::extract("Num-\u{FF11}\u{FF12}\u{FF13}").unwrap().1);
     // If not possible number present, return empty string.
-    assert!(!helper::extract("Num-....").is_ok());
+    assert!(helper::extract("Num-....").is_err());
     // Leading brackets are stripped - these are not used when parsing.
     assert_eq!("650) 253-0000", helper::extract("(650) 253-0000").unwrap().1);
 
@@ -521,12 +626,12 @@ mod test {
 			prefix:   Some("1".into()),
 
 			.. Default::default()
-		}, helper::country_code(&*DATABASE, Some(country::US),
+		}, helper::country_code(&DATABASE, Some(country::US),
 			Number {
 				national: "011112-3456789".into(),
 
 				.. Default::default()
-			}).unwrap());
+			}, &mut Tracer::default()).unwrap());
 
 		assert_eq!(Number {
 			country:  country::Source::Plus,
@@ -534,12 +639,12 @@ mod test {
 			prefix:   Some("64".into()),
 
 			.. Default::default()
-		}, helper::country_code(&*DATABASE, Some(country::US),
+		}, helper::country_code(&DATABASE, Some(country::US),
 			Number {
 				national: "+6423456789".into(),
 
 				.. Default::default()
-			}).unwrap());
+			}, &mut Tracer::default()).unwrap());
 
 		assert_eq!(Number {
 			country:  country::Source::Plus,
@@ -547,12 +652,12 @@ mod test {
 			prefix:   Some("800".into()),
 
 			.. Default::default()
-		}, helper::country_code(&*DATABASE, Some(country::US),
+		}, helper::country_code(&DATABASE, Some(country::US),
 			Number {
 				national: "+80012345678".into(),
 
 				.. Default::default()
-			}).unwrap());
+			}, &mut Tracer::default()).unwrap());
 
 		assert_eq!(Number {
 			country:  country::Source::Default,
@@ -560,19 +665,19 @@ mod test {
 			prefix:   Some("1".into()),
 
 			.. Default::default()
-		}, helper::country_code(&*DATABASE, Some(country::US),
+		}, helper::country_code(&DATABASE, Some(country::US),
 			Number {
 				national: "2345-6789".into(),
 
 				.. Default::default()
-			}).unwrap());
+			}, &mut Tracer::default()).unwrap());
 
-		assert!(helper::country_code(&*DATABASE, Some(country::US),
+		assert!(helper::country_code(&DATABASE, Some(country::US),
 			Number {
 				national: "0119991123456789".into(),
 
 				.. Default::default()
-			}).is_err());
+			}, &mut Tracer::default()).is_err());
 
 		assert_eq!(Number {
 			national: "6106194466".into(),
@@ -580,12 +685,12 @@ mod test {
 			country:  country::Source::Number,
 
 			.. Default::default()
-		}, helper::country_code(&*DATABASE, Some(country::US),
+		}, helper::country_code(&DATABASE, Some(country::US),
 			Number {
 				national: "(1 610) 619 4466".into(),
 
 				.. Default::default()
-			}).unwrap());
+			}, &mut Tracer::default()).unwrap());
 
 		assert_eq!(Number {
 			national: "3298888888".into(),
@@ -593,12 +698,12 @@ mod test {
 			country:  country::Source::Number,
 
 			.. Default::default()
-		}, helper::country_code(&*DATABASE, Some(country::IT),
+		}, helper::country_code(&DATABASE, Some(country::IT),
 			Number {
 				national: "393298888888".into(),
 
 				.. Default::default()
-			}).unwrap());
+			}, &mut Tracer::default()).unwrap());
 	}
 
 	#[test]
@@ -626,58 +731,58 @@ mod test {
 			national: "45677003898003".into(),
 
 			.. Default::default()
-		}, helper::international_prefix(Some(&CachedRegex::new(DATABASE.cache(), "00[39]").unwrap()),
+		}, helper::international_prefix(Some(&Regex::new("00[39]").unwrap()),
 			Number {
 				national: "0034567700-3898003".into(),
 
 				.. Default::default()
-			}));
+			}, &mut Tracer::default()));
 
 		assert_eq!(Number {
 			country:  country::Source::Idd,
 			national: "45677003898003".into(),
 
 			.. Default::default()
-		}, helper::international_prefix(Some(&CachedRegex::new(DATABASE.cache(), "00[39]").unwrap()),
+		}, helper::international_prefix(Some(&Regex::new("00[39]").unwrap()),
 			Number {
 				national: "00945677003898003".into(),
 
 				.. Default::default()
-			}));
+			}, &mut Tracer::default()));
 
 		assert_eq!(Number {
 			country:  country::Source::Idd,
 			national: "45677003898003".into(),
 
 			.. Default::default()
-		}, helper::international_prefix(Some(&CachedRegex::new(DATABASE.cache(), "00[39]").unwrap()),
+		}, helper::international_prefix(Some(&Regex::new("00[39]").unwrap()),
 			Number {
 				national: "00 9 45677003898003".into(),
 
 				.. Default::default()
-			}));
+			}, &mut Tracer::default()));
 
 		assert_eq!(Number {
 			national: "45677003898003".into(),
 
 			.. Default::default()
-		}, helper::international_prefix(Some(&CachedRegex::new(DATABASE.cache(), "00[39]").unwrap()),
+		}, helper::international_prefix(Some(&Regex::new("00[39]").unwrap()),
 			Number {
 				national: "45677003898003".into(),
 
 				.. Default::default()
-			}));
+			}, &mut Tracer::default()));
 
 		assert_eq!(Number {
 			country:  country::Source::Plus,
 			national: "45677003898003".into(),
 
 			.. Default::default()
-		}, helper::international_prefix(Some(&CachedRegex::new(DATABASE.cache(), "00[39]").unwrap()),
+		}, helper::international_prefix(Some(&Regex::new("00[39]").unwrap()),
 			Number {
 				national: "+45677003898003".into(),
 
-			.. Default::default()
-			}));
+				.. Default::default()
+			}, &mut Tracer::default()));
 	}
 }
@@ -12,79 +12,317 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::metadata::{DATABASE, Database};
+use crate::metadata::{DATABASE, Database, CLASSIFIED};
 use crate::phone_number::{PhoneNumber, Type};
 use crate::national_number::NationalNumber;
 use crate::country;
 use crate::extension::Extension;
 use crate::carrier::Carrier;
+use crate::subaddress::Subaddress;
+use crate::domain::Domain;
+use crate::vanity;
+use crate::normalize;
 use crate::consts;
 use crate::validator::{self, Validation};
+use crate::context::Context;
+use crate::trace::{Tracer, Trace, Decision, Syntax};
 use crate::error;
+use self::helper::AsCharExt;
 
-use nom::{IResult, branch::alt};
+use std::borrow::Cow;
+use std::ops::Range;
+
+use nom::{IResult, branch::alt, combinator::map};
 
 #[macro_use]
 pub mod helper;
 pub mod valid;
 pub mod rfc3966;
+pub mod sip;
 pub mod natural;
 
 /// Parse a phone number.
 pub fn parse<S: AsRef<str>>(country: Option<country::Id>, string: S) -> Result<PhoneNumber, error::Parse> {
-	parse_with(&*DATABASE, country, string)
+	parse_with(&DATABASE, country, string)
 }
 
 /// Parse a phone number using a specific `Database`.
 pub fn parse_with<S: AsRef<str>>(database: &Database, country: Option<country::Id>, string: S) -> Result<PhoneNumber, error::Parse> {
-	fn phone_number(i: &str) -> IResult<&str, helper::Number> {
-		parse! { i => alt((rfc3966::phone_number, natural::phone_number)) }
+	parse_traced(database, country, None, string.as_ref(), &mut Tracer::default())
+}
+
+/// Parse a phone number in the given `Context`, completing local numbers with
+/// its area code.
+pub fn parse_in_context<S: AsRef<str>>(context: &Context, string: S) -> Result<PhoneNumber, error::Parse> {
+	parse_in_context_with(&DATABASE, context, string)
+}
+
+/// Parse a phone number in the given `Context` using a specific `Database`,
+/// completing local numbers with its area code.
+pub fn parse_in_context_with<S: AsRef<str>>(database: &Database, context: &Context, string: S) -> Result<PhoneNumber, error::Parse> {
+	parse_traced(database, Some(context.country()), context.area_code(), string.as_ref(), &mut Tracer::default())
+}
+
+/// Parse a phone number, explaining the decisions taken along the way.
+pub fn parse_explained<S: AsRef<str>>(country: Option<country::Id>, string: S) -> Result<(PhoneNumber, Trace), error::Parse> {
+	parse_explained_with(&DATABASE, country, string)
+}
+
+/// Parse a phone number using a specific `Database`, explaining the decisions
+/// taken along the way.
+pub fn parse_explained_with<S: AsRef<str>>(database: &Database, country: Option<country::Id>, string: S) -> Result<(PhoneNumber, Trace), error::Parse> {
+	let mut tracer = Tracer::enabled();
+	let number     = parse_traced(database, country, None, string.as_ref(), &mut tracer)?;
+
+	Ok((number, tracer.finish()))
+}
+
+fn parse_traced(database: &Database, country: Option<country::Id>, area_code: Option<&str>, string: &str, tracer: &mut Tracer) -> Result<PhoneNumber, error::Parse> {
+	if let Some(number) = parse_private(database, country, area_code, string, tracer) {
+		return number;
 	}
 
-	// Try to parse the number as RFC3966 or natural language.
-	let (_, mut number) = phone_number(string.as_ref())
-		.or(Err(error::Parse::NoNumber))?;
+	parse_public(database, country, area_code, string, tracer)
+}
 
-	// Normalize the number and extract country code.
-	number = helper::country_code(database, country, number)?;
+/// Try the private numbering plans of the database in order.
+///
+/// Only numbers written with digits and punctuation alone can be private, a
+/// number starting with the outside line prefix of a plan is parsed as a public
+/// number without it, unless it's already public as written.
+fn parse_private(database: &Database, country: Option<country::Id>, area_code: Option<&str>, string: &str, tracer: &mut Tracer) -> Option<Result<PhoneNumber, error::Parse>> {
+	// Most databases have no plans, skip any work then.
+	database.private_plans().next()?;
+
+	let candidate = normalize::extract_possible_number(string);
+	if candidate.is_empty() || !candidate.chars().all(|c| c.as_dec_digit().is_some() || c.is_punctuation()) {
+		return None;
+	}
 
-	// Extract carrier and strip national prefix if present.
-	if let Some(meta) = country.and_then(|c| database.by_id(c.as_ref())) {
-		let mut potential = helper::national_number(meta, number.clone());
+	let digits = normalize::normalize_digits_only(candidate);
+
+	for plan in database.private_plans() {
+		if plan.is_match(&digits) {
+			tracer.record(|| Decision::PrivatePlan {
+				plan: plan.id().into(),
+			});
+
+			// The candidate is part of the input, so the error can point at it.
+			let value = match digits.parse() {
+				Ok(value) =>
+					value,
+
+				Err(e) => {
+					let start = candidate.as_ptr() as usize - string.as_ptr() as usize;
+
+					return Some(Err(error::Parse::new(error::ParseKind::MalformedInteger(e),
+						error::Stage::Length, start .. start + candidate.len())));
+				}
+			};
+
+			return Some(Ok(PhoneNumber {
+				code: country::Code {
+					value:  0,
+					source: country::Source::Default,
+				},
+
+				national: NationalNumber {
+					value,
+					zeros: digits.chars().take_while(|&c| c == '0').count() as u8,
+				},
+
+				extension:  None,
+				carrier:    None,
+				subaddress: None,
+				domain:     None,
+				vanity:     None,
+				completed:  false,
+				private:    Some(plan.id().into()),
+			}));
+		}
 
-		// Strip national prefix if present.
-		if let Some(prefix) = meta.national_prefix.as_ref() {
-			if potential.national.starts_with(prefix) {
-				potential.national = helper::trim(potential.national, prefix.len());
+		if let Some(prefix) = plan.outside_line().filter(|p| digits.len() > p.len() && digits.starts_with(p)) {
+			if is_public(database, country, area_code, string, &digits) {
+				return None;
 			}
+
+			tracer.record(|| Decision::OutsideLine {
+				plan:   plan.id().into(),
+				prefix: prefix.into(),
+			});
+
+			// Skip the digits of the prefix as written, the possible number starts
+			// with the first digit of the input.
+			let (index, last) = string.char_indices()
+				.filter(|(_, c)| c.as_dec_digit().is_some())
+				.nth(prefix.len() - 1)?;
+			let offset = index + last.len_utf8();
+
+			return Some(parse_public(database, country, area_code, &string[offset ..], tracer)
+				.map_err(|e| { let span = e.span(); e.with_span(offset + span.start .. offset + span.end) }));
+		}
+	}
+
+	None
+}
+
+/// Check if a number starting with an outside line prefix is public as written,
+/// so the prefix is part of it.
+///
+/// Emergency and short codes of the region, and numbers shorter than any of its
+/// public numbers, are dialled as they are, like valid public numbers.
+fn is_public(database: &Database, country: Option<country::Id>, area_code: Option<&str>, string: &str, digits: &str) -> bool {
+	if let Some(meta) = country.and_then(|c| database.by_id(c.as_ref())) {
+		let descriptors = meta.descriptors();
+
+		if descriptors.emergency().into_iter().chain(descriptors.short_code()).any(|d| d.is_match(digits)) {
+			return true;
+		}
+
+		let shortest = CLASSIFIED.iter()
+			.filter_map(|&kind| descriptors.get(kind))
+			.flat_map(|d| d.possible_length().iter().chain(d.possible_local_length()))
+			.min();
+
+		if shortest.map(|&length| digits.len() < length as usize).unwrap_or(false) {
+			return true;
 		}
+	}
+
+	parse_public(database, country, area_code, string, &mut Tracer::default())
+		.map(|number| number.is_valid_with(database))
+		.unwrap_or(false)
+}
+
+fn parse_public(database: &Database, country: Option<country::Id>, area_code: Option<&str>, string: &str, tracer: &mut Tracer) -> Result<PhoneNumber, error::Parse> {
+	fn phone_number(i: &str) -> IResult<&str, (Syntax, helper::Number<'_>)> {
+		parse! { i => alt((
+			map(sip::phone_number, |n| (Syntax::Sip, n)),
+			map(rfc3966::phone_number, |n| (Syntax::Rfc3966, n)),
+			map(natural::phone_number, |n| (Syntax::Natural, n)),
+		)) }
+	}
+
+	// Try to parse the number as a SIP URI, RFC3966 or natural language.
+	let (_, (syntax, mut number)) = phone_number(string)
+		.map_err(|_| error::Parse::new(error::ParseKind::NoNumber,
+			error::Stage::Extraction, 0 .. string.len()))?;
+
+	tracer.record(|| Decision::Syntax(syntax));
+
+	// Keep the number as written around, to recover any vanity spelling and to
+	// locate errors in the input.
+	let raw     = number.national.clone();
+	let locator = helper::Locator::new(&raw);
+
+	// Spans are relative to the number as written, move them to the input if
+	// the parser took it from there, otherwise point at the whole input.
+	let offset = match raw {
+		Cow::Borrowed(raw) =>
+			(raw.as_ptr() as usize).checked_sub(string.as_ptr() as usize)
+				.filter(|&o| o + raw.len() <= string.len()),
+
+		Cow::Owned(_) =>
+			None,
+	};
+	let locate = |span: Range<usize>| {
+		offset.map(|o| o + span.start .. o + span.end).unwrap_or(0 .. string.len())
+	};
+
+	// Normalize the number and extract country code.
+	number = helper::country_code(database, country, number, tracer)
+		.map_err(|e| { let span = locate(e.span()); e.with_span(span) })?;
+
+	let code = match number.prefix.as_ref().map(|p| p.parse()) {
+		Some(Ok(code)) =>
+			Some(code),
+
+		Some(Err(e)) =>
+			return Err(error::Parse::new(error::ParseKind::MalformedInteger(e),
+				error::Stage::CountryCode, 0 .. string.len())),
+
+		None =>
+			None,
+	};
+
+	let fail = |kind, stage, national: &str| {
+		let length = national.len();
+		error::Parse::new(kind, stage, locate(locator.span(length, length)))
+			.with_code(code)
+	};
+
+	// Extract carrier and strip national prefix if present.
+	let meta         = country.and_then(|c| database.by_id(c.as_ref()));
+	let mut stripped = false;
+	if let Some(meta) = meta {
+		// The national prefix is stripped once, falling back to it when there's no
+		// national prefix for parsing.
+		let potential = helper::national_number(meta, number.clone(), tracer);
 
 		if validator::length(meta, &potential, Type::Unknown) != Validation::TooShort {
-			number = potential;
+			stripped = potential.national != number.national;
+			number   = potential;
+		}
+		else if potential.national != number.national {
+			tracer.record(|| Decision::NationalPrefixRejected {
+				region: meta.id.clone(),
+			});
+		}
+	}
+
+	// Keep the spelling of the number as written, before any completion.
+	let vanity = vanity::extract(&raw, &number.national);
+
+	// Complete numbers only diallable locally with the area code of the context.
+	let mut completed = false;
+	if let (Some(meta), Some(area_code)) = (meta, area_code) {
+		if number.country == country::Source::Default &&
+		   validator::any_length(meta, &number) == Validation::IsPossibleLocalOnly
+		{
+			let mut full = number.clone();
+			full.national = format!("{}{}", area_code, number.national).into();
+
+			if validator::any_length(meta, &full) == Validation::IsPossible {
+				tracer.record(|| Decision::AreaCode {
+					region:    meta.id.clone(),
+					area_code: area_code.into(),
+				});
+
+				number    = full;
+				completed = true;
+			}
 		}
 	}
 
 	if number.national.len() < consts::MIN_LENGTH_FOR_NSN {
-		return Err(error::Parse::TooShortNsn.into());
+		let stage = if stripped { error::Stage::NationalPrefix } else { error::Stage::Length };
+		return Err(fail(error::ParseKind::TooShortNsn, stage, &number.national));
 	}
 
 	if number.national.len() > consts::MAX_LENGTH_FOR_NSN {
-		return Err(error::Parse::TooLong.into());
+		return Err(fail(error::ParseKind::TooLong, error::Stage::Length, &number.national));
 	}
 
 	Ok(PhoneNumber {
+		vanity,
+		completed,
+		private: None,
+
 		code: country::Code {
-			value:  number.prefix.map(|p| p.parse()).unwrap_or(Ok(0))?,
+			value:  code.unwrap_or(0),
 			source: number.country,
 		},
 
 		national: NationalNumber {
-			value: number.national.parse()?,
+			value: number.national.parse()
+				.map_err(|e| fail(error::ParseKind::MalformedInteger(e), error::Stage::Length, &number.national))?,
 			zeros: number.national.chars().take_while(|&c| c == '0').count() as u8,
 		},
 
-		extension: number.extension.map(|s| Extension(s.into_owned())),
-		carrier:   number.carrier.map(|s| Carrier(s.into_owned())),
+		extension:  number.extension.map(|s| Extension(s.into_owned())),
+		carrier:    number.carrier.map(|s| Carrier(s.into_owned())),
+		subaddress: number.subaddress.map(|s| Subaddress(s.into_owned())),
+		domain:     number.domain.map(|s| Domain(s.into_owned())),
 	})
 }
 
@@ -94,6 +332,9 @@ mod test {
 	use crate::phone_number::PhoneNumber;
 	use crate::national_number::NationalNumber;
 	use crate::country;
+	use crate::error;
+	use crate::trace::{Decision, Syntax};
+	use crate::context::Context;
 
 	#[test]
 	fn parse() {
@@ -108,8 +349,13 @@ mod test {
 				zeros: 0,
 			},
 
-			extension: None,
-			carrier:   None,
+			extension:  None,
+			carrier:    None,
+			subaddress: None,
+			domain:     None,
+			vanity:     None,
+			completed:  false,
+			private:    None,
 		};
 
 		number.code.source = country::Source::Default;
@@ -120,14 +366,22 @@ mod test {
 
 		number.code.source = country::Source::Plus;
 		assert_eq!(number, parser::parse(Some(country::NZ), "tel:03-331-6005;phone-context=+64").unwrap());
-		// FIXME: What the fuck is this.
-		// assert_eq!(number, parser::parse(Some(country::NZ), "tel:331-6005;phone-context=+64-3").unwrap());
-		// assert_eq!(number, parser::parse(Some(country::NZ), "tel:331-6005;phone-context=+64-3").unwrap());
+		assert_eq!(number, parser::parse(Some(country::NZ), "tel:331-6005;phone-context=+64-3").unwrap());
+		assert_eq!(number, parser::parse(Some(country::US), "tel:331-6005;phone-context=+64-3").unwrap());
 		assert_eq!(number, parser::parse(Some(country::NZ), "tel:03-331-6005;phone-context=+64;a=%A1").unwrap());
-		assert_eq!(number, parser::parse(Some(country::NZ), "tel:03-331-6005;isub=12345;phone-context=+64").unwrap());
-		assert_eq!(number, parser::parse(Some(country::NZ), "tel:+64-3-331-6005;isub=12345").unwrap());
 		assert_eq!(number, parser::parse(Some(country::NZ), "03-331-6005;phone-context=+64").unwrap());
 
+		let mut subaddress = number.clone();
+		subaddress.subaddress = Some("12345".into());
+		assert_eq!(subaddress, parser::parse(Some(country::NZ), "tel:03-331-6005;isub=12345;phone-context=+64").unwrap());
+		assert_eq!(subaddress, parser::parse(Some(country::NZ), "tel:+64-3-331-6005;isub=12345").unwrap());
+		assert_eq!(subaddress, parser::parse(Some(country::NZ), "tel:+64-3-331-6005;isub=%31%32%33%34%35").unwrap());
+
+		let mut domain = number.clone();
+		domain.code.source = country::Source::Default;
+		domain.domain = Some("example.com".into());
+		assert_eq!(domain, parser::parse(Some(country::NZ), "tel:03-331-6005;phone-context=example.com").unwrap());
+
 		number.code.source = country::Source::Idd;
 		assert_eq!(number, parser::parse(Some(country::NZ), "0064 3 331 6005").unwrap());
 		assert_eq!(number, parser::parse(Some(country::US), "01164 3 331 6005").unwrap());
@@ -150,8 +404,13 @@ mod test {
 				zeros: 0,
 			},
 
-			extension: None,
-			carrier:   None,
+			extension:  None,
+			carrier:    None,
+			subaddress: None,
+			domain:     None,
+			vanity:     None,
+			completed:  false,
+			private:    None,
 		};
 
 		assert_eq!(number, parser::parse(Some(country::NZ), "64(0)64123456").unwrap());
@@ -167,8 +426,13 @@ mod test {
 				zeros: 0,
 			},
 
-			extension: None,
-			carrier:   None,
+			extension:  None,
+			carrier:    None,
+			subaddress: None,
+			domain:     None,
+			vanity:     None,
+			completed:  false,
+			private:    None,
 		}, parser::parse(Some(country::DE), "301/23456").unwrap());
 
 		assert_eq!(PhoneNumber {
@@ -182,8 +446,13 @@ mod test {
 				zeros: 0,
 			},
 
-			extension: None,
-			carrier:   None,
+			extension:  None,
+			carrier:    None,
+			subaddress: None,
+			domain:     None,
+			vanity:     None,
+			completed:  false,
+			private:    None,
 		}, parser::parse(Some(country::JP), "+81 *2345").unwrap());
 
 		assert_eq!(PhoneNumber {
@@ -197,8 +466,13 @@ mod test {
 				zeros: 0,
 			},
 
-			extension: None,
-			carrier:   None,
+			extension:  None,
+			carrier:    None,
+			subaddress: None,
+			domain:     None,
+			vanity:     None,
+			completed:  false,
+			private:    None,
 		}, parser::parse(Some(country::NZ), "12").unwrap());
 
 		assert_eq!(PhoneNumber {
@@ -212,8 +486,114 @@ mod test {
 				zeros: 0,
 			},
 
-			extension: None,
-			carrier:   Some("12".into()),
+			extension:  None,
+			carrier:    Some("12".into()),
+			subaddress: None,
+			domain:     None,
+			vanity:     None,
+			completed:  false,
+			private:    None,
 		}, parser::parse(Some(country::BR), "012 3121286979").unwrap());
+
+		assert_eq!(PhoneNumber {
+			code: country::Code {
+				value:  1,
+				source: country::Source::Number,
+			},
+
+			national: NationalNumber {
+				value: 8003569377,
+				zeros: 0,
+			},
+
+			extension:  None,
+			carrier:    None,
+			subaddress: None,
+			domain:     None,
+			vanity:     Some("800-FLOWERS".into()),
+			completed:  false,
+			private:    None,
+		}, parser::parse(Some(country::US), "1-800-FLOWERS").unwrap());
+
+		// The national prefix is only stripped once, even when the number starts
+		// with it too.
+		assert_eq!(8001234567, parser::parse(Some(country::RU), "8 800 123 45 67").unwrap()
+			.national().value());
+	}
+
+	#[test]
+	fn errors() {
+		let error = parser::parse(Some(country::US), "call me").unwrap_err();
+		assert!(matches!(error.kind(), error::ParseKind::NoNumber));
+		assert_eq!(error::Stage::Extraction, error.stage());
+		assert_eq!(0 .. 7, error.span());
+
+		let error = parser::parse(Some(country::US), "Call: 011 9991 123456").unwrap_err();
+		assert!(matches!(error.kind(), error::ParseKind::InvalidCountryCode));
+		assert_eq!(error::Stage::CountryCode, error.stage());
+		assert_eq!(10 .. 13, error.span());
+		assert_eq!(None, error.code());
+
+		let error = parser::parse(Some(country::US), "Call: 011 12").unwrap_err();
+		assert!(matches!(error.kind(), error::ParseKind::TooShortAfterIdd));
+		assert_eq!(error::Stage::Idd, error.stage());
+		assert_eq!(10 .. 12, error.span());
+
+		// The number is located where it was parsed from, even if it's also
+		// written earlier in the input.
+		let error = parser::parse(Some(country::US), r#""011-12" <sip:011-12@example.com>"#).unwrap_err();
+		assert!(matches!(error.kind(), error::ParseKind::TooShortAfterIdd));
+		assert_eq!(18 .. 20, error.span());
+
+		let error = parser::parse(Some(country::US), "+1 234 567 890 123 456 789").unwrap_err();
+		assert!(matches!(error.kind(), error::ParseKind::TooLong));
+		assert_eq!(error::Stage::Length, error.stage());
+		assert_eq!(3 .. 26, error.span());
+		assert_eq!(Some(1), error.code());
+	}
+
+	#[test]
+	fn explained() {
+		let (number, trace) = parser::parse_explained(Some(country::US), "(1 610) 619 4466").unwrap();
+		assert_eq!(number, parser::parse(Some(country::US), "(1 610) 619 4466").unwrap());
+		assert_eq!(&[
+			Decision::Syntax(Syntax::Natural),
+			Decision::CountryCodeInNumber { region: "US".into(), general: false, possible: false },
+			Decision::CountryCode { code: 1, source: country::Source::Number },
+		][..], &*trace);
+
+		let (_, trace) = parser::parse_explained(Some(country::US), "011 64 3 331 6005").unwrap();
+		assert_eq!(&[
+			Decision::Syntax(Syntax::Natural),
+			Decision::Idd { pattern: "011".into(), prefix: "011".into() },
+			Decision::CountryCode { code: 64, source: country::Source::Idd },
+		][..], &*trace);
+
+		let (_, trace) = parser::parse_explained(Some(country::BR), "012 3121286979").unwrap();
+		assert!(matches!(&trace[2], Decision::NationalPrefixForParsing {
+			region, transform: Some(transform), carrier: Some(carrier), national, ..
+		} if region == "BR" && transform == "$2" && carrier == "12" && national == "3121286979"));
+
+		let (_, trace) = parser::parse_explained(Some(country::NZ), "03-331 6005").unwrap();
+		assert_eq!(Some(&Decision::NationalPrefix { region: "NZ".into(), prefix: "0".into() }),
+			trace.last());
+	}
+
+	#[test]
+	fn in_context() {
+		let context = Context::new(country::US).with_area_code("650");
+		let number  = parser::parse_in_context(&context, "253-0000").unwrap();
+
+		assert_eq!(parser::parse(None, "+1 650 253 0000").unwrap().national(), number.national());
+		assert_eq!(country::Source::Default, number.code().source());
+		assert!(number.is_completed());
+
+		let number = parser::parse_in_context(&context, "(415) 253-0000").unwrap();
+		assert_eq!(4152530000, number.national().value());
+		assert!(!number.is_completed());
+
+		let number = parser::parse(Some(country::US), "253-0000").unwrap();
+		assert_eq!(2530000, number.national().value());
+		assert!(!number.is_completed());
 	}
 }
@@ -17,19 +17,19 @@ use nom::{IResult};
 use crate::consts;
 use crate::parser::helper::*;
 
-pub fn phone_number(i: &str) -> IResult<&str, Number> {
+pub fn phone_number(i: &str) -> IResult<&str, Number<'_>> {
 	let (_, i)    = try_parse!(i, extract);
-	let extension = consts::EXTN_PATTERN.captures(&i);
+	let extension = consts::EXTN_PATTERN.captures(i);
 
 	Ok(("", Number {
 		national: extension.as_ref()
 			.map(|c| &i[.. c.get(0).unwrap().start()])
-			.unwrap_or(&i)
+			.unwrap_or(i)
 			.into(),
 
 		extension: extension.as_ref()
-			.map(|c| c.get(2).unwrap().as_str())
-			.map(Into::into),
+			.and_then(|c| c.iter().skip(1).flatten().next())
+			.map(|m| m.as_str().into()),
 
 		.. Default::default()
 	}))
@@ -49,5 +49,33 @@ mod test {
 
 				.. Default::default()
 			});
+
+		for &(number, extension) in &[
+			("+7 495 123-45-67 доб. 123", "123"),
+			("03-1234-5678 内線 456", "456"),
+			("030 1234567 Durchwahl 89", "89"),
+			("01 23 45 67 89 poste 12", "12"),
+			("06 1234567 int. 3", "3"),
+			("650 253 0000;ext=4567", "4567"),
+			("650 253 0000 - 4567#", "4567"),
+			("030 1234567 Dw. 89", "89"),
+			("03-1234-5678内線456", "456"),
+		] {
+			assert_eq!(Some(extension.into()),
+				natural::phone_number(number).unwrap().1.extension);
+		}
+
+		// Short keywords need digits after them, and a separator before them
+		// unless they're written in a script without cases.
+		for &number in &[
+			"030 1234567 App",
+			"030 1234567 Dw.",
+			"020 123 4567 tst",
+			"010 1234 5678 转",
+			"0301234567 WhatsApp 12",
+			"0301234567 Thedw 12",
+		] {
+			assert_eq!(None, natural::phone_number(number).unwrap().1.extension, "{}", number);
+		}
 	}
 }
@@ -12,32 +12,54 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use nom::{self, IResult, AsChar, error::{make_error, ErrorKind}, character::complete::*, bytes::complete::*, combinator::*, multi::*};
+use nom::{self, IResult, AsChar, error::{make_error, ErrorKind}, character::complete::*, bytes::complete::*, combinator::*, multi::*, sequence::*};
+use std::borrow::Cow;
+use std::str;
 use fnv::FnvHashMap;
 
 use crate::parser::helper::*;
 
-pub fn phone_number(i: &str) -> IResult<&str, Number> {
+pub fn phone_number(i: &str) -> IResult<&str, Number<'_>> {
 	parse! { i =>
 		opt(tag_no_case("Tel:"));
+		telephone_subscriber
+	}
+}
+
+/// Parse the `telephone-subscriber` part of a `tel:` URI, which is also what
+/// makes up the user part of a SIP URI.
+pub fn telephone_subscriber(i: &str) -> IResult<&str, Number<'_>> {
+	parse! { i =>
 		let prefix = opt(prefix);
-		let national = take_while1(number);
+		let national = recognize(pair(opt(char('+')), take_while1(number)));
 		check;
 		let params = opt(parameters);
 	};
 
-	Ok((i, Number {
-		national: (*national).into(),
+	let mut params = params.unwrap_or_default();
+	let global     = prefix.is_some() || national.starts_with('+');
+
+	// A local number is only meaningful within its context, a global number
+	// context is prepended to the local number digits, while a domain name is
+	// kept around as is.
+	let (national, domain) = match params.remove("phone-context") {
+		Some(context) if !global && context.starts_with('+') =>
+			(Cow::Owned(format!("{}{}", context, national)), None),
+
+		Some(context) if !global =>
+			(Cow::Borrowed(national), Some(context)),
+
+		_ =>
+			(Cow::Borrowed(national), None),
+	};
 
-		prefix: prefix.or_else(||
-			params.as_ref()
-				.and_then(|m| m.get("phone-context"))
-				.map(|&s| if s.as_bytes()[0] == b'+' { &s[1 ..] } else { &s }))
-			.map(|cs| cs.into()),
+	Ok((i, Number {
+		national,
+		domain,
 
-		extension: params.as_ref()
-			.and_then(|m| m.get("ext"))
-			.map(|&cs| cs.into()),
+		prefix:     prefix.map(Into::into),
+		extension:  params.remove("ext"),
+		subaddress: params.remove("isub"),
 
 		.. Default::default()
 	}))
@@ -46,11 +68,14 @@ pub fn phone_number(i: &str) -> IResult<&str, Number> {
 fn prefix(i: &str) -> IResult<&str, &str> {
 	parse! { i =>
 		char('+');
-		take_till1(separator)
-	}
+		let prefix = digit1;
+		peek(one_of("-.()"));
+	};
+
+	Ok((i, prefix))
 }
 
-fn parameters(i: &str) -> IResult<&str, FnvHashMap<&str, &str>> {
+fn parameters(i: &str) -> IResult<&str, FnvHashMap<&str, Cow<'_, str>>> {
 	parse! { i =>
 		let params = many1(parameter);
 	};
@@ -58,7 +83,7 @@ fn parameters(i: &str) -> IResult<&str, FnvHashMap<&str, &str>> {
 	Ok((i, params.into_iter().collect()))
 }
 
-fn parameter(i: &str) -> IResult<&str, (&str, &str)> {
+fn parameter(i: &str) -> IResult<&str, (&str, Cow<'_, str>)> {
 	parse! { i =>
 		char(';');
 		let key = take_while(pname);
@@ -66,7 +91,61 @@ fn parameter(i: &str) -> IResult<&str, (&str, &str)> {
 		let value = take_while(pchar);
 	};
 
-	Ok((i, (key, value)))
+	Ok((i, (key, unescape(value))))
+}
+
+/// Percent-encode any character that cannot appear verbatim in a parameter
+/// value.
+pub fn escape(value: &str) -> Cow<'_, str> {
+	if value.chars().all(|c| c != '%' && pchar(c)) {
+		return Cow::Borrowed(value);
+	}
+
+	let mut result = String::with_capacity(value.len() * 3);
+
+	for c in value.chars() {
+		if c != '%' && pchar(c) {
+			result.push(c);
+		}
+		else {
+			let mut buffer = [0; 4];
+
+			for byte in c.encode_utf8(&mut buffer).bytes() {
+				result.push_str(&format!("%{:02X}", byte));
+			}
+		}
+	}
+
+	Cow::Owned(result)
+}
+
+/// Decode any percent-encoded octets in a parameter value.
+fn unescape(value: &str) -> Cow<'_, str> {
+	if !value.contains('%') {
+		return Cow::Borrowed(value);
+	}
+
+	let bytes      = value.as_bytes();
+	let mut result = Vec::with_capacity(bytes.len());
+	let mut index  = 0;
+
+	while index < bytes.len() {
+		let decoded = bytes.get(index + 1 .. index + 3)
+			.filter(|hex| bytes[index] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+			.and_then(|hex| str::from_utf8(hex).ok())
+			.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+		if let Some(byte) = decoded {
+			result.push(byte);
+			index += 3;
+		}
+		else {
+			result.push(bytes[index]);
+			index += 1;
+		}
+	}
+
+	Cow::Owned(String::from_utf8_lossy(&result).into_owned())
 }
 
 fn check(i: &str) -> IResult<&str, ()> {
@@ -83,7 +162,7 @@ fn pname(c: char) -> bool {
 }
 
 fn pchar(c: char) -> bool {
-	parameter_unreserved(c) || unreserved(c)
+	parameter_unreserved(c) || unreserved(c) || c == '%'
 }
 
 fn number(c: char) -> bool {
@@ -119,13 +198,21 @@ mod test {
 	fn phone_number() {
 		assert_eq!(rfc3966::phone_number("tel:2034567890;ext=456;phone-context=+44").unwrap().1,
 			Number {
-				national:  "2034567890".into(),
-				prefix:    Some("44".into()),
+				national:  "+442034567890".into(),
 				extension: Some("456".into()),
 
 				.. Default::default()
 			});
 
+		assert_eq!(rfc3966::phone_number("tel:7042;phone-context=example.com;isub=%31%2F2").unwrap().1,
+			Number {
+				national:   "7042".into(),
+				subaddress: Some("1/2".into()),
+				domain:     Some("example.com".into()),
+
+				.. Default::default()
+			});
+
 		assert_eq!(rfc3966::phone_number("tel:+64-3-331-6005;ext=1235").unwrap().1,
 			Number {
 				national:  "-3-331-6005".into(),
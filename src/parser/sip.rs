@@ -0,0 +1,89 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use nom::{IResult, branch::alt, character::complete::*, bytes::complete::*};
+
+use crate::parser::helper::*;
+use crate::parser::rfc3966;
+
+/// Parse a SIP or SIPS URI, either bare or in `name-addr` form, extracting the
+/// phone number from its user part.
+pub fn phone_number(i: &str) -> IResult<&str, Number<'_>> {
+	parse! { i => alt((name_addr, addr_spec)) }
+}
+
+/// Parse a `name-addr`, e.g. `"Alice" <sip:+14155550100@example.com>`, any
+/// trailing header parameters are left unparsed.
+fn name_addr(i: &str) -> IResult<&str, Number<'_>> {
+	parse! { i =>
+		take_till(|c| c == '<');
+		char('<');
+		let uri = take_till1(|c| c == '>');
+		char('>');
+	};
+
+	let (_, number) = addr_spec(uri)?;
+	Ok((i, number))
+}
+
+/// Parse an `addr-spec`, e.g. `sip:+14155550100;ext=12@example.com;user=phone`,
+/// the host and URI parameters are skipped.
+fn addr_spec(i: &str) -> IResult<&str, Number<'_>> {
+	parse! { i =>
+		alt((tag_no_case("sips:"), tag_no_case("sip:")));
+		let user = take_till1(|c| c == '@');
+		char('@');
+		take_till1(|c| c == ';' || c == '?' || c == '>');
+	};
+
+	let (remaining, number) = rfc3966::telephone_subscriber(user)?;
+	eof(remaining)?;
+
+	Ok((i, number))
+}
+
+#[cfg(test)]
+mod test {
+	use crate::parser::sip;
+	use crate::parser::helper::*;
+
+	#[test]
+	fn phone_number() {
+		assert_eq!(sip::phone_number("sip:+14155550100;ext=12@example.com;user=phone").unwrap().1,
+			Number {
+				national:  "+14155550100".into(),
+				extension: Some("12".into()),
+
+				.. Default::default()
+			});
+
+		assert_eq!(sip::phone_number("\"Alice\" <sips:+1-415-555-0100@example.com;user=phone>;tag=1928301774").unwrap().1,
+			Number {
+				national: "-415-555-0100".into(),
+				prefix:   Some("1".into()),
+
+				.. Default::default()
+			});
+
+		assert_eq!(sip::phone_number("<SIP:4155550100;phone-context=+1@example.com:5060>").unwrap().1,
+			Number {
+				national: "+14155550100".into(),
+
+				.. Default::default()
+			});
+
+		assert!(sip::phone_number("sip:alice@example.com").is_err());
+		assert!(sip::phone_number("tel:+14155550100").is_err());
+	}
+}
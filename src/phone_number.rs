@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 use std::ops::Deref;
 use either::*;
@@ -21,6 +22,9 @@ use crate::country;
 use crate::national_number::NationalNumber;
 use crate::extension::Extension;
 use crate::carrier::Carrier;
+use crate::subaddress::Subaddress;
+use crate::domain::Domain;
+use crate::vanity::Vanity;
 use crate::metadata::{DATABASE, Database, Metadata};
 use crate::parser;
 use crate::formatter;
@@ -28,7 +32,11 @@ use crate::validator;
 use crate::error;
 
 /// A phone number.
-#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Debug)]
+///
+/// Numbers are compared and hashed without the vanity spelling and whether
+/// they were completed from a `Context`, which only tell how the number was
+/// written.
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct PhoneNumber {
 	/// The country calling code for this number, as defined by the International
 	/// Telecommunication Union (ITU). For example, this would be 1 for NANPA
@@ -69,6 +77,31 @@ pub struct PhoneNumber {
 	/// Note this is the "preferred" code, which means other codes may work as
 	/// well.
 	pub(crate) carrier: Option<Carrier>,
+
+	/// The ISDN subaddress, as given by the `isub` parameter of an RFC3966
+	/// number.
+	#[serde(default)]
+	pub(crate) subaddress: Option<Subaddress>,
+
+	/// The domain name the number is local to, as given by a domain name
+	/// `phone-context` of an RFC3966 number.
+	#[serde(default)]
+	pub(crate) domain: Option<Domain>,
+
+	/// The vanity spelling of the national number, when it was written using
+	/// letters, e.g. "800-FLOWERS".
+	#[serde(default)]
+	pub(crate) vanity: Option<Vanity>,
+
+	/// Whether the national number was completed with the area code of a parse
+	/// `Context`.
+	#[serde(default)]
+	pub(crate) completed: bool,
+
+	/// The ID of the private numbering plan the number belongs to, private
+	/// numbers have no country code.
+	#[serde(default)]
+	pub(crate) private: Option<String>,
 }
 
 /// Wrapper to make it easier to access information about the country of a
@@ -79,10 +112,10 @@ pub struct Country<'a>(&'a PhoneNumber);
 #[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum Type {
-	///
+	/// Fixed line numbers.
 	FixedLine,
 
-	///
+	/// Mobile numbers.
 	Mobile,
 
 	/// In some regions (e.g. the USA), it is impossible to distinguish between
@@ -92,7 +125,7 @@ pub enum Type {
 	/// Freephone lines.
 	TollFree,
 
-	///
+	/// Premium rate lines.
 	PremiumRate,
 
 	/// The cost of this call is shared between the caller and the recipient, and
@@ -108,7 +141,7 @@ pub enum Type {
 	/// Voice over IP numbers. This includes TSoIP (Telephony Service over IP).
 	Voip,
 
-	///
+	/// Pager numbers.
 	Pager,
 
 	/// Used for "Universal Access Numbers" or "Company Numbers". They may be
@@ -116,22 +149,22 @@ pub enum Type {
 	/// company.
 	Uan,
 
-	///
+	/// Emergency numbers.
 	Emergency,
 
 	/// Used for "Voice Mail Access Numbers".
 	Voicemail,
 
-	///
+	/// Short codes.
 	ShortCode,
 
-	///
+	/// Standard rate short codes.
 	StandardRate,
 
-	///
+	/// Carrier specific short codes.
 	Carrier,
 
-	///
+	/// Numbers that cannot be dialled internationally.
 	NoInternational,
 
 	/// A phone number is of type UNKNOWN when it does not fit any of the known
@@ -147,6 +180,32 @@ impl FromStr for PhoneNumber {
 	}
 }
 
+impl PartialEq for PhoneNumber {
+	fn eq(&self, other: &PhoneNumber) -> bool {
+		self.code       == other.code &&
+		self.national   == other.national &&
+		self.extension  == other.extension &&
+		self.carrier    == other.carrier &&
+		self.subaddress == other.subaddress &&
+		self.domain     == other.domain &&
+		self.private    == other.private
+	}
+}
+
+impl Eq for PhoneNumber { }
+
+impl Hash for PhoneNumber {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.code.hash(state);
+		self.national.hash(state);
+		self.extension.hash(state);
+		self.carrier.hash(state);
+		self.subaddress.hash(state);
+		self.domain.hash(state);
+		self.private.hash(state);
+	}
+}
+
 impl fmt::Display for PhoneNumber {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		write!(f, "{}", self.format())
@@ -155,7 +214,7 @@ impl fmt::Display for PhoneNumber {
 
 impl PhoneNumber {
 	/// Get information about the country for the phone number.
-	pub fn country(&self) -> Country {
+	pub fn country(&self) -> Country<'_> {
 		Country(self)
 	}
 
@@ -179,6 +238,38 @@ impl PhoneNumber {
 		self.carrier.as_ref()
 	}
 
+	/// Get the ISDN subaddress.
+	pub fn subaddress(&self) -> Option<&Subaddress> {
+		self.subaddress.as_ref()
+	}
+
+	/// Get the domain the number is local to.
+	pub fn domain(&self) -> Option<&Domain> {
+		self.domain.as_ref()
+	}
+
+	/// Get the vanity spelling.
+	pub fn vanity(&self) -> Option<&Vanity> {
+		self.vanity.as_ref()
+	}
+
+	/// Whether the national number was completed with the area code of a parse
+	/// `Context`, i.e. the number was written in its local form.
+	pub fn is_completed(&self) -> bool {
+		self.completed
+	}
+
+	/// Get the ID of the private numbering plan the number belongs to, if it's
+	/// a private number.
+	pub fn private_plan(&self) -> Option<&str> {
+		self.private.as_ref().map(AsRef::as_ref)
+	}
+
+	/// Whether the number belongs to a private numbering plan.
+	pub fn is_private(&self) -> bool {
+		self.private.is_some()
+	}
+
 	/// Prepare a formatter for this `PhoneNumber`.
 	///
 	/// # Example
@@ -187,7 +278,7 @@ impl PhoneNumber {
 	/// use phonenumber::{self, country, Mode};
 	///
 	/// let number = phonenumber::parse(Some(country::DE), "301/23456").unwrap()
-	/// 	.format().mode(Mode::National).to_string();
+	///     .format().mode(Mode::National).to_string();
 	///
 	/// assert_eq!("030 123456", number);
 	/// ```
@@ -201,9 +292,13 @@ impl PhoneNumber {
 	}
 
 	/// Get the metadata that applies to this phone number from the given
-	/// database.
+	/// database, the one of its plan for a private number.
 	pub fn metadata<'a>(&self, database: &'a Database) -> Option<&'a Metadata> {
-		match try_opt!(None; validator::source_for(database, self.code.value(), &self.national.to_string())) {
+		if let Some(plan) = self.private_plan() {
+			return database.private_plan(plan).map(|p| p.metadata());
+		}
+
+		match try_opt!(None; validator::source_for(database, self.code.value(), &self.national.digits())) {
 			Left(region) =>
 				database.by_id(region.as_ref()),
 
@@ -229,7 +324,7 @@ impl<'a> Country<'a> {
 	}
 
 	pub fn id(&self) -> Option<country::Id> {
-		self.0.metadata(&*DATABASE).map(|m| m.id().parse().unwrap())
+		self.0.metadata(&DATABASE).map(|m| m.id().parse().unwrap())
 	}
 }
 
@@ -243,6 +338,9 @@ impl<'a> Deref for Country<'a> {
 
 #[cfg(test)]
 mod test {
+	use std::collections::HashSet;
+
+	use crate::phone_number::PhoneNumber;
 	use crate::parser;
 	use crate::country;
 
@@ -264,4 +362,30 @@ mod test {
 			parser::parse(None, "+16137827274").unwrap()
 				.country().id().unwrap());
 	}
+
+	#[test]
+	fn vanity() {
+		let vanity = parser::parse(Some(country::US), "1-800-FLOWERS").unwrap();
+		let digits = parser::parse(Some(country::US), "1-800-356-9377").unwrap();
+
+		assert!(vanity.vanity().is_some());
+		assert_eq!(vanity, digits);
+		assert_eq!(1, vec![vanity, digits].into_iter().collect::<HashSet<_>>().len());
+	}
+
+	#[test]
+	fn deserialize() {
+		// Numbers serialized before the newer fields existed still load.
+		let number: PhoneNumber = serde_json::from_str(r#"{
+			"code":      { "value": 1, "source": "plus" },
+			"national":  { "value": 6502530000, "zeros": 0 },
+			"extension": null,
+			"carrier":   null
+		}"#).unwrap();
+
+		assert_eq!(parser::parse(None, "+1 650 253 0000").unwrap(), number);
+		assert_eq!(None, number.subaddress());
+		assert_eq!(None, number.domain());
+		assert_eq!(None, number.vanity());
+	}
 }
@@ -0,0 +1,46 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Deref;
+use std::fmt;
+
+/// An ISDN subaddress of a phone number.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Debug)]
+pub struct Subaddress(pub(crate) String);
+
+impl<T: Into<String>> From<T> for Subaddress {
+	fn from(value: T) -> Subaddress {
+		Subaddress(value.into())
+	}
+}
+
+impl Deref for Subaddress {
+	type Target = str;
+
+	fn deref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl AsRef<str> for Subaddress {
+	fn as_ref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl fmt::Display for Subaddress {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
@@ -0,0 +1,221 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::ops::Deref;
+
+use crate::country;
+
+/// The ordered decisions taken while parsing a phone number, see
+/// `parse_explained`.
+#[derive(Clone, Eq, PartialEq, Default, Debug)]
+pub struct Trace(pub(crate) Vec<Decision>);
+
+/// The syntax a phone number was written in.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Syntax {
+	/// A SIP or SIPS URI.
+	Sip,
+
+	/// An RFC3966 URI or telephone subscriber.
+	Rfc3966,
+
+	/// Natural language.
+	Natural,
+}
+
+/// A single decision taken while parsing.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Decision {
+	/// The sub-parser that matched the input.
+	Syntax(Syntax),
+
+	/// The international dialing prefix matched and was stripped.
+	Idd {
+		/// The `internationalPrefix` pattern of the default country.
+		pattern: String,
+
+		/// The stripped prefix.
+		prefix: String,
+	},
+
+	/// The country code of the default country was found at the start of the
+	/// number and taken from it, because the number without it was a better
+	/// fit for the metadata.
+	CountryCodeInNumber {
+		/// The default country.
+		region: String,
+
+		/// Whether the number with the country code matched the general
+		/// `nationalNumberPattern`.
+		general: bool,
+
+		/// Whether the number with the country code had a possible length.
+		possible: bool,
+	},
+
+	/// The country code was found.
+	CountryCode {
+		/// The country code.
+		code: u16,
+
+		/// Where the country code came from.
+		source: country::Source,
+	},
+
+	/// The `nationalPrefixForParsing` pattern matched.
+	NationalPrefixForParsing {
+		/// The country whose metadata was used.
+		region: String,
+
+		/// The `nationalPrefixForParsing` pattern.
+		pattern: String,
+
+		/// The `nationalPrefixTransformRule`, if it was applied.
+		transform: Option<String>,
+
+		/// The carrier code extracted, if any.
+		carrier: Option<String>,
+
+		/// The national number left.
+		national: String,
+	},
+
+	/// The `nationalPrefix` was stripped.
+	NationalPrefix {
+		/// The country whose metadata was used.
+		region: String,
+
+		/// The `nationalPrefix`.
+		prefix: String,
+	},
+
+	/// The local number was completed with the area code of the parse context.
+	AreaCode {
+		/// The country of the context.
+		region: String,
+
+		/// The area code of the context.
+		area_code: String,
+	},
+
+	/// Stripping the national prefix was undone, because the number left was
+	/// too short for the country.
+	NationalPrefixRejected {
+		/// The country whose metadata was used.
+		region: String,
+	},
+
+	/// The number belongs to a private numbering plan.
+	PrivatePlan {
+		/// The ID of the plan.
+		plan: String,
+	},
+
+	/// The outside line prefix of a private numbering plan was stripped, the
+	/// rest is a public number.
+	OutsideLine {
+		/// The ID of the plan.
+		plan: String,
+
+		/// The stripped prefix.
+		prefix: String,
+	},
+}
+
+/// Records decisions only when tracing was asked for.
+#[derive(Default, Debug)]
+pub(crate) struct Tracer(Option<Vec<Decision>>);
+
+impl Tracer {
+	pub fn enabled() -> Self {
+		Tracer(Some(Vec::new()))
+	}
+
+	pub fn record<F: FnOnce() -> Decision>(&mut self, decision: F) {
+		if let Some(decisions) = self.0.as_mut() {
+			decisions.push(decision());
+		}
+	}
+
+	pub fn finish(self) -> Trace {
+		Trace(self.0.unwrap_or_default())
+	}
+}
+
+impl Deref for Trace {
+	type Target = [Decision];
+
+	fn deref(&self) -> &[Decision] {
+		&self.0
+	}
+}
+
+impl fmt::Display for Trace {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for (index, decision) in self.0.iter().enumerate() {
+			writeln!(f, "{}. {}", index + 1, decision)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl fmt::Display for Decision {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Decision::Syntax(syntax) =>
+				write!(f, "parsed as {:?}", syntax),
+
+			Decision::Idd { pattern, prefix } =>
+				write!(f, "stripped IDD {:?} matching {:?}", prefix, pattern),
+
+			Decision::CountryCodeInNumber { region, general, possible } =>
+				write!(f, "took the country code of {} from the number (general pattern matched: {}, possible length: {})",
+					region, general, possible),
+
+			Decision::CountryCode { code, source } =>
+				write!(f, "country code {} from {:?}", code, source),
+
+			Decision::NationalPrefixForParsing { region, pattern, transform, carrier, national } => {
+				write!(f, "national prefix for parsing of {} {:?} matched", region, pattern)?;
+
+				if let Some(transform) = transform {
+					write!(f, ", transformed with {:?}", transform)?;
+				}
+
+				if let Some(carrier) = carrier {
+					write!(f, ", carrier {:?}", carrier)?;
+				}
+
+				write!(f, ", leaving {:?}", national)
+			}
+
+			Decision::NationalPrefix { region, prefix } =>
+				write!(f, "stripped national prefix {:?} of {}", prefix, region),
+
+			Decision::AreaCode { region, area_code } =>
+				write!(f, "completed the local number with area code {:?} of {}", area_code, region),
+
+			Decision::NationalPrefixRejected { region } =>
+				write!(f, "kept the national prefix, the number is too short for {} without it", region),
+
+			Decision::PrivatePlan { plan } =>
+				write!(f, "matched private numbering plan {}", plan),
+
+			Decision::OutsideLine { plan, prefix } =>
+				write!(f, "stripped outside line prefix {:?} of private numbering plan {}", prefix, plan),
+		}
+	}
+}
@@ -14,7 +14,7 @@
 
 use either::*;
 
-use crate::metadata::{DATABASE, Database, Metadata};
+use crate::metadata::{DATABASE, Database, Metadata, CLASSIFIED};
 use crate::country;
 use crate::phone_number::{Type, PhoneNumber};
 use crate::consts;
@@ -51,41 +51,26 @@ pub enum Validation {
 impl Validation {
 	/// Whether it's a possible number.
 	pub fn is_possible(&self) -> bool {
-		match *self {
+		matches!(*self,
 			Validation::IsPossible |
-			Validation::IsPossibleLocalOnly =>
-				true,
-
-			_ =>
-				false,
-		}
+			Validation::IsPossibleLocalOnly)
 	}
 
 	/// Whether it's an invalid number.
 	pub fn is_invalid(&self) -> bool {
-		match *self {
+		matches!(*self,
 			Validation::InvalidCountryCode |
 			Validation::TooShort |
 			Validation::InvalidLength |
-			Validation::TooLong =>
-				true,
-
-			_ =>
-				false,
-		}
+			Validation::TooLong)
 	}
 
 	/// Whether the length is invalid.
 	pub fn is_invalid_length(&self) -> bool {
-		match *self {
+		matches!(*self,
 			Validation::TooShort |
 			Validation::InvalidLength |
-			Validation::TooLong =>
-				true,
-
-			_ =>
-				false,
-		}
+			Validation::TooLong)
 	}
 }
 
@@ -102,21 +87,13 @@ pub fn is_viable<S: AsRef<str>>(string: S) -> bool {
 
 /// Check if the phone number is valid.
 pub fn is_valid(number: &PhoneNumber) -> bool {
-	is_valid_with(&*DATABASE, number)
+	is_valid_with(&DATABASE, number)
 }
 
 /// Check if the phone number is valid with the given `Database`.
 pub fn is_valid_with(database: &Database, number: &PhoneNumber) -> bool {
-	let code     = number.country().code();
-	let national = number.national.to_string();
-	let source   = try_opt!(false; source_for(database, code, &national));
-	let meta     = try_opt!(false; match source {
-		Left(region) =>
-			database.by_id(region.as_ref()),
-
-		Right(code) =>
-			database.by_code(&code).and_then(|m| m.into_iter().next()),
-	});
+	let national = number.national.digits();
+	let meta     = try_opt!(false; number.metadata(database));
 
 	number_type(meta, &national) != Type::Unknown
 }
@@ -129,10 +106,10 @@ pub fn length(meta: &Metadata, number: &ParseNumber, kind: Type) -> Validation {
 	let length   = number.national.len() as u16;
 	let local    = &desc.possible_local_length[..];
 	let possible = if desc.possible_length.is_empty() {
-		&desc.possible_length[..]
+		&meta.descriptors.general.possible_length[..]
 	}
 	else {
-		&meta.descriptors.general.possible_length[..]
+		&desc.possible_length[..]
 	};
 
 	if possible.is_empty() {
@@ -161,15 +138,40 @@ pub fn length(meta: &Metadata, number: &ParseNumber, kind: Type) -> Validation {
 	}
 }
 
+/// Check the length of the number against every number type of the region,
+/// rather than against the general description only, which may not carry any
+/// possible lengths.
+pub fn any_length(meta: &Metadata, number: &ParseNumber) -> Validation {
+	let results = [
+		Type::FixedLine, Type::Mobile, Type::TollFree, Type::PremiumRate,
+		Type::SharedCost, Type::PersonalNumber, Type::Voip, Type::Pager, Type::Uan,
+		Type::Voicemail,
+	].iter().filter(|&&kind| meta.descriptors().get(kind).is_some())
+		.map(|&kind| length(meta, number, kind))
+		.collect::<Vec<_>>();
+
+	if results.contains(&Validation::IsPossible) {
+		Validation::IsPossible
+	}
+	else if results.contains(&Validation::IsPossibleLocalOnly) {
+		Validation::IsPossibleLocalOnly
+	}
+	else {
+		length(meta, number, Type::Unknown)
+	}
+}
+
 /// Find the metadata source.
 pub fn source_for(database: &Database, code: u16, national: &str) -> Option<Either<country::Id, u16>> {
 	let regions = try_opt!(None; database.region(&code));
 
 	if regions.len() == 1 {
-		return if regions[0] == "001" {
+		let region = regions.clone().next().unwrap();
+
+		return if region == "001" {
 			Some(Right(code))
 		} else {
-			match regions[0].parse() {
+			match region.parse() {
 				Ok(value) => Some(Left(value)),
 				Err(_) => None,
 			}
@@ -195,57 +197,76 @@ pub fn source_for(database: &Database, code: u16, national: &str) -> Option<Eith
 }
 
 pub fn number_type(meta: &Metadata, value: &str) -> Type {
-	if !meta.descriptors.general.is_match(value) {
+	let descriptors = meta.descriptors();
+
+	// All descriptors are matched at once when the classifier can, and one by
+	// one by their regex otherwise.
+	if let Some(mask) = descriptors.classifier.as_ref().and_then(|c| c.prefixes(value)) {
+		return classify(meta, |kind| {
+			let index = CLASSIFIED.iter().position(|&k| k == kind).unwrap();
+
+			mask & (1 << index) != 0 &&
+				descriptors.get(kind).map(|d| d.is_possible_length(value)).unwrap_or(false)
+		});
+	}
+
+	classify(meta, |kind| descriptors.get(kind).map(|d| d.is_match(value)).unwrap_or(false))
+}
+
+/// Find the type of a number given which descriptors it matches,
+/// `Type::Unknown` standing for the general descriptor.
+fn classify<F: Fn(Type) -> bool>(meta: &Metadata, matches: F) -> Type {
+	if !matches(Type::Unknown) {
 		return Type::Unknown;
 	}
 
-	if meta.descriptors.premium_rate.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+	if matches(Type::PremiumRate) {
 		return Type::PremiumRate;
 	}
 
-	if meta.descriptors.toll_free.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+	if matches(Type::TollFree) {
 		return Type::TollFree;
 	}
 
-	if meta.descriptors.shared_cost.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+	if matches(Type::SharedCost) {
 		return Type::SharedCost;
 	}
 
-	if meta.descriptors.voip.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+	if matches(Type::Voip) {
 		return Type::Voip;
 	}
 
-	if meta.descriptors.personal_number.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+	if matches(Type::PersonalNumber) {
 		return Type::PersonalNumber;
 	}
 
-	if meta.descriptors.pager.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+	if matches(Type::Pager) {
 		return Type::Pager;
 	}
 
-	if meta.descriptors.uan.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+	if matches(Type::Uan) {
 		return Type::Uan;
 	}
 
-	if meta.descriptors.voicemail.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+	if matches(Type::Voicemail) {
 		return Type::Voicemail;
 	}
 
-	if meta.descriptors.fixed_line.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+	if matches(Type::FixedLine) {
 		if meta.descriptors.fixed_line.as_ref().map(|d| d.national_number.as_str()) ==
 		   meta.descriptors.mobile.as_ref().map(|d| d.national_number.as_str())
 		{
 			return Type::FixedLineOrMobile;
 		}
 
-		if meta.descriptors.mobile.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+		if matches(Type::Mobile) {
 			return Type::FixedLineOrMobile;
 		}
 
 		return Type::FixedLine;
 	}
 
-	if meta.descriptors.mobile.as_ref().map(|d| d.is_match(value)).unwrap_or(false) {
+	if matches(Type::Mobile) {
 		return Type::Mobile;
 	}
 
@@ -254,8 +275,10 @@ pub fn number_type(meta: &Metadata, value: &str) -> Type {
 
 #[cfg(test)]
 mod test {
-	use crate::validator;
-	use crate::parser;
+	use crate::validator::{self, Validation};
+	use crate::metadata::DATABASE;
+	use crate::parser::{self, helper::Number};
+	use crate::phone_number::Type;
 	use crate::country;
 
 	#[test]
@@ -299,4 +322,66 @@ mod test {
 		assert!(!validator::is_valid(&parser::parse(
 			None, "+800 123456789").unwrap()));
 	}
+
+	#[test]
+	fn length() {
+		fn number(national: &str) -> Number<'_> {
+			Number { national: national.into(), .. Default::default() }
+		}
+
+		let de = DATABASE.by_id("DE").unwrap();
+
+		// The lengths of the type are used when it has any.
+		assert_eq!(Validation::IsPossible,
+			validator::length(de, &number("15123456789"), Type::Mobile));
+		assert_eq!(Validation::TooShort,
+			validator::length(de, &number("1512345"), Type::Mobile));
+		assert_eq!(Validation::IsPossibleLocalOnly,
+			validator::length(de, &number("123"), Type::FixedLine));
+	}
+
+	#[test]
+	fn classifier() {
+		use std::fs::File;
+		use std::io::BufReader;
+
+		use crate::metadata::{Descriptor, LOADED, CLASSIFIED, loader};
+		use crate::phone_number::Type;
+
+		let path     = "assets/PhoneNumberMetadata.xml";
+		let database = &*LOADED;
+		let regions  = loader::load(BufReader::new(File::open(path).unwrap())).unwrap();
+
+		let regex = |desc: &Descriptor, value: &str|
+			desc.is_possible_length(value) &&
+			desc.national_number().find(value).map(|m| m.start() == 0).unwrap_or(false);
+
+		for region in regions {
+			let meta = database.by_code(&region.country_code.unwrap()).unwrap()
+				.find(|m| Some(m.id()) == region.id.as_deref()).unwrap();
+
+			assert!(meta.descriptors.classifier.is_some(), "{} has no classifier", meta.id());
+
+			let examples = CLASSIFIED.iter()
+				.chain(&[Type::Emergency, Type::ShortCode, Type::StandardRate, Type::Carrier, Type::NoInternational])
+				.filter_map(|&kind| meta.descriptors.get(kind))
+				.filter_map(|d| d.example())
+				.collect::<Vec<_>>();
+
+			for example in examples {
+				for kind in CLASSIFIED.iter() {
+					if let Some(desc) = meta.descriptors.get(*kind) {
+						assert_eq!(regex(desc, example), desc.is_match(example),
+							"{} {:?} {}", meta.id(), kind, example);
+					}
+				}
+
+				assert_eq!(
+					validator::classify(meta, |kind| meta.descriptors.get(kind)
+						.map(|d| regex(d, example)).unwrap_or(false)),
+					validator::number_type(meta, example),
+					"{} {}", meta.id(), example);
+			}
+		}
+	}
 }
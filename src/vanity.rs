@@ -0,0 +1,130 @@
+// Copyright (C) 2017 1aim GmbH
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::ops::Deref;
+use std::fmt;
+
+use crate::consts;
+use crate::parser::helper::AsCharExt;
+
+/// The vanity spelling of the national number, e.g. "800-FLOWERS".
+///
+/// Letters are upper-cased and grouping symbols are normalized, but otherwise
+/// it is kept as it was written.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Debug)]
+pub struct Vanity(pub(crate) String);
+
+impl<T: Into<String>> From<T> for Vanity {
+	fn from(value: T) -> Vanity {
+		Vanity(value.into())
+	}
+}
+
+impl Deref for Vanity {
+	type Target = str;
+
+	fn deref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl AsRef<str> for Vanity {
+	fn as_ref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl fmt::Display for Vanity {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+/// Convert any letters in the number to their keypad digit, leaving every other
+/// character as is, e.g. "1-800-FLOWERS" becomes "1-800-3569377".
+///
+/// The input is borrowed back if it contains no letters.
+pub fn convert_alpha_characters(value: &str) -> Cow<'_, str> {
+	if !value.chars().any(|c| consts::ALPHA_MAPPINGS.contains_key(&c)) {
+		return Cow::Borrowed(value);
+	}
+
+	Cow::Owned(value.chars()
+		.map(|c| consts::ALPHA_MAPPINGS.get(&c).cloned().unwrap_or(c))
+		.collect())
+}
+
+/// Extract the vanity spelling of the given national number from the raw
+/// input it was parsed from, if the input is a vanity number at all.
+pub(crate) fn extract(raw: &str, national: &str) -> Option<Vanity> {
+	if !consts::VALID_ALPHA_PHONE.is_match(raw) {
+		return None;
+	}
+
+	// Keep track of where each digit came from.
+	let mut digits  = String::new();
+	let mut offsets = Vec::new();
+
+	for (offset, c) in raw.char_indices() {
+		let digit = c.as_dec_digit()
+			.or_else(|| consts::ALPHA_MAPPINGS.get(&c).cloned());
+
+		if let Some(digit) = digit {
+			digits.push(digit);
+			offsets.push((offset, offset + c.len_utf8()));
+		}
+	}
+
+	// The national number is not what was written when a national prefix
+	// transform rule applied, in which case the spelling cannot be kept.
+	if !digits.ends_with(national) || national.is_empty() {
+		return None;
+	}
+
+	let start = offsets[digits.len() - national.len()].0;
+	let end   = offsets[offsets.len() - 1].1;
+
+	let spelling = raw[start .. end].chars()
+		.filter_map(|c| c.as_dec_digit()
+			.or_else(|| consts::ALL_PLUS_NUMBER_GROUPING_SYMBOLS.get(&c).cloned()))
+		.collect::<String>();
+
+	if spelling.chars().any(|c| c.is_ascii_alphabetic()) {
+		Some(Vanity(spelling))
+	}
+	else {
+		None
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::vanity;
+
+	#[test]
+	fn convert_alpha_characters() {
+		assert_eq!("1-800-3569377", vanity::convert_alpha_characters("1-800-FLOWERS"));
+		assert_eq!("1800 3569377", vanity::convert_alpha_characters("1800 flowers"));
+		assert_eq!("+1 650 253 0000", vanity::convert_alpha_characters("+1 650 253 0000"));
+	}
+
+	#[test]
+	fn extract() {
+		assert_eq!(Some("800-FLOWERS".into()), vanity::extract("1-800-FLOWERS", "8003569377"));
+		assert_eq!(Some("800 FLOWERS".into()), vanity::extract("+1 800 flowers!", "8003569377"));
+		assert_eq!(None, vanity::extract("1-800-356-9377", "8003569377"));
+		assert_eq!(None, vanity::extract("1-800-FLOWERS", "8003569"));
+	}
+}